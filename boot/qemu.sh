@@ -1,9 +1,10 @@
 #!/bin/sh
 
 TOP=$(git rev-parse --show-toplevel)
+QEMU_SERIAL=${QEMU_SERIAL:-"-serial null -serial pty"}
 $TOP/bin/qemu-system-aarch64 \
     -nographic \
     -M raspi3 \
-    -serial null -serial pty \
+    $QEMU_SERIAL \
     -kernel \
     "$@"
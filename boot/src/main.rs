@@ -6,6 +6,7 @@
 
 #[cfg(not(test))]
 mod init;
+mod net;
 
 use xmodem::Xmodem;
 use core::time::Duration;
@@ -74,9 +75,18 @@ fn kmain() -> ! {
         }
     }
     // FIXME: Implement the bootloader.
+    let mut bin = unsafe { core::slice::from_raw_parts_mut(BINARY_START, MAX_BINARY_SIZE) };
+
+    // Try the boot server first. On the Pi, the PL011 and the mini UART compete
+    // for GPIO 14 and 15; the mini UART takes them back for the fallback.
+    if let Some(config) = net::BootConfig::from_atags() {
+        if net::fetch_kernel(&config, bin).is_ok() {
+            unsafe { jump_to(BINARY_START); }
+        }
+    }
+
     let mut mu = pi::uart::MiniUart::new();
     mu.set_read_timeout(Duration::from_millis(750));
-    let mut bin = unsafe { core::slice::from_raw_parts_mut(BINARY_START, MAX_BINARY_SIZE) };
     loop {
         /*
         while mu.has_byte() {
@@ -0,0 +1,270 @@
+//! Downloads the kernel over TFTP, carried by IPv4 and UDP over SLIP on the
+//! PL011 UART.
+//!
+//! Only what a single download needs is implemented: there is no ARP, no
+//! routing and no fragmentation. The boot server must be on the other end
+//! of the serial line, e.g. a host running `sliptap` from `lib/sliptap`.
+//!
+//! The line runs at `DEFAULT_BAUD_RATE` unless the command line has a
+//! `baud=` argument, and `sliptap` must be started with the same rate (`-b`).
+//! Only at rates well above the mini UART's 115200 baud does the download
+//! beat XMODEM, since IP, UDP and SLIP add their own framing.
+//!
+//! On real hardware, the PL011 and the mini UART compete for GPIO 14 and 15:
+//! each switches the pins to itself when it is created. The serial adapter
+//! then talks to whichever UART was created last, so the XMODEM fallback on
+//! the mini UART only works after the download has given up.
+
+use core::time::Duration;
+
+use pi::atags::Atags;
+use pi::pl011::{Config, Pl011, UART_CLOCK};
+use pi::timer::current_time;
+use slip::{Decoder, MAX_PACKET_SIZE};
+use tftp::{Download, Event, MAX_BLOCK_SIZE};
+
+/// The file requested from the boot server.
+const KERNEL_FILENAME: &str = "kernel8.img";
+
+/// Our address unless the command line has an `ip=` argument; the address
+/// the kernel uses on the SLIP link.
+const DEFAULT_ADDRESS: [u8; 4] = [192, 168, 7, 2];
+
+/// The PL011's baud rate unless the command line has a `baud=` argument.
+const DEFAULT_BAUD_RATE: u32 = 921_600;
+
+/// How long to wait for a packet before sending the last one again.
+const PACKET_TIMEOUT: Duration = Duration::from_secs(1);
+
+const IP_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/// The offset of the UDP payload in a packet without IP options.
+const UDP_PAYLOAD: usize = IP_HEADER_LEN + UDP_HEADER_LEN;
+
+/// Where to download the kernel from, read from the ATAG command line.
+pub struct BootConfig {
+    /// Our IPv4 address, from `ip=a.b.c.d` or `ip=a.b.c.d/nn`
+    address: [u8; 4],
+    /// The TFTP server's IPv4 address, from `bootserver=a.b.c.d`
+    server: [u8; 4],
+    /// The PL011's baud rate, from `baud=n`
+    baud_rate: u32,
+}
+
+impl BootConfig {
+    /// Returns the configuration on the command line, or `None` if there is
+    /// no `bootserver=` argument.
+    pub fn from_atags() -> Option<BootConfig> {
+        let cmdline = Atags::get().find_map(|tag| tag.cmd())?;
+        let mut address = DEFAULT_ADDRESS;
+        let mut server = None;
+        let mut baud_rate = DEFAULT_BAUD_RATE;
+        for arg in cmdline.split(|c| c == ' ' || c == '\0') {
+            let mut parts = arg.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("ip"), Some(value)) => {
+                    let value = value.splitn(2, '/').next().unwrap_or(value);
+                    address = parse_ipv4(value).unwrap_or(address);
+                }
+                (Some("bootserver"), Some(value)) => server = parse_ipv4(value),
+                (Some("baud"), Some(value)) => {
+                    // The PL011 cannot divide its clock by less than 16.
+                    match value.parse() {
+                        Ok(rate) if rate > 0 && rate <= UART_CLOCK / 16 => baud_rate = rate,
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+        Some(BootConfig {
+            address,
+            server: server?,
+            baud_rate,
+        })
+    }
+}
+
+/// Parses a dotted-quad IPv4 address.
+fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut address = [0; 4];
+    let mut parts = s.split('.');
+    for byte in address.iter_mut() {
+        *byte = parts.next()?.parse().ok()?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(address),
+    }
+}
+
+/// Returns the internet checksum of `data`.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = match *chunk {
+            [hi, lo] => u16::from_be_bytes([hi, lo]),
+            [hi] => u16::from_be_bytes([hi, 0]),
+            _ => unreachable!(),
+        };
+        sum += word as u32;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// A UDP endpoint on the serial line.
+struct Link<'a> {
+    uart: Pl011,
+    decoder: Decoder,
+    config: &'a BootConfig,
+    port: u16,
+    /// The packet being sent or received
+    packet: [u8; MAX_PACKET_SIZE],
+}
+
+impl<'a> Link<'a> {
+    /// Sends `payload` to `port` on the boot server in a UDP datagram.
+    fn send(&mut self, port: u16, payload: &[u8]) {
+        let total_len = IP_HEADER_LEN + UDP_HEADER_LEN + payload.len();
+        let packet = &mut self.packet[..total_len];
+
+        let (ip, rest) = packet.split_at_mut(IP_HEADER_LEN);
+        ip.copy_from_slice(&[
+            0x45, 0, 0, 0, // version, header length, length
+            0, 0, 0x40, 0, // identification, don't fragment
+            64, IP_PROTOCOL_UDP, 0, 0, // TTL, protocol, checksum
+            0, 0, 0, 0, // source
+            0, 0, 0, 0, // destination
+        ]);
+        ip[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
+        ip[12..16].copy_from_slice(&self.config.address);
+        ip[16..20].copy_from_slice(&self.config.server);
+        let sum = checksum(ip);
+        ip[10..12].copy_from_slice(&sum.to_be_bytes());
+
+        // A UDP checksum of 0 means there is none.
+        let (udp, data) = rest.split_at_mut(UDP_HEADER_LEN);
+        udp[0..2].copy_from_slice(&self.port.to_be_bytes());
+        udp[2..4].copy_from_slice(&port.to_be_bytes());
+        udp[4..6].copy_from_slice(&((UDP_HEADER_LEN + payload.len()) as u16).to_be_bytes());
+        udp[6..8].copy_from_slice(&[0, 0]);
+        data.copy_from_slice(payload);
+
+        let uart = &mut self.uart;
+        slip::encode(packet, |byte| uart.write_byte(byte));
+    }
+
+    /// Waits up to `PACKET_TIMEOUT` for a UDP datagram from the boot server
+    /// to our port. Returns the source port and the payload length; the
+    /// payload is at `UDP_PAYLOAD` in `self.packet`.
+    fn receive(&mut self) -> Option<(u16, usize)> {
+        let end = current_time() + PACKET_TIMEOUT;
+        while current_time() <= end {
+            if !self.uart.has_byte() {
+                continue;
+            }
+            let byte = self.uart.read_byte();
+            let len = match self.decoder.push(byte) {
+                Some(packet) => {
+                    self.packet[..packet.len()].copy_from_slice(packet);
+                    packet.len()
+                }
+                None => continue,
+            };
+            if let Some(received) = self.parse(len) {
+                return Some(received);
+            }
+        }
+        None
+    }
+
+    /// Checks the IPv4 packet of length `len` in `self.packet` and returns
+    /// its UDP source port and payload length if it is for us.
+    fn parse(&self, len: usize) -> Option<(u16, usize)> {
+        let packet = &self.packet[..len];
+        if len < UDP_PAYLOAD || packet[0] != 0x45 || checksum(&packet[..IP_HEADER_LEN]) != 0 {
+            return None;
+        }
+        let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+        let fragmented = u16::from_be_bytes([packet[6], packet[7]]) & 0x3fff != 0;
+        if total_len > len || total_len < UDP_PAYLOAD || fragmented {
+            return None;
+        }
+        if packet[9] != IP_PROTOCOL_UDP
+            || packet[12..16] != self.config.server
+            || packet[16..20] != self.config.address
+        {
+            return None;
+        }
+
+        let udp = &packet[IP_HEADER_LEN..total_len];
+        let src_port = u16::from_be_bytes([udp[0], udp[1]]);
+        let dst_port = u16::from_be_bytes([udp[2], udp[3]]);
+        let udp_len = u16::from_be_bytes([udp[4], udp[5]]) as usize;
+        if dst_port != self.port || udp_len < UDP_HEADER_LEN || udp_len > udp.len() {
+            return None;
+        }
+        Some((src_port, udp_len - UDP_HEADER_LEN))
+    }
+}
+
+/// Why the kernel could not be downloaded.
+#[derive(Debug)]
+pub enum Error {
+    Tftp(tftp::Error),
+    /// The file does not fit in the buffer.
+    TooLarge,
+}
+
+impl From<tftp::Error> for Error {
+    fn from(error: tftp::Error) -> Error {
+        Error::Tftp(error)
+    }
+}
+
+/// Downloads `kernel8.img` from the boot server in `config` into `buf`.
+/// Returns the size of the kernel.
+pub fn fetch_kernel(config: &BootConfig, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut link = Link {
+        uart: Pl011::with_config(Config {
+            baud_rate: config.baud_rate,
+            ..Config::default()
+        }),
+        decoder: Decoder::new(),
+        config,
+        // An ephemeral port that differs between attempts, which keeps
+        // packets from an earlier download from being mistaken for ours.
+        port: 0xc000 | (current_time().as_micros() as u16 & 0x3fff),
+        packet: [0; MAX_PACKET_SIZE],
+    };
+
+    let mut download = Download::new(KERNEL_FILENAME, Some(MAX_BLOCK_SIZE))?;
+    let mut payload = [0u8; MAX_PACKET_SIZE];
+    link.send(download.destination_port(), download.packet());
+    while !download.is_done() {
+        let (from_port, len) = match link.receive() {
+            Some(received) => received,
+            None => {
+                download.timeout()?;
+                link.send(download.destination_port(), download.packet());
+                continue;
+            }
+        };
+        payload[..len].copy_from_slice(&link.packet[UDP_PAYLOAD..UDP_PAYLOAD + len]);
+        match download.receive(from_port, &payload[..len])? {
+            Event::Data { offset, data, .. } => {
+                let dest = buf.get_mut(offset..offset + data.len()).ok_or(Error::TooLarge)?;
+                dest.copy_from_slice(data);
+            }
+            Event::Reply => (),
+            Event::Ignored => continue,
+        }
+        link.send(download.destination_port(), download.packet());
+    }
+    Ok(download.received())
+}
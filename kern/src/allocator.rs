@@ -12,7 +12,7 @@ mod tests;
 use core::alloc::{GlobalAlloc, Layout};
 use core::fmt;
 
-use crate::mutex::Mutex;
+use crate::mutex::{Mutex, Once};
 use pi::atags::{Atag, Atags};
 
 /// `LocalAlloc` is an analogous trait to the standard library's `GlobalAlloc`,
@@ -23,7 +23,7 @@ pub trait LocalAlloc {
 }
 
 /// Thread-safe (locking) wrapper around a particular memory allocator.
-pub struct Allocator(Mutex<Option<AllocatorImpl>>);
+pub struct Allocator(Once<Mutex<AllocatorImpl>>);
 
 impl Allocator {
     /// Returns an uninitialized `Allocator`.
@@ -31,7 +31,7 @@ impl Allocator {
     /// The allocator must be initialized by calling `initialize()` before the
     /// first memory allocation. Failure to do will result in panics.
     pub const fn uninitialized() -> Self {
-        Allocator(Mutex::new(None))
+        Allocator(Once::new())
     }
 
     /// Initializes the memory allocator.
@@ -44,24 +44,24 @@ impl Allocator {
     pub unsafe fn initialize(&self) {
         let (start, end) = memory_map().expect("failed to find memory map");
         info!("heap beg: {:x}, end: {:x}", start, end);
-        *self.0.lock() = Some(AllocatorImpl::new(start, end));
+        self.0.call_once(|| Mutex::new(AllocatorImpl::new(start, end)));
     }
 }
 
 unsafe impl GlobalAlloc for Allocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         self.0
-            .lock()
-            .as_mut()
+            .get()
             .expect("allocator uninitialized")
+            .lock()
             .alloc(layout)
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         self.0
-            .lock()
-            .as_mut()
+            .get()
             .expect("allocator uninitialized")
+            .lock()
             .dealloc(ptr, layout);
     }
 }
@@ -89,8 +89,8 @@ pub fn memory_map() -> Option<(usize, usize)> {
 
 impl fmt::Debug for Allocator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.0.lock().as_mut() {
-            Some(ref alloc) => write!(f, "{:?}", alloc)?,
+        match self.0.get() {
+            Some(alloc) => write!(f, "{:?}", *alloc.lock())?,
             None => write!(f, "Not yet initialized")?,
         }
         Ok(())
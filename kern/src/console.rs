@@ -1,12 +1,226 @@
+use alloc::boxed::Box;
 use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use pi::atags::Atags;
+use pi::interrupt::{Controller, Interrupt};
+use pi::pl011::Pl011;
 use pi::uart::MiniUart;
 use shim::io;
 
 use crate::mutex::Mutex;
+use crate::traps::irq::IrqHandlerRegistry;
+use crate::GLOBAL_IRQ;
+
+mod ring;
+
+use self::ring::Ring;
+
+/// Bytes received from the UART but not yet read. Filled by `drain_rx()` and
+/// emptied by `Console` while `CONSOLE` is held.
+static RX: Ring = Ring::new();
+/// Bytes written to the console but not yet sent. Filled by `Console` while
+/// `CONSOLE` is held and emptied by `drain_tx()`.
+static TX: Ring = Ring::new();
+
+/// Set while a core is in `drain_rx()`, the only producer of `RX`.
+static RX_BUSY: AtomicBool = AtomicBool::new(false);
+/// Set while a core is in `drain_tx()`, the only consumer of `TX`.
+static TX_BUSY: AtomicBool = AtomicBool::new(false);
+/// The `Backend` the console was initialized with, or `0` before that.
+static BACKEND: AtomicU8 = AtomicU8::new(0);
+/// Set once the UART interrupt handler has been registered.
+static IRQ_READY: AtomicBool = AtomicBool::new(false);
+
+/// The UART device a console is built on.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Backend {
+    /// The auxiliary mini UART, `UART1`.
+    MiniUart = 1,
+    /// The PL011 UART, `UART0`.
+    Pl011 = 2,
+}
+
+impl Backend {
+    /// Returns the backend named by a `console=miniuart` or `console=pl011`
+    /// argument on the kernel command line. Without one, the PL011 is used if
+    /// the kernel was built with the `console-pl011` feature and the mini UART
+    /// otherwise.
+    fn select() -> Backend {
+        let arg = Atags::get()
+            .find_map(|tag| tag.cmd())
+            .and_then(|cmd| {
+                cmd.split(|c| c == ' ' || c == '\0')
+                    .find(|arg| arg.starts_with("console="))
+            });
+        match arg {
+            Some("console=pl011") => Backend::Pl011,
+            Some("console=miniuart") => Backend::MiniUart,
+            _ if cfg!(feature = "console-pl011") => Backend::Pl011,
+            _ => Backend::MiniUart,
+        }
+    }
+
+    /// Returns the interrupt the backend raises.
+    fn interrupt(self) -> Interrupt {
+        match self {
+            Backend::MiniUart => Interrupt::Aux,
+            Backend::Pl011 => Interrupt::Uart,
+        }
+    }
+}
+
+/// Returns the backend of the console, or `None` if the console has not been
+/// initialized yet.
+pub fn backend() -> Option<Backend> {
+    match BACKEND.load(Ordering::Acquire) {
+        1 => Some(Backend::MiniUart),
+        2 => Some(Backend::Pl011),
+        _ => None,
+    }
+}
+
+/// A handle to the UART of either backend.
+enum Uart {
+    MiniUart(MiniUart),
+    Pl011(Pl011),
+}
+
+impl Uart {
+    /// Initializes the UART of `backend`.
+    fn new(backend: Backend) -> Uart {
+        match backend {
+            Backend::MiniUart => Uart::MiniUart(MiniUart::new()),
+            Backend::Pl011 => Uart::Pl011(Pl011::new()),
+        }
+    }
+
+    /// Returns another handle to the already initialized UART of `backend`.
+    unsafe fn shared(backend: Backend) -> Uart {
+        match backend {
+            Backend::MiniUart => Uart::MiniUart(MiniUart::shared()),
+            Backend::Pl011 => Uart::Pl011(Pl011::shared()),
+        }
+    }
+
+    fn has_byte(&self) -> bool {
+        match self {
+            Uart::MiniUart(uart) => uart.has_byte(),
+            Uart::Pl011(uart) => uart.has_byte(),
+        }
+    }
+
+    /// Reads a byte. Bytes received with an error are dropped.
+    fn read_byte(&mut self) -> Option<u8> {
+        match self {
+            Uart::MiniUart(uart) => Some(uart.read_byte()),
+            Uart::Pl011(uart) => uart.try_read_byte().ok(),
+        }
+    }
+
+    fn can_write(&self) -> bool {
+        match self {
+            Uart::MiniUart(uart) => uart.can_write(),
+            Uart::Pl011(uart) => uart.can_write(),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        match self {
+            Uart::MiniUart(uart) => uart.write_byte(byte),
+            Uart::Pl011(uart) => uart.write_byte(byte),
+        }
+    }
+
+    fn set_interrupts(&mut self, rx: bool, tx: bool) {
+        match self {
+            Uart::MiniUart(uart) => uart.set_interrupts(rx, tx),
+            Uart::Pl011(uart) => uart.set_interrupts(rx, tx),
+        }
+    }
+}
+
+/// Moves bytes from the UART's receive FIFO into `RX`. Bytes that arrive
+/// while `RX` is full are dropped so that the receive interrupt is cleared.
+///
+/// This function never blocks: if another core is already draining, it
+/// returns immediately and leaves the work to that core.
+fn drain_rx() {
+    let backend = match backend() {
+        Some(backend) => backend,
+        None => return,
+    };
+    while !RX_BUSY.compare_and_swap(false, true, Ordering::Acquire) {
+        let mut uart = unsafe { Uart::shared(backend) };
+        while uart.has_byte() {
+            if let Some(b) = uart.read_byte() {
+                RX.push(b);
+            }
+        }
+        RX_BUSY.store(false, Ordering::Release);
+        // A byte that arrived after the last check but before the flag was
+        // released would otherwise wait for the next interrupt.
+        if !uart.has_byte() {
+            break;
+        }
+    }
+}
+
+/// Moves bytes from `TX` into the UART's transmit FIFO until either runs out,
+/// and keeps the transmit interrupt enabled only while `TX` is not empty.
+///
+/// Like `drain_rx()`, this function never blocks.
+fn drain_tx() {
+    let backend = match backend() {
+        Some(backend) => backend,
+        None => return,
+    };
+    while !TX_BUSY.compare_and_swap(false, true, Ordering::Acquire) {
+        let mut uart = unsafe { Uart::shared(backend) };
+        while uart.can_write() {
+            match TX.pop() {
+                Some(b) => uart.write_byte(b),
+                None => break,
+            }
+        }
+        uart.set_interrupts(true, !TX.is_empty());
+        TX_BUSY.store(false, Ordering::Release);
+        // Bytes pushed after the interrupt was disabled must not be stranded.
+        if TX.is_empty() {
+            break;
+        }
+        if !uart.can_write() {
+            break;
+        }
+    }
+}
+
+/// The handler for the console's UART interrupt. Services both directions of
+/// the UART without taking `CONSOLE`.
+fn handle_uart_irq() {
+    drain_rx();
+    drain_tx();
+}
+
+/// Registers the console's interrupt handler and enables the UART's receive
+/// and transmit interrupts. Until this is called, the console is serviced by
+/// polling alone.
+pub fn initialize_interrupts() {
+    CONSOLE.lock().initialize();
+    let int = backend().expect("console uninitialized").interrupt();
+    GLOBAL_IRQ.register(int, Box::new(|_| handle_uart_irq()));
+    Controller::new().enable(int);
+    IRQ_READY.store(true, Ordering::Release);
+    drain_tx();
+}
 
 /// A global singleton allowing read/write access to the console.
+///
+/// Input and output go through the `RX` and `TX` rings, which are serviced by
+/// the UART's interrupt handler. Every method also services the rings itself,
+/// so the console keeps working on cores that run with interrupts masked.
 pub struct Console {
-    inner: Option<MiniUart>,
+    inner: Option<Uart>,
 }
 
 impl Console {
@@ -19,50 +233,104 @@ impl Console {
     #[inline]
     fn initialize(&mut self) {
         if self.inner.is_none() {
-            self.inner = Some(MiniUart::new());
+            let backend = Backend::select();
+            self.inner = Some(Uart::new(backend));
+            BACKEND.store(backend as u8, Ordering::Release);
         }
     }
 
-    /// Returns a mutable borrow to the inner `MiniUart`, initializing it as
-    /// needed.
-    fn inner(&mut self) -> &mut MiniUart {
+    /// Moves every byte waiting in the UART's receive FIFO into the receive
+    /// buffer. This method does not block.
+    pub fn poll_rx(&mut self) {
         self.initialize();
-        match &mut self.inner {
-            Some(mu) => return mu,
-            None => panic!(),
+        drain_rx();
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read.
+    pub fn has_byte(&mut self) -> bool {
+        self.poll_rx();
+        !RX.is_empty()
+    }
+
+    /// Copies as many buffered bytes as fit into `buf` and returns the number
+    /// of bytes copied. This method does not block and returns `0` if no byte
+    /// has been received.
+    pub fn read_buffered(&mut self, buf: &mut [u8]) -> usize {
+        self.poll_rx();
+        let mut n = 0;
+        while n < buf.len() {
+            match RX.pop() {
+                Some(b) => buf[n] = b,
+                None => break,
+            }
+            n += 1;
         }
+        n
     }
 
     /// Reads a byte from the UART device, blocking until a byte is available.
     pub fn read_byte(&mut self) -> u8 {
-        return self.inner().read_byte();
+        loop {
+            self.poll_rx();
+            if let Some(b) = RX.pop() {
+                return b;
+            }
+        }
     }
 
-    /// Writes the byte `byte` to the UART device.
+    /// Writes the byte `byte` to the UART device. Once interrupts are enabled,
+    /// this blocks only while the transmit buffer is full; before that, it
+    /// blocks until the byte has been handed to the UART.
     pub fn write_byte(&mut self, byte: u8) {
-        self.inner().write_byte(byte);
+        self.initialize();
+        while !TX.push(byte) {
+            drain_tx();
+        }
+        if IRQ_READY.load(Ordering::Acquire) {
+            drain_tx();
+        } else {
+            while !TX.is_empty() {
+                drain_tx();
+            }
+        }
     }
 }
 
 impl io::Read for Console {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        return self.inner().read(buf);
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while !self.has_byte() {}
+        Ok(self.read_buffered(buf))
     }
 }
 
 impl io::Write for Console {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        return self.inner().write(buf);
+        for &b in buf {
+            self.write_byte(b);
+        }
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> io::Result<()> {
+        while !TX.is_empty() {
+            drain_tx();
+        }
         Ok(())
     }
 }
 
 impl fmt::Write for Console {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        return self.inner().write_str(s);
+        for b in s.bytes() {
+            if b == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(b);
+        }
+        Ok(())
     }
 }
 
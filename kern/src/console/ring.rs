@@ -0,0 +1,81 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(test)]
+mod tests;
+
+/// The number of bytes a `Ring` can hold. Must be a power of two.
+pub const RING_SIZE: usize = 1024;
+
+/// A fixed-size, lock-free byte queue for exactly one producer and one
+/// consumer, which may run concurrently on different cores or in an interrupt
+/// handler.
+///
+/// `head` and `tail` count every byte ever popped and pushed and wrap around
+/// on overflow, so the ring is full when they are `RING_SIZE` apart.
+pub struct Ring {
+    buf: UnsafeCell<[u8; RING_SIZE]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    /// Returns a new, empty `Ring`.
+    pub const fn new() -> Ring {
+        Ring {
+            buf: UnsafeCell::new([0; RING_SIZE]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of bytes in the ring.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    /// Returns `true` if the ring holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be pushed.
+    pub fn is_full(&self) -> bool {
+        self.len() == RING_SIZE
+    }
+
+    /// Appends `byte` to the ring. Returns `false` if the ring is full.
+    ///
+    /// Only the producer may call this method.
+    pub fn push(&self, byte: u8) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == RING_SIZE {
+            return false;
+        }
+        unsafe {
+            (*self.buf.get())[tail % RING_SIZE] = byte;
+        }
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Removes and returns the oldest byte in the ring, or `None` if the ring
+    /// is empty.
+    ///
+    /// Only the consumer may call this method.
+    pub fn pop(&self) -> Option<u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let byte = unsafe { (*self.buf.get())[head % RING_SIZE] };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(byte)
+    }
+}
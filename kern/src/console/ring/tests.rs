@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use std::thread;
+
+use super::{Ring, RING_SIZE};
+
+#[test]
+fn empty() {
+    let ring = Ring::new();
+    assert!(ring.is_empty());
+    assert!(!ring.is_full());
+    assert_eq!(ring.len(), 0);
+    assert_eq!(ring.pop(), None);
+}
+
+#[test]
+fn fifo_order() {
+    let ring = Ring::new();
+    for b in 0..10u8 {
+        assert!(ring.push(b));
+    }
+    assert_eq!(ring.len(), 10);
+    for b in 0..10u8 {
+        assert_eq!(ring.pop(), Some(b));
+    }
+    assert_eq!(ring.pop(), None);
+}
+
+#[test]
+fn full() {
+    let ring = Ring::new();
+    for i in 0..RING_SIZE {
+        assert!(ring.push(i as u8));
+    }
+    assert!(ring.is_full());
+    assert!(!ring.push(0xff));
+    assert_eq!(ring.pop(), Some(0));
+    assert!(ring.push(0xff));
+    assert!(ring.is_full());
+}
+
+#[test]
+fn wraps_around() {
+    let ring = Ring::new();
+    for i in 0..RING_SIZE * 3 + 7 {
+        assert!(ring.push(i as u8));
+        assert_eq!(ring.pop(), Some(i as u8));
+    }
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn concurrent_producer_consumer() {
+    const COUNT: usize = RING_SIZE * 64;
+
+    let ring = Arc::new(Ring::new());
+    let producer = {
+        let ring = ring.clone();
+        thread::spawn(move || {
+            for i in 0..COUNT {
+                while !ring.push(i as u8) {
+                    thread::yield_now();
+                }
+            }
+        })
+    };
+
+    for i in 0..COUNT {
+        loop {
+            if let Some(b) = ring.pop() {
+                assert_eq!(b, i as u8);
+                break;
+            }
+            thread::yield_now();
+        }
+    }
+    producer.join().unwrap();
+    assert!(ring.is_empty());
+}
@@ -10,7 +10,7 @@ pub use fat32::traits;
 use fat32::vfat::{Dir, Entry, File, VFat, VFatHandle};
 
 use self::sd::Sd;
-use crate::mutex::Mutex;
+use crate::mutex::{Mutex, Once};
 
 #[derive(Clone)]
 pub struct PiVFatHandle(Rc<Mutex<VFat<Self>>>);
@@ -38,7 +38,7 @@ impl VFatHandle for PiVFatHandle {
         f(&mut self.0.lock())
     }
 }
-pub struct FileSystem(Mutex<Option<PiVFatHandle>>);
+pub struct FileSystem(Once<Mutex<PiVFatHandle>>);
 
 impl FileSystem {
     /// Returns an uninitialized `FileSystem`.
@@ -46,7 +46,7 @@ impl FileSystem {
     /// The file system must be initialized by calling `initialize()` before the
     /// first memory allocation. Failure to do will result in panics.
     pub const fn uninitialized() -> Self {
-        FileSystem(Mutex::new(None))
+        FileSystem(Once::new())
     }
 
     /// Initializes the file system.
@@ -57,7 +57,30 @@ impl FileSystem {
     ///
     /// Panics if the underlying disk or file sytem failed to initialize.
     pub unsafe fn initialize(&self) {
-        *self.0.lock() = Some(VFat::<PiVFatHandle>::from(Sd::new().unwrap()).unwrap());
+        self.0.call_once(|| Mutex::new(VFat::<PiVFatHandle>::from(Sd::new().unwrap()).unwrap()));
+    }
+
+    /// Returns the handle to the file system. Its reference count is not
+    /// atomic (see `PiVFatHandle`), so it is cloned only under this mutex.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file system has not been initialized.
+    fn handle(&self) -> &Mutex<PiVFatHandle> {
+        self.0.get().expect("file system uninitialized")
+    }
+
+    /// Writes the sectors modified in the file system cache back to the disk.
+    /// Does nothing if the file system has not been initialized, or while the
+    /// SD card is read only.
+    pub fn flush(&self) -> io::Result<()> {
+        if !Sd::WRITABLE {
+            return Ok(());
+        }
+        match self.0.get() {
+            Some(handle) => handle.lock().lock(|vfat| vfat.flush()),
+            None => Ok(()),
+        }
     }
 }
 
@@ -85,8 +108,8 @@ impl fat32::traits::FileSystem for &FileSystem {
             // k = x.sectors_per_cluster.into();
             k = x.device.partition.sector_size as u32;
         };
-        (*self.0.lock()).clone().unwrap().lock(call);
-        return (*self.0.lock()).clone().unwrap().open(path);
+        self.handle().lock().clone().lock(call);
+        return self.handle().lock().clone().open(path);
     }
 
 }
@@ -42,6 +42,11 @@ fn wait_micros(micro_seconds: u64) {
 pub struct Sd;
 
 impl Sd {
+    /// Whether sectors can be written to the SD card. `libsd` can only read
+    /// them, so `write_sector` fails and modified sectors stay in the file
+    /// system cache.
+    pub const WRITABLE: bool = false;
+
     /// Initializes the SD card controller and returns a handle to it.
     /// The caller should assure that the method is invoked only once during the
     /// kernel initialization. We can enforce the requirement in safe Rust code
@@ -78,7 +83,12 @@ impl BlockDevice for Sd {
         return Ok(k as usize);
     }
 
+    /// Fails, since the SD card is read only (see `Sd::WRITABLE`).
+    ///
+    /// # Errors
+    ///
+    /// An I/O error of kind `PermissionDenied` is always returned.
     fn write_sector(&mut self, _n: u64, _buf: &[u8]) -> io::Result<usize> {
-        unimplemented!("SD card and file system are read only")
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "SD card is read only"))
     }
 }
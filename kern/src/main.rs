@@ -27,6 +27,7 @@ pub mod param;
 pub mod percore;
 pub mod process;
 pub mod shell;
+pub mod timer;
 pub mod traps;
 pub mod vm;
 
@@ -75,7 +76,8 @@ use allocator::Allocator;
 use fs::FileSystem;
 use net::uspi::Usb;
 use net::GlobalEthernetDriver;
-use process::GlobalScheduler;
+use process::{Futexes, GlobalScheduler, Ports, SharedMemory};
+use timer::Timers;
 use traps::irq::{Fiq, GlobalIrq, LocalIrq};
 use vm::VMManager;
 
@@ -88,6 +90,10 @@ pub static USB: Usb = Usb::uninitialized();
 pub static GLOBAL_IRQ: GlobalIrq = GlobalIrq::new();
 pub static FIQ: Fiq = Fiq::new();
 pub static ETHERNET: GlobalEthernetDriver = GlobalEthernetDriver::uninitialized();
+pub static FUTEXES: Futexes = Futexes::new();
+pub static SHARED_MEMORY: SharedMemory = SharedMemory::new();
+pub static PORTS: Ports = Ports::new();
+pub static TIMERS: Timers = Timers::new();
 
 extern "C" {
     static __text_beg: u64;
@@ -123,6 +129,7 @@ unsafe fn kmain() -> ! {
     }
 
     ALLOCATOR.initialize();
+    console::initialize_interrupts();
     FILESYSTEM.initialize();
 
     /*
@@ -166,6 +173,8 @@ unsafe fn kmain() -> ! {
     */
     VMM.initialize();
     // kprintln!("VMM INITIALIZED");
+    let usb_available = USB.initialize() && USB.is_eth_available();
+    ETHERNET.initialize(net::select_device(usb_available));
     SCHEDULER.initialize();
     init::initialize_app_cores();
     VMM.wait();
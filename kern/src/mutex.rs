@@ -6,6 +6,12 @@ use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use crate::percore::*;
 use aarch64::*;
 
+#[cfg(test)]
+mod tests;
+
+/// The value of `Mutex::owner` while no core holds the mutex.
+const NO_OWNER: usize = usize::max_value();
+
 #[repr(align(32))]
 pub struct Mutex<T> {
     data: UnsafeCell<T>,
@@ -27,7 +33,7 @@ impl<T> Mutex<T> {
     pub const fn new(val: T) -> Mutex<T> {
         Mutex {
             lock: AtomicBool::new(false),
-            owner: AtomicUsize::new(usize::max_value()),
+            owner: AtomicUsize::new(NO_OWNER),
             data: UnsafeCell::new(val),
         }
     }
@@ -38,7 +44,7 @@ impl<T> Mutex<T> {
     // need any real synchronization.
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
         if !is_mmu_ready() {
-            assert!(aarch64::affinity() == 0, "Non Zero");
+            assert!(affinity() == 0, "Non Zero");
             let this = 0;
             if !self.lock.load(Ordering::Relaxed) || self.owner.load(Ordering::Relaxed) == this {
                 self.lock.store(true, Ordering::Relaxed);
@@ -50,35 +56,44 @@ impl<T> Mutex<T> {
         }
         // if !self.lock.compare_and_swap(false, true, Ordering::SeqCst) {
         if !self.lock.swap(true, Ordering::Acquire) {
-            getcpu();
-            // self.owner.store(affinity(), Ordering::SeqCst);
+            self.owner.store(getcpu(), Ordering::Relaxed);
             return Some(MutexGuard { lock: &self });
         }
         return None;
     }
 
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
+    /// Waits until the lock can be acquired, then acquires it. The core waits
+    /// for an event between attempts, which the holder sends on unlocking.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current core already holds the lock, which would
+    /// otherwise deadlock: interrupts are masked in the kernel, so nothing
+    /// else could run on this core to release it.
     #[inline(never)]
     pub fn lock(&self) -> MutexGuard<T> {
         // Wait until we can "aquire" the lock, then "acquire" it.
         loop {
-            match self.try_lock() {
-                Some(guard) => {
-                    return guard;
-                },
-                None => continue,
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            let owner = self.owner.load(Ordering::Relaxed);
+            if owner == affinity() {
+                panic!("core {} locks a mutex it already holds", owner);
             }
+            wait_for_event();
         }
     }
 
     fn unlock(&self) {
+        self.owner.store(NO_OWNER, Ordering::Relaxed);
         if !is_mmu_ready() {
             self.lock.store(false, Ordering::Relaxed);
             return;
         }
         putcpu(affinity());
         self.lock.store(false, Ordering::Release);
+        send_event();
     }
 }
 
@@ -110,3 +125,411 @@ impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
         }
     }
 }
+
+/// Returns `true` once the MMU is on and the other cores may be running.
+/// Before that, only core 0 runs and exclusive loads and stores do not work
+/// on the uncached memory, so the locks below use plain ones instead.
+#[cfg(not(test))]
+fn is_smp() -> bool {
+    is_mmu_ready()
+}
+
+#[cfg(test)]
+fn is_smp() -> bool {
+    true
+}
+
+/// Test threads stand in for cores: each gets its own core number, so that
+/// `Mutex` can tell its holder from the threads waiting for it.
+#[cfg(test)]
+fn affinity() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local!(static CORE: usize = NEXT.fetch_add(1, Ordering::Relaxed));
+    CORE.with(|core| *core)
+}
+
+#[cfg(test)]
+fn is_mmu_ready() -> bool {
+    true
+}
+
+#[cfg(test)]
+fn getcpu() -> usize {
+    affinity()
+}
+
+#[cfg(test)]
+fn putcpu(_cpu: usize) {}
+
+/// Waits until another core sends an event, or returns at once if one has
+/// been sent since the last wait.
+#[cfg(not(test))]
+fn wait_for_event() {
+    wfe();
+}
+
+#[cfg(test)]
+fn wait_for_event() {
+    std::thread::yield_now();
+}
+
+/// Wakes the cores waiting in `wait_for_event()`. The barrier makes the
+/// stores before it visible to them first.
+#[cfg(not(test))]
+fn send_event() {
+    unsafe { asm!("dsb ish" :::: "volatile") };
+    sev();
+}
+
+#[cfg(test)]
+fn send_event() {}
+
+/// Replaces `current` with `new` in `word` if `word` holds `current`, and
+/// returns whether it did.
+fn compare_and_set(word: &AtomicUsize, current: usize, new: usize) -> bool {
+    if !is_smp() {
+        if word.load(Ordering::Relaxed) != current {
+            return false;
+        }
+        word.store(new, Ordering::Relaxed);
+        return true;
+    }
+    word.compare_exchange(current, new, Ordering::AcqRel, Ordering::Relaxed)
+        .is_ok()
+}
+
+/// Set in `RwLock::state` while a writer holds the lock.
+const WRITER: usize = 1;
+/// Set in `RwLock::state` while a writer waits for the lock. New readers wait
+/// too, so that writers are not starved by a stream of readers.
+const WRITER_WAITING: usize = 2;
+/// What every reader holding the lock adds to `RwLock::state`.
+const READER: usize = 4;
+
+/// A reader-writer lock for read-mostly data: any number of readers or a
+/// single writer hold it at a time. Waiting cores sleep in `wfe` until the
+/// lock is released.
+#[repr(align(32))]
+pub struct RwLock<T> {
+    data: UnsafeCell<T>,
+    state: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+pub struct RwLockReadGuard<'a, T: 'a> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> !Send for RwLockReadGuard<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for RwLockReadGuard<'a, T> {}
+
+pub struct RwLockWriteGuard<'a, T: 'a> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> !Send for RwLockWriteGuard<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for RwLockWriteGuard<'a, T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(val: T) -> RwLock<T> {
+        RwLock {
+            data: UnsafeCell::new(val),
+            state: AtomicUsize::new(0),
+        }
+    }
+
+    /// Acquires the lock for reading if no writer holds it or waits for it.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & (WRITER | WRITER_WAITING) != 0 {
+            return None;
+        }
+        if compare_and_set(&self.state, state, state + READER) {
+            Some(RwLockReadGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Waits until the lock can be acquired for reading, then acquires it.
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            let state = self.state.load(Ordering::Relaxed);
+            if state & (WRITER | WRITER_WAITING) != 0 {
+                wait_for_event();
+            }
+        }
+    }
+
+    /// Acquires the lock for writing if no one holds it.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & !WRITER_WAITING != 0 {
+            return None;
+        }
+        if compare_and_set(&self.state, state, WRITER) {
+            Some(RwLockWriteGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Waits until the lock can be acquired for writing, then acquires it.
+    /// Readers that arrive meanwhile wait behind the writer.
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            let state = self.state.load(Ordering::Relaxed);
+            if state & !WRITER_WAITING == 0 {
+                continue;
+            }
+            if state & WRITER_WAITING != 0 || compare_and_set(&self.state, state, state | WRITER_WAITING) {
+                wait_for_event();
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the data. No locking is needed, since
+    /// the lock is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    fn read_unlock(&self) {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if compare_and_set(&self.state, state, state - READER) {
+                break;
+            }
+        }
+        send_event();
+    }
+
+    fn write_unlock(&self) {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if compare_and_set(&self.state, state, state & !WRITER) {
+                break;
+            }
+        }
+        send_event();
+    }
+}
+
+impl<'a, T: 'a> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: 'a> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock()
+    }
+}
+
+impl<'a, T: 'a> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: 'a> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: 'a> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Some(guard) => f.debug_struct("RwLock").field("data", &&*guard).finish(),
+            None => f.debug_struct("RwLock").field("data", &"<locked>").finish(),
+        }
+    }
+}
+
+/// A counting semaphore. `acquire()` takes one of a number of permits,
+/// waiting in `wfe` while there are none, and `release()` returns one. A
+/// permit may be released by a different core than the one that took it.
+#[derive(Debug)]
+pub struct Semaphore {
+    permits: AtomicUsize,
+}
+
+/// A permit taken with `Semaphore::access()`, released when this is dropped.
+#[derive(Debug)]
+pub struct SemaphoreGuard<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Semaphore {
+    /// Returns a semaphore with `permits` permits available.
+    pub const fn new(permits: usize) -> Semaphore {
+        Semaphore { permits: AtomicUsize::new(permits) }
+    }
+
+    /// Returns the number of permits available.
+    pub fn available(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    /// Takes a permit if one is available, and returns whether it did.
+    pub fn try_acquire(&self) -> bool {
+        loop {
+            let permits = self.permits.load(Ordering::Relaxed);
+            if permits == 0 {
+                return false;
+            }
+            if compare_and_set(&self.permits, permits, permits - 1) {
+                return true;
+            }
+        }
+    }
+
+    /// Waits until a permit is available, then takes it.
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            wait_for_event();
+        }
+    }
+
+    /// Returns a permit, waking the cores waiting for one.
+    pub fn release(&self) {
+        loop {
+            let permits = self.permits.load(Ordering::Relaxed);
+            if compare_and_set(&self.permits, permits, permits + 1) {
+                break;
+            }
+        }
+        send_event();
+    }
+
+    /// Waits for a permit and returns a guard that releases it when dropped.
+    pub fn access(&self) -> SemaphoreGuard {
+        self.acquire();
+        SemaphoreGuard { semaphore: self }
+    }
+}
+
+impl<'a> Drop for SemaphoreGuard<'a> {
+    fn drop(&mut self) {
+        self.semaphore.release()
+    }
+}
+
+/// `Once::state` before the value has been initialized.
+const INCOMPLETE: usize = 0;
+/// `Once::state` while a core runs the initializer.
+const RUNNING: usize = 1;
+/// `Once::state` once the value is available.
+const COMPLETE: usize = 2;
+
+/// A value initialized once, on first use, for globals that cannot be built
+/// by a `const fn`. Cores that want the value while another initializes it
+/// wait in `wfe`.
+pub struct Once<T> {
+    data: UnsafeCell<Option<T>>,
+    state: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for Once<T> {}
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+
+impl<T> Once<T> {
+    /// Returns an uninitialized value.
+    pub const fn new() -> Once<T> {
+        Once {
+            data: UnsafeCell::new(None),
+            state: AtomicUsize::new(INCOMPLETE),
+        }
+    }
+
+    /// Returns the value, initializing it with `init` if this is the first
+    /// call. `init` runs once even if several cores call this at the same
+    /// time. It must not use this `Once` itself, or it waits for itself
+    /// forever.
+    pub fn call_once<F: FnOnce() -> T>(&self, init: F) -> &T {
+        if compare_and_set(&self.state, INCOMPLETE, RUNNING) {
+            unsafe { *self.data.get() = Some(init()) };
+            self.state.store(COMPLETE, Ordering::Release);
+            send_event();
+        }
+        loop {
+            if let Some(value) = self.get() {
+                return value;
+            }
+            wait_for_event();
+        }
+    }
+
+    /// Returns the value if it has been initialized.
+    pub fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) != COMPLETE {
+            return None;
+        }
+        unsafe { (*self.data.get()).as_ref() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Once<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_struct("Once").field("data", value).finish(),
+            None => f.debug_struct("Once").field("data", &"<uninitialized>").finish(),
+        }
+    }
+}
+
+/// A global built with `T::default()` on first use, for tables that cannot be
+/// built by a `const fn`.
+pub struct Lazy<T>(Once<T>);
+
+impl<T> Lazy<T> {
+    /// Returns a value that is built when it is first used.
+    pub const fn new() -> Lazy<T> {
+        Lazy(Once::new())
+    }
+}
+
+impl<T: Default> Deref for Lazy<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0.call_once(T::default)
+    }
+}
+
+impl<T: Default> Lazy<Mutex<T>> {
+    /// Enters a critical region and executes the provided closure with a
+    /// mutable reference to the value.
+    pub fn critical<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        f(&mut self.lock())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::thread;
+
+use super::*;
+
+const THREADS: usize = 8;
+const ROUNDS: usize = 1000;
+
+/// Runs `f` on `THREADS` threads, passing each its index, and waits for them.
+fn run_threads<F>(f: F)
+where
+    F: Fn(usize) + Send + Sync + 'static,
+{
+    let f = Arc::new(f);
+    let threads: Vec<_> = (0..THREADS)
+        .map(|i| {
+            let f = f.clone();
+            thread::spawn(move || f(i))
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn mutex_excludes_other_cores() {
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+    let other = mutex.clone();
+    thread::spawn(move || assert!(other.try_lock().is_none()))
+        .join()
+        .unwrap();
+    drop(guard);
+    *mutex.try_lock().unwrap() += 1;
+    assert_eq!(*mutex.lock(), 1);
+    assert_eq!(mutex.owner.load(Ordering::Relaxed), NO_OWNER);
+}
+
+#[test]
+fn mutex_stress() {
+    let mutex = Arc::new(Mutex::new((0, 0)));
+    let shared = mutex.clone();
+    run_threads(move |_| {
+        for _ in 0..ROUNDS {
+            let mut pair = shared.lock();
+            assert_eq!(pair.0, pair.1);
+            pair.0 += 1;
+            pair.1 += 1;
+        }
+    });
+    assert_eq!(*mutex.lock(), (THREADS * ROUNDS, THREADS * ROUNDS));
+    assert!(!mutex.lock.load(Ordering::Relaxed));
+}
+
+#[test]
+fn rwlock_readers_share_and_writers_exclude() {
+    let lock = RwLock::new(0);
+    {
+        let a = lock.read();
+        let b = lock.try_read().unwrap();
+        assert_eq!(*a + *b, 0);
+        assert!(lock.try_write().is_none());
+    }
+    {
+        let mut w = lock.write();
+        *w = 1;
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+    }
+    assert_eq!(*lock.read(), 1);
+}
+
+#[test]
+fn rwlock_waiting_writer_holds_off_readers() {
+    let lock = Arc::new(RwLock::new(0));
+    let reader = lock.read();
+    let writer = {
+        let lock = lock.clone();
+        thread::spawn(move || *lock.write() = 1)
+    };
+    while lock.state.load(Ordering::Relaxed) & WRITER_WAITING == 0 {
+        thread::yield_now();
+    }
+    assert!(lock.try_read().is_none());
+    drop(reader);
+    writer.join().unwrap();
+    assert_eq!(*lock.read(), 1);
+}
+
+#[test]
+fn rwlock_stress() {
+    let lock = Arc::new(RwLock::new((0, 0)));
+    let shared = lock.clone();
+    run_threads(move |i| {
+        for _ in 0..ROUNDS {
+            if i % 2 == 0 {
+                let mut pair = shared.write();
+                pair.0 += 1;
+                pair.1 += 1;
+            } else {
+                let pair = shared.read();
+                assert_eq!(pair.0, pair.1);
+            }
+        }
+    });
+    assert_eq!(*lock.read(), (THREADS / 2 * ROUNDS, THREADS / 2 * ROUNDS));
+    assert_eq!(lock.state.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn semaphore_counts_permits() {
+    let semaphore = Semaphore::new(2);
+    assert!(semaphore.try_acquire());
+    {
+        let _guard = semaphore.access();
+        assert_eq!(semaphore.available(), 0);
+        assert!(!semaphore.try_acquire());
+    }
+    assert_eq!(semaphore.available(), 1);
+    semaphore.release();
+    assert_eq!(semaphore.available(), 2);
+}
+
+#[test]
+fn semaphore_stress() {
+    const PERMITS: usize = 3;
+    let semaphore = Arc::new(Semaphore::new(PERMITS));
+    let inside = Arc::new(AtomicUsize::new(0));
+    let (s, n) = (semaphore.clone(), inside.clone());
+    run_threads(move |_| {
+        for _ in 0..ROUNDS {
+            let _guard = s.access();
+            assert!(n.fetch_add(1, Ordering::SeqCst) < PERMITS);
+            n.fetch_sub(1, Ordering::SeqCst);
+        }
+    });
+    assert_eq!(semaphore.available(), PERMITS);
+}
+
+#[test]
+fn once_initializes_once() {
+    let once = Arc::new(Once::new());
+    let calls = Arc::new(AtomicUsize::new(0));
+    assert!(once.get().is_none());
+    let (o, c) = (once.clone(), calls.clone());
+    run_threads(move |i| {
+        let value = o.call_once(|| {
+            c.fetch_add(1, Ordering::SeqCst);
+            thread::yield_now();
+            i
+        });
+        assert_eq!(o.get(), Some(value));
+    });
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(once.get().is_some());
+}
+
+#[test]
+fn lazy_builds_default_on_first_use() {
+    let lazy: Arc<Lazy<Mutex<Vec<usize>>>> = Arc::new(Lazy::new());
+    let shared = lazy.clone();
+    run_threads(move |i| shared.critical(|v| v.push(i)));
+    let mut all = lazy.critical(|v| v.clone());
+    all.sort();
+    assert_eq!(all, (0..THREADS).collect::<Vec<_>>());
+}
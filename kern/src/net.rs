@@ -1,5 +1,9 @@
 ///! Network device that wraps USPi in smoltcp abstraction
 pub mod uspi;
+pub mod dns;
+pub mod loopback;
+pub mod slip;
+pub mod telnet;
 
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
@@ -9,21 +13,53 @@ use core::convert::TryInto;
 use core::fmt;
 use core::time::Duration;
 
-use smoltcp::iface::{EthernetInterfaceBuilder, NeighborCache};
+use pi::atags::Atags;
+use pi::timer::current_time;
+use smoltcp::dhcp::{Dhcpv4Client, Dhcpv4Config};
+use smoltcp::iface::{EthernetInterfaceBuilder, NeighborCache, Routes};
 use smoltcp::phy::{self, Device, DeviceCapabilities};
-use smoltcp::socket::{SocketHandle, SocketRef, TcpSocketBuffer};
+use smoltcp::socket::{IcmpEndpoint, IcmpPacketMetadata, IcmpSocketBuffer};
+use smoltcp::socket::{RawPacketMetadata, RawSocketBuffer};
+use smoltcp::socket::{SocketHandle, SocketRef, TcpSocketBuffer, UdpPacketMetadata, UdpSocketBuffer};
 use smoltcp::time::Instant;
-use smoltcp::wire::{IpAddress, IpCidr};
-
-use crate::mutex::Mutex;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv4Cidr};
+
+use crate::mutex::{Mutex, MutexGuard, Once};
+use crate::console::{self, kprintln, Backend};
+use crate::net::dns::Resolver;
+use crate::net::loopback::{Loopback, LOOPBACK_ETH_ADDR};
+use crate::net::slip::{Slip, SLIP_ETH_ADDR};
+use crate::net::telnet::TelnetServer;
 use crate::param::MTU;
 use crate::USB;
 
 // We always use owned buffer as internal storage
 pub type SocketSet = smoltcp::socket::SocketSet<'static, 'static, 'static>;
 pub type TcpSocket = smoltcp::socket::TcpSocket<'static>;
+pub type UdpSocket = smoltcp::socket::UdpSocket<'static, 'static>;
+pub type IcmpSocket = smoltcp::socket::IcmpSocket<'static, 'static>;
 pub type EthernetInterface<T> = smoltcp::iface::EthernetInterface<'static, 'static, 'static, T>;
 
+/// The number of datagrams a UDP socket buffers in each direction.
+const UDP_PACKET_COUNT: usize = 16;
+
+/// The number of packets an ICMP socket buffers in each direction, and the
+/// size of each buffer.
+const ICMP_PACKET_COUNT: usize = 8;
+const ICMP_BUFFER_SIZE: usize = 4096;
+
+/// The first identifier handed out to ICMP sockets bound to identifier `0`.
+const ICMP_IDENT_START: u16 = 0x4000;
+
+/// The protocol of a socket held by a process.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SocketKind {
+    Tcp,
+    Udp,
+    /// An ICMP socket bound to an echo identifier
+    Icmp,
+}
+
 /// 8-byte aligned `u8` slice.
 #[repr(align(8))]
 struct FrameBuf([u8; MTU as usize]);
@@ -135,61 +171,416 @@ impl phy::TxToken for TxToken {
     }
 }
 
-/// Creates and returns a new ethernet interface using `UsbEthernet` struct.
-pub fn create_interface() -> EthernetInterface<UsbEthernet> {
+/// The device behind the ethernet interface.
+#[derive(Debug)]
+pub enum NetDevice {
+    /// The LAN9514 ethernet controller driven by USPi.
+    Usb(UsbEthernet),
+    /// A loopback device, used when USB ethernet is not available.
+    Loopback(Loopback),
+    /// A SLIP device on the PL011 UART, for talking to the host under QEMU.
+    Slip(Slip),
+}
+
+impl NetDevice {
+    /// Returns the USB ethernet device. USPi must have been initialized.
+    pub fn usb() -> NetDevice {
+        NetDevice::Usb(UsbEthernet)
+    }
+
+    /// Returns a fresh loopback device.
+    pub fn loopback() -> NetDevice {
+        NetDevice::Loopback(Loopback::new())
+    }
+
+    /// Returns a SLIP device on the PL011 UART. The console must not be on
+    /// the PL011.
+    pub fn slip() -> NetDevice {
+        NetDevice::Slip(Slip::new())
+    }
+
+    /// Returns `true` if this is the USB ethernet device.
+    pub fn is_usb(&self) -> bool {
+        match self {
+            NetDevice::Usb(_) => true,
+            NetDevice::Loopback(_) | NetDevice::Slip(_) => false,
+        }
+    }
+}
+
+impl<'a> Device<'a> for NetDevice {
+    type RxToken = NetRxToken;
+    type TxToken = NetTxToken<'a>;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        match self {
+            NetDevice::Usb(device) => device.capabilities(),
+            NetDevice::Loopback(device) => device.capabilities(),
+            NetDevice::Slip(device) => device.capabilities(),
+        }
+    }
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        match self {
+            NetDevice::Usb(device) => device
+                .receive()
+                .map(|(rx, tx)| (NetRxToken::Usb(rx), NetTxToken::Usb(tx))),
+            NetDevice::Loopback(device) => device
+                .receive()
+                .map(|(rx, tx)| (NetRxToken::Loopback(rx), NetTxToken::Loopback(tx))),
+            NetDevice::Slip(device) => device
+                .receive()
+                .map(|(rx, tx)| (NetRxToken::Slip(rx), NetTxToken::Slip(tx))),
+        }
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        match self {
+            NetDevice::Usb(device) => device.transmit().map(NetTxToken::Usb),
+            NetDevice::Loopback(device) => device.transmit().map(NetTxToken::Loopback),
+            NetDevice::Slip(device) => device.transmit().map(NetTxToken::Slip),
+        }
+    }
+}
+
+pub enum NetRxToken {
+    Usb(RxToken),
+    Loopback(loopback::RxToken),
+    Slip(slip::RxToken),
+}
+
+impl phy::RxToken for NetRxToken {
+    fn consume<R, F>(self, timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        match self {
+            NetRxToken::Usb(token) => token.consume(timestamp, f),
+            NetRxToken::Loopback(token) => token.consume(timestamp, f),
+            NetRxToken::Slip(token) => token.consume(timestamp, f),
+        }
+    }
+}
+
+pub enum NetTxToken<'a> {
+    Usb(TxToken),
+    Loopback(loopback::TxToken<'a>),
+    Slip(slip::TxToken),
+}
+
+impl<'a> phy::TxToken for NetTxToken<'a> {
+    fn consume<R, F>(self, timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        match self {
+            NetTxToken::Usb(token) => token.consume(timestamp, len, f),
+            NetTxToken::Loopback(token) => token.consume(timestamp, len, f),
+            NetTxToken::Slip(token) => token.consume(timestamp, len, f),
+        }
+    }
+}
+
+/// Creates and returns a new ethernet interface on top of `device`.
+///
+/// A USB interface starts with the link-local address `169.254.32.10/16` in
+/// its first address slot, which `EthernetDriver` later replaces with the
+/// address acquired over DHCP, and the loopback address `127.0.0.1/8`. A
+/// loopback interface only has `127.0.0.1/8`, and a SLIP interface only has
+/// the address `EthernetDriver` assigns it.
+pub fn create_interface(device: NetDevice) -> EthernetInterface<NetDevice> {
     // Lab 5 2.B
-    unimplemented!("create_interface")
+    let (ethernet_addr, ip_addrs) = match device {
+        NetDevice::Usb(_) => (
+            USB.get_eth_addr(),
+            vec![
+                IpCidr::new(IpAddress::v4(169, 254, 32, 10), 16),
+                IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8),
+            ],
+        ),
+        NetDevice::Loopback(_) => (
+            LOOPBACK_ETH_ADDR,
+            vec![IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8)],
+        ),
+        NetDevice::Slip(_) => (
+            SLIP_ETH_ADDR,
+            vec![IpCidr::Ipv4(slip_default_config().address.unwrap())],
+        ),
+    };
+    EthernetInterfaceBuilder::new(device)
+        .ethernet_addr(ethernet_addr)
+        .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+        .ip_addrs(ip_addrs)
+        .routes(Routes::new(BTreeMap::new()))
+        .finalize()
+}
+
+/// Returns the configuration of a SLIP interface without an `ip=` argument
+/// on the command line: `192.168.7.2/24`, with the host end of the serial
+/// line, `192.168.7.1`, as the gateway and DNS server.
+fn slip_default_config() -> IpConfig {
+    let host = Ipv4Address::new(192, 168, 7, 1);
+    IpConfig {
+        address: Some(Ipv4Cidr::new(Ipv4Address::new(192, 168, 7, 2), 24)),
+        gateway: Some(host),
+        dns_servers: vec![host],
+    }
+}
+
+/// The maximum number of DNS servers remembered from a configuration.
+const MAX_DNS_SERVERS: usize = 3;
+
+/// How long to wait for a DHCP lease before applying the static configuration
+/// from the kernel command line.
+const DHCP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The size of the raw socket buffers used by the DHCP client. A DHCP packet
+/// with its IP and UDP headers fits in 600 bytes.
+const DHCP_BUFFER_SIZE: usize = 600;
+
+/// An IPv4 configuration of the ethernet interface.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IpConfig {
+    /// The address of the interface with its netmask.
+    pub address: Option<Ipv4Cidr>,
+    /// The default gateway.
+    pub gateway: Option<Ipv4Address>,
+    /// The DNS servers, in order of preference.
+    pub dns_servers: Vec<Ipv4Address>,
+}
+
+impl IpConfig {
+    /// Parses a static configuration from a kernel command line such as
+    /// `ip=192.168.1.20/24 gw=192.168.1.1 dns=192.168.1.1`. Returns `None`
+    /// if the command line has no valid `ip=` argument.
+    pub fn from_cmdline(cmdline: &str) -> Option<IpConfig> {
+        let mut config = IpConfig::default();
+        for arg in cmdline.split(|c| c == ' ' || c == '\0') {
+            let mut kv = arg.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some("ip"), Some(value)) => config.address = value.parse().ok(),
+                (Some("gw"), Some(value)) => config.gateway = value.parse().ok(),
+                (Some("dns"), Some(value)) => config.dns_servers.extend(value.parse::<Ipv4Address>().ok()),
+                _ => (),
+            }
+        }
+        config.dns_servers.truncate(MAX_DNS_SERVERS);
+        config.address.map(|_| config)
+    }
+}
+
+impl From<Dhcpv4Config> for IpConfig {
+    fn from(config: Dhcpv4Config) -> IpConfig {
+        IpConfig {
+            address: config.address,
+            gateway: config.router,
+            dns_servers: config.dns_servers.iter().filter_map(|&dns| dns).collect(),
+        }
+    }
 }
 
 const PORT_MAP_SIZE: usize = 65536 / 64;
 
+/// The first port of the ephemeral port range.
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+/// The poll interval used when smoltcp has no pending timer.
+const MAX_POLL_DELAY: Duration = Duration::from_millis(100);
+
 pub struct EthernetDriver {
     /// A set of sockets
     socket_set: SocketSet,
     /// Bitmap to track the port usage
     port_map: [u64; PORT_MAP_SIZE],
     /// Internal ethernet interface
-    ethernet: EthernetInterface<UsbEthernet>,
+    ethernet: EthernetInterface<NetDevice>,
+    /// DHCP client that acquires and renews the address of the interface, or
+    /// `None` unless the interface runs on USB ethernet
+    dhcp: Option<Dhcpv4Client>,
+    /// Static configuration from the kernel command line, if any
+    static_config: Option<IpConfig>,
+    /// Time after which the static configuration is applied if no lease has
+    /// been acquired
+    dhcp_deadline: Instant,
+    /// Configuration currently applied to the interface
+    config: Option<IpConfig>,
+    /// DNS resolver state
+    dns: Resolver,
+    /// Remote shell server, if enabled on the kernel command line
+    telnet: Option<TelnetServer>,
+    /// The echo identifiers ICMP sockets are bound to
+    icmp_idents: Vec<(SocketHandle, u16)>,
+    /// The next identifier to try for an ICMP socket bound to identifier `0`
+    next_icmp_ident: u16,
 }
 
 impl EthernetDriver {
-    /// Creates a fresh ethernet driver.
-    fn new() -> EthernetDriver {
+    /// Creates a fresh ethernet driver on top of `device`. On USB ethernet,
+    /// the driver starts acquiring a DHCP lease. A SLIP interface is
+    /// configured statically right away.
+    fn new(device: NetDevice, timestamp: Instant) -> EthernetDriver {
         // Lab 5 2.B
-        unimplemented!("new")
+        let mut socket_set = SocketSet::new(Vec::new());
+        let is_slip = match device {
+            NetDevice::Slip(_) => true,
+            _ => false,
+        };
+        let dhcp = if device.is_usb() {
+            let rx_buffer = RawSocketBuffer::new(
+                vec![RawPacketMetadata::EMPTY; 1],
+                vec![0; DHCP_BUFFER_SIZE],
+            );
+            let tx_buffer = RawSocketBuffer::new(
+                vec![RawPacketMetadata::EMPTY; 1],
+                vec![0; DHCP_BUFFER_SIZE],
+            );
+            Some(Dhcpv4Client::new(&mut socket_set, rx_buffer, tx_buffer, timestamp))
+        } else {
+            None
+        };
+        let cmdline = Atags::get().find_map(|tag| tag.cmd());
+        let static_config = cmdline.and_then(IpConfig::from_cmdline);
+
+        let mut driver = EthernetDriver {
+            socket_set,
+            port_map: [0; PORT_MAP_SIZE],
+            ethernet: create_interface(device),
+            dhcp,
+            static_config,
+            dhcp_deadline: timestamp + DHCP_TIMEOUT.into(),
+            config: None,
+            dns: Resolver::new(),
+            telnet: None,
+            icmp_idents: Vec::new(),
+            next_icmp_ident: ICMP_IDENT_START,
+        };
+        if let Some(port) = cmdline.and_then(telnet::port_from_cmdline) {
+            match driver.mark_port(port) {
+                Some(_) => driver.telnet = Some(TelnetServer::new(port)),
+                None => kprintln!("telnet: port {} is in use", port),
+            }
+        }
+        if is_slip {
+            let config = driver.static_config.clone().unwrap_or_else(slip_default_config);
+            driver.apply_config(config);
+        }
+        driver
     }
 
-    /// Polls the ethernet interface.
+    /// Polls the ethernet interface. The interface itself answers echo
+    /// requests to our address, so the kernel responds to pings without an
+    /// ICMP socket.
     /// See also `smoltcp::iface::EthernetInterface::poll()`.
     fn poll(&mut self, timestamp: Instant) {
         // Lab 5 2.B
-        unimplemented!("poll")
+        if let Err(e) = self.ethernet.poll(&mut self.socket_set, timestamp) {
+            debug!("ethernet poll failed: {:?}", e);
+        }
+        self.poll_dhcp(timestamp);
+        self.poll_telnet();
+        self.prune();
+    }
+
+    /// Drives the DHCP client. The client renews the lease once half of it
+    /// has elapsed; every lease it acquires or renews is applied to the
+    /// interface. If no lease arrives before `DHCP_TIMEOUT`, the static
+    /// configuration from the command line is applied instead, and stays in
+    /// effect until a lease is acquired.
+    fn poll_dhcp(&mut self, timestamp: Instant) {
+        let dhcp = match self.dhcp.as_mut() {
+            Some(dhcp) => dhcp,
+            None => return,
+        };
+        match dhcp.poll(&mut self.ethernet, &mut self.socket_set, timestamp) {
+            Ok(Some(config)) if config.address.is_some() => {
+                self.apply_config(config.into());
+            }
+            Ok(_) => {
+                if self.config.is_none() && timestamp >= self.dhcp_deadline {
+                    if let Some(config) = self.static_config.clone() {
+                        info!("no DHCP lease; using static configuration");
+                        self.apply_config(config);
+                    }
+                }
+            }
+            Err(e) => debug!("dhcp poll failed: {:?}", e),
+        }
+    }
+
+    /// Applies `config` to the interface, replacing its primary address and
+    /// default route.
+    fn apply_config(&mut self, config: IpConfig) {
+        if self.config.as_ref() == Some(&config) {
+            return;
+        }
+        info!("network configuration: {:?}", config);
+
+        if let Some(address) = config.address {
+            self.ethernet.update_ip_addrs(|addrs| addrs[0] = IpCidr::Ipv4(address));
+        }
+        let routes = self.ethernet.routes_mut();
+        let result = match config.gateway {
+            Some(gateway) => routes.add_default_ipv4_route(gateway).map(|_| ()),
+            None => {
+                routes.remove_default_ipv4_route();
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            debug!("failed to update the default route: {:?}", e);
+        }
+
+        self.config = Some(config);
+    }
+
+    /// Returns the configuration applied to the interface, or `None` if
+    /// neither a DHCP lease nor a static configuration is in effect yet.
+    pub fn config(&self) -> Option<&IpConfig> {
+        self.config.as_ref()
     }
 
     /// Returns an advisory wait time to call `poll()` the next time.
     /// See also `smoltcp::iface::EthernetInterface::poll_delay()`.
     fn poll_delay(&mut self, timestamp: Instant) -> Duration {
         // Lab 5 2.B
-        unimplemented!("poll_delay")
+        let delay = match self.ethernet.poll_delay(&self.socket_set, timestamp) {
+            Some(delay) => Duration::from_millis(delay.total_millis()).min(MAX_POLL_DELAY),
+            None => MAX_POLL_DELAY,
+        };
+        match self.dhcp.as_mut() {
+            Some(dhcp) => delay.min(Duration::from_millis(dhcp.next_poll(timestamp).total_millis())),
+            None => delay,
+        }
     }
 
     /// Marks a port as used. Returns `Some(port)` on success, `None` on failure.
     pub fn mark_port(&mut self, port: u16) -> Option<u16> {
         // Lab 5 2.B
-        unimplemented!("mark_port")
+        let (idx, bit) = (port as usize / 64, 1u64 << (port % 64));
+        if port == 0 || self.port_map[idx] & bit != 0 {
+            return None;
+        }
+        self.port_map[idx] |= bit;
+        Some(port)
     }
 
     /// Clears used bit of a port. Returns `Some(port)` on success, `None` on failure.
     pub fn erase_port(&mut self, port: u16) -> Option<u16> {
         // Lab 5 2.B
-        unimplemented!("erase_port")
+        let (idx, bit) = (port as usize / 64, 1u64 << (port % 64));
+        if self.port_map[idx] & bit == 0 {
+            return None;
+        }
+        self.port_map[idx] &= !bit;
+        Some(port)
     }
 
     /// Returns the first open port between the ephemeral port range 49152 ~ 65535.
     /// Note that this function does not mark the returned port.
     pub fn get_ephemeral_port(&mut self) -> Option<u16> {
         // Lab 5 2.B
-        unimplemented!("get_ephemeral_port")
+        (EPHEMERAL_PORT_START..=u16::max_value())
+            .find(|&port| self.port_map[port as usize / 64] & (1u64 << (port % 64)) == 0)
     }
 
     /// Finds a socket with a `SocketHandle`.
@@ -197,6 +588,21 @@ impl EthernetDriver {
         self.socket_set.get::<TcpSocket>(handle)
     }
 
+    /// Finds a UDP socket with a `SocketHandle`.
+    pub fn get_udp_socket(&mut self, handle: SocketHandle) -> SocketRef<'_, UdpSocket> {
+        self.socket_set.get::<UdpSocket>(handle)
+    }
+
+    /// Returns the local port of the socket `handle` of kind `kind`, or `0` if
+    /// it is not bound.
+    pub fn local_port(&mut self, kind: SocketKind, handle: SocketHandle) -> u16 {
+        match kind {
+            SocketKind::Tcp => self.get_socket(handle).local_endpoint().port,
+            SocketKind::Udp => self.get_udp_socket(handle).endpoint().port,
+            SocketKind::Icmp => 0,
+        }
+    }
+
     /// This function creates a new TCP socket, adds it to the internal socket
     /// set, and returns the `SocketHandle` of the new socket.
     pub fn add_socket(&mut self) -> SocketHandle {
@@ -206,8 +612,79 @@ impl EthernetDriver {
         self.socket_set.add(tcp_socket)
     }
 
+    /// This function creates a new UDP socket, adds it to the internal socket
+    /// set, and returns the `SocketHandle` of the new socket.
+    pub fn add_udp_socket(&mut self) -> SocketHandle {
+        let rx_buffer = UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; UDP_PACKET_COUNT],
+            vec![0; 16384],
+        );
+        let tx_buffer = UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; UDP_PACKET_COUNT],
+            vec![0; 16384],
+        );
+        let udp_socket = UdpSocket::new(rx_buffer, tx_buffer);
+        self.socket_set.add(udp_socket)
+    }
+
+    /// This function creates a new ICMP socket, adds it to the internal
+    /// socket set, and returns the `SocketHandle` of the new socket. The
+    /// socket receives nothing until it is bound with `bind_icmp()`.
+    pub fn add_icmp_socket(&mut self) -> SocketHandle {
+        let rx_buffer = IcmpSocketBuffer::new(
+            vec![IcmpPacketMetadata::EMPTY; ICMP_PACKET_COUNT],
+            vec![0; ICMP_BUFFER_SIZE],
+        );
+        let tx_buffer = IcmpSocketBuffer::new(
+            vec![IcmpPacketMetadata::EMPTY; ICMP_PACKET_COUNT],
+            vec![0; ICMP_BUFFER_SIZE],
+        );
+        let icmp_socket = IcmpSocket::new(rx_buffer, tx_buffer);
+        self.socket_set.add(icmp_socket)
+    }
+
+    /// Finds an ICMP socket with a `SocketHandle`.
+    pub fn get_icmp_socket(&mut self, handle: SocketHandle) -> SocketRef<'_, IcmpSocket> {
+        self.socket_set.get::<IcmpSocket>(handle)
+    }
+
+    /// Returns the echo identifier the ICMP socket `handle` is bound to.
+    pub fn icmp_ident(&self, handle: SocketHandle) -> Option<u16> {
+        self.icmp_idents
+            .iter()
+            .find(|&&(h, _)| h == handle)
+            .map(|&(_, ident)| ident)
+    }
+
+    /// Binds the ICMP socket `handle` to the echo identifier `ident`, or to an
+    /// unused identifier if `ident` is `0`, so that it receives the echo
+    /// replies that carry it. Returns the identifier, or `None` if it is in
+    /// use or the socket is already bound.
+    pub fn bind_icmp(&mut self, handle: SocketHandle, ident: u16) -> Option<u16> {
+        if self.icmp_ident(handle).is_some() {
+            return None;
+        }
+        let in_use = |idents: &[(SocketHandle, u16)], ident| idents.iter().any(|&(_, i)| i == ident);
+        let ident = match ident {
+            0 => {
+                let mut ident = self.next_icmp_ident;
+                while ident == 0 || in_use(&self.icmp_idents, ident) {
+                    ident = ident.wrapping_add(1);
+                }
+                self.next_icmp_ident = ident.wrapping_add(1);
+                ident
+            }
+            ident if in_use(&self.icmp_idents, ident) => return None,
+            ident => ident,
+        };
+        self.get_icmp_socket(handle).bind(IcmpEndpoint::Ident(ident)).ok()?;
+        self.icmp_idents.push((handle, ident));
+        Some(ident)
+    }
+
     /// Releases a socket from the internal socket set.
     pub fn release(&mut self, handle: SocketHandle) {
+        self.icmp_idents.retain(|&(h, _)| h != handle);
         self.socket_set.release(handle);
     }
 
@@ -217,54 +694,84 @@ impl EthernetDriver {
     }
 }
 
+/// Returns the network device to use. A `net=usb`, `net=loopback` or
+/// `net=slip` argument on the kernel command line selects the device;
+/// without one, USB ethernet is used if `usb_available` is `true` and the
+/// loopback device otherwise. SLIP is only available while the console is on
+/// the mini UART.
+pub fn select_device(usb_available: bool) -> NetDevice {
+    let arg = Atags::get()
+        .find_map(|tag| tag.cmd())
+        .and_then(|cmd| {
+            cmd.split(|c| c == ' ' || c == '\0')
+                .find(|arg| arg.starts_with("net="))
+        });
+    match arg {
+        Some("net=loopback") => NetDevice::loopback(),
+        Some("net=slip") if console::backend() == Some(Backend::MiniUart) => NetDevice::slip(),
+        Some("net=slip") => {
+            kprintln!("SLIP needs the console on the mini UART");
+            NetDevice::loopback()
+        }
+        _ if usb_available => NetDevice::usb(),
+        _ => NetDevice::loopback(),
+    }
+}
+
 /// A thread-safe wrapper for `EthernetDriver`.
-pub struct GlobalEthernetDriver(Mutex<Option<EthernetDriver>>);
+pub struct GlobalEthernetDriver(Once<Mutex<EthernetDriver>>);
 
 impl GlobalEthernetDriver {
     pub const fn uninitialized() -> GlobalEthernetDriver {
-        GlobalEthernetDriver(Mutex::new(None))
+        GlobalEthernetDriver(Once::new())
     }
 
-    pub fn initialize(&self) {
-        let mut lock = self.0.lock();
-        *lock = Some(EthernetDriver::new());
+    pub fn initialize(&self, device: NetDevice) {
+        let now = Instant::from_millis(current_time().as_millis() as i64);
+        self.0.call_once(|| Mutex::new(EthernetDriver::new(device, now)));
+    }
+
+    /// Locks the driver.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initialize()` has not been called.
+    fn lock(&self) -> MutexGuard<EthernetDriver> {
+        self.0.get().expect("Uninitialized EthernetDriver").lock()
+    }
+
+    /// Returns `true` if `initialize()` has been called.
+    pub fn is_initialized(&self) -> bool {
+        self.0.get().is_some()
     }
 
     pub fn poll(&self, timestamp: Instant) {
         // Lab 5 2.B
-        unimplemented!("poll")
+        self.lock().poll(timestamp)
     }
 
     pub fn poll_delay(&self, timestamp: Instant) -> Duration {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("Uninitialized EthernetDriver")
-            .poll_delay(timestamp)
+        self.lock().poll_delay(timestamp)
     }
 
     pub fn mark_port(&self, port: u16) -> Option<u16> {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("Uninitialized EthernetDriver")
-            .mark_port(port)
+        self.lock().mark_port(port)
     }
 
     pub fn get_ephemeral_port(&self) -> Option<u16> {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("Uninitialized EthernetDriver")
-            .get_ephemeral_port()
+        self.lock().get_ephemeral_port()
     }
 
     pub fn add_socket(&self) -> SocketHandle {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("Uninitialized EthernetDriver")
-            .add_socket()
+        self.lock().add_socket()
+    }
+
+    pub fn add_udp_socket(&self) -> SocketHandle {
+        self.lock().add_udp_socket()
+    }
+
+    pub fn add_icmp_socket(&self) -> SocketHandle {
+        self.lock().add_icmp_socket()
     }
 
     /// Enters a critical region and execute the provided closure with a mutable
@@ -273,11 +780,32 @@ impl GlobalEthernetDriver {
     where
         F: FnOnce(&mut SocketRef<'_, TcpSocket>) -> R,
     {
-        let mut guard = self.0.lock();
-        let mut socket = guard
-            .as_mut()
-            .expect("Uninitialized EthernetDriver")
-            .get_socket(handle);
+        let mut guard = self.lock();
+        let mut socket = guard.get_socket(handle);
+
+        f(&mut socket)
+    }
+
+    /// Enters a critical region and execute the provided closure with a mutable
+    /// reference to the UDP socket.
+    pub fn with_udp_socket<F, R>(&self, handle: SocketHandle, f: F) -> R
+    where
+        F: FnOnce(&mut SocketRef<'_, UdpSocket>) -> R,
+    {
+        let mut guard = self.lock();
+        let mut socket = guard.get_udp_socket(handle);
+
+        f(&mut socket)
+    }
+
+    /// Enters a critical region and execute the provided closure with a mutable
+    /// reference to the ICMP socket.
+    pub fn with_icmp_socket<F, R>(&self, handle: SocketHandle, f: F) -> R
+    where
+        F: FnOnce(&mut SocketRef<'_, IcmpSocket>) -> R,
+    {
+        let mut guard = self.lock();
+        let mut socket = guard.get_icmp_socket(handle);
 
         f(&mut socket)
     }
@@ -288,9 +816,6 @@ impl GlobalEthernetDriver {
     where
         F: FnOnce(&mut EthernetDriver) -> R,
     {
-        let mut guard = self.0.lock();
-        let mut ethernet = guard.as_mut().expect("Uninitialized EthernetDriver");
-
-        f(&mut ethernet)
+        f(&mut self.lock())
     }
 }
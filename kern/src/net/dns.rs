@@ -0,0 +1,464 @@
+///! Stub DNS resolver for IPv4 addresses
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use kernel_api::{OsError, OsResult};
+use shim::path::Path;
+use smoltcp::socket::SocketHandle;
+use smoltcp::time::Instant;
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+
+use crate::net::EthernetDriver;
+use crate::{ETHERNET, FILESYSTEM};
+
+#[cfg(test)]
+mod tests;
+
+/// The path of the static hosts table on the FAT32 volume.
+pub const HOSTS_PATH: &str = "/hosts";
+
+/// The UDP port DNS servers listen on.
+const DNS_PORT: u16 = 53;
+
+/// The longest name a query can carry.
+const MAX_NAME_LEN: usize = 253;
+
+/// The size of the buffer queries are built in and responses received into.
+const MESSAGE_SIZE: usize = 512;
+
+/// How long to wait for a response before asking the next server. A server
+/// that answers with a failure is not waited for.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The number of times a query is sent before it times out.
+const MAX_ATTEMPTS: u32 = 4;
+
+/// The maximum number of cached addresses.
+const CACHE_SIZE: usize = 64;
+
+/// The longest time an address stays cached, whatever its TTL.
+const MAX_TTL: u32 = 24 * 60 * 60;
+
+/// The `A` record type and the `IN` class.
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+
+/// Header flags: a response, and a query that asks for recursion.
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+/// The response code of a name that does not exist.
+const RCODE_NAME_ERROR: u16 = 3;
+
+/// The outcome of a query, as reported by a response.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Answer {
+    /// The name has the address, which may be cached for the TTL in seconds.
+    Address(Ipv4Address, u32),
+    /// The name does not exist or has no IPv4 address.
+    NoSuchName,
+    /// The server failed to answer.
+    Failure,
+}
+
+/// Returns `name` in the form names are looked up and cached in: lowercase
+/// and without a trailing dot.
+pub fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Writes a recursive query for the `A` record of `name` with the ID `id`
+/// into `buf`. Returns the length of the query, or `None` if `name` is not a
+/// valid domain name or `buf` is too small.
+pub fn build_query(id: u16, name: &str, buf: &mut [u8]) -> Option<usize> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return None;
+    }
+
+    let mut msg = Vec::with_capacity(12 + name.len() + 6);
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    // One question; no answer, authority or additional records.
+    msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&TYPE_A.to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    let dst = buf.get_mut(..msg.len())?;
+    dst.copy_from_slice(&msg);
+    Some(msg.len())
+}
+
+/// Returns the ID of the DNS message `buf`, if it is long enough to have one.
+pub fn message_id(buf: &[u8]) -> Option<u16> {
+    read_u16(buf, 0)
+}
+
+/// Parses `buf` as the response to the query with the ID `id`. Returns
+/// `None` if `buf` is not such a response or is malformed.
+///
+/// The first `A` record in the answer section is used; `CNAME` records that
+/// lead to it are skipped.
+pub fn parse_response(id: u16, buf: &[u8]) -> Option<Answer> {
+    if read_u16(buf, 0)? != id {
+        return None;
+    }
+    let flags = read_u16(buf, 2)?;
+    if flags & FLAG_RESPONSE == 0 {
+        return None;
+    }
+    match flags & 0xf {
+        0 => (),
+        RCODE_NAME_ERROR => return Some(Answer::NoSuchName),
+        _ => return Some(Answer::Failure),
+    }
+
+    let questions = read_u16(buf, 4)?;
+    let answers = read_u16(buf, 6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(buf, pos)? + 4;
+    }
+    for _ in 0..answers {
+        pos = skip_name(buf, pos)?;
+        let rtype = read_u16(buf, pos)?;
+        let class = read_u16(buf, pos + 2)?;
+        let ttl = read_u32(buf, pos + 4)?;
+        let len = read_u16(buf, pos + 8)? as usize;
+        let data = buf.get(pos + 10..pos + 10 + len)?;
+        if rtype == TYPE_A && class == CLASS_IN && len == 4 {
+            return Some(Answer::Address(Ipv4Address::from_bytes(data), ttl));
+        }
+        pos += 10 + len;
+    }
+    Some(Answer::NoSuchName)
+}
+
+/// Returns the position right after the possibly compressed name that starts
+/// at `pos` in `buf`.
+fn skip_name(buf: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *buf.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            // A pointer ends the name.
+            len if len & 0xc0 == 0xc0 => {
+                buf.get(pos + 1)?;
+                return Some(pos + 2);
+            }
+            len if len & 0xc0 != 0 => return None,
+            len => pos += 1 + len,
+        }
+    }
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    let bytes = buf.get(pos..pos + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    let bytes = buf.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Looks `name` up in the hosts table `hosts`, which has one address per
+/// line followed by the names it belongs to. Text after a `#` is a comment.
+/// Lines with addresses other than IPv4 ones are ignored.
+pub fn parse_hosts(hosts: &str, name: &str) -> Option<Ipv4Address> {
+    hosts.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let addr = fields.next()?.parse::<Ipv4Address>().ok()?;
+        if fields.any(|host| host.eq_ignore_ascii_case(name)) {
+            Some(addr)
+        } else {
+            None
+        }
+    })
+}
+
+/// Looks `name` up in the hosts table at `HOSTS_PATH`. Returns `None` if the
+/// table does not exist or does not have `name`.
+pub fn lookup_hosts(name: &str) -> Option<Ipv4Address> {
+    use fat32::traits::{Entry, FileSystem};
+    use shim::io::Read;
+
+    let entry = FILESYSTEM.open(Path::new(HOSTS_PATH)).ok()?;
+    let mut file = entry.into_file()?;
+    let mut buf = vec![0; file.size as usize];
+    file.read_exact(&mut buf).ok()?;
+    parse_hosts(core::str::from_utf8(&buf).ok()?, name)
+}
+
+/// A query waiting for a response.
+#[derive(Debug)]
+struct Query {
+    name: String,
+    /// The number of times the query has been sent
+    attempts: u32,
+    /// When to send the query again, or give up
+    deadline: Instant,
+    /// The server the query was last sent to, the only one it takes a
+    /// response from
+    server: Option<Ipv4Address>,
+    /// Whether a server has answered with a failure
+    failed: bool,
+    /// The outcome, once a response has arrived
+    result: Option<OsResult<Ipv4Address>>,
+}
+
+/// A query its process waits on. The query is withdrawn when this is
+/// dropped, whether its outcome has been picked up or the wait was
+/// interrupted.
+#[derive(Debug)]
+pub struct PendingQuery(u16);
+
+impl PendingQuery {
+    /// Tracks the query `id`.
+    pub fn new(id: u16) -> PendingQuery {
+        PendingQuery(id)
+    }
+
+    /// Returns the outcome of the query, if it has one yet. See
+    /// `EthernetDriver::poll_query()`.
+    pub fn outcome(&self, now: Instant) -> Option<OsResult<Ipv4Address>> {
+        ETHERNET.critical(|ethernet| ethernet.poll_query(self.0, now))
+    }
+}
+
+impl Drop for PendingQuery {
+    fn drop(&mut self) {
+        ETHERNET.critical(|ethernet| ethernet.dns.queries.remove(&self.0));
+    }
+}
+
+/// The state of the resolver: its socket, the cache and the outstanding
+/// queries.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    /// UDP socket bound to an ephemeral port, created by the first query
+    socket: Option<SocketHandle>,
+    /// Cached addresses and when they expire
+    cache: BTreeMap<String, (Ipv4Address, Instant)>,
+    /// Outstanding queries by ID
+    queries: BTreeMap<u16, Query>,
+    next_id: u16,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver::default()
+    }
+
+    /// Caches `addr` for `name` for `ttl` seconds, evicting the entry that
+    /// expires first if the cache is full.
+    fn insert(&mut self, name: &str, addr: Ipv4Address, ttl: u32, now: Instant) {
+        if ttl == 0 {
+            return;
+        }
+        if self.cache.len() >= CACHE_SIZE && !self.cache.contains_key(name) {
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|entry| (entry.1).1)
+                .map(|(name, _)| name.clone());
+            if let Some(oldest) = oldest {
+                self.cache.remove(&oldest);
+            }
+        }
+        let ttl = Duration::from_secs(ttl.min(MAX_TTL) as u64);
+        self.cache.insert(String::from(name), (addr, now + ttl.into()));
+    }
+}
+
+impl EthernetDriver {
+    /// Returns the cached address of `name` if it has not expired.
+    pub fn lookup_cached(&mut self, name: &str, now: Instant) -> Option<Ipv4Address> {
+        match self.dns.cache.get(name) {
+            Some(&(addr, expiry)) if now < expiry => Some(addr),
+            Some(_) => {
+                self.dns.cache.remove(name);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Sends a query for `name` and returns its ID, which `poll_query()`
+    /// takes.
+    ///
+    /// # Errors
+    /// This function returns `OsError::NoEntry` if no DNS server is known and
+    /// `OsError::InvalidArgument` if `name` is not a valid domain name.
+    pub fn start_query(&mut self, name: &str, now: Instant) -> OsResult<u16> {
+        if self.dns_servers().is_empty() {
+            return Err(OsError::NoEntry);
+        }
+        if build_query(0, name, &mut [0; MESSAGE_SIZE]).is_none() {
+            return Err(OsError::InvalidArgument);
+        }
+
+        let mut id = self.dns.next_id;
+        while self.dns.queries.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+        self.dns.next_id = id.wrapping_add(1);
+
+        let query = Query {
+            name: String::from(name),
+            attempts: 0,
+            deadline: now,
+            server: None,
+            failed: false,
+            result: None,
+        };
+        self.dns.queries.insert(id, query);
+        self.send_query(id, now);
+        Ok(id)
+    }
+
+    /// Processes received responses and retransmissions, and returns the
+    /// outcome of the query `id` once it is known. The query stays until its
+    /// `PendingQuery` is dropped.
+    ///
+    /// A query that is not answered within `QUERY_TIMEOUT`, or that a server
+    /// fails to answer, is sent to the next server, up to `MAX_ATTEMPTS`
+    /// times in total. It then fails with `OsError::IoError` if a server
+    /// answered with a failure and with `OsError::IoErrorTimedOut` otherwise.
+    /// A name that does not exist fails with `OsError::NoEntry`.
+    pub fn poll_query(&mut self, id: u16, now: Instant) -> Option<OsResult<Ipv4Address>> {
+        self.receive_responses(now);
+
+        let query = match self.dns.queries.get(&id) {
+            Some(query) => query,
+            None => return Some(Err(OsError::IoErrorTimedOut)),
+        };
+        if let Some(result) = query.result {
+            return Some(result);
+        }
+        if now < query.deadline {
+            return None;
+        }
+        if query.attempts >= MAX_ATTEMPTS {
+            return Some(Err(if query.failed { OsError::IoError } else { OsError::IoErrorTimedOut }));
+        }
+        self.send_query(id, now);
+        None
+    }
+
+    /// Returns the DNS servers of the current configuration.
+    fn dns_servers(&self) -> &[Ipv4Address] {
+        self.config()
+            .map(|config| &config.dns_servers[..])
+            .unwrap_or(&[])
+    }
+
+    /// Returns the resolver's socket, creating and binding it if needed.
+    fn dns_socket(&mut self) -> Option<SocketHandle> {
+        if let Some(handle) = self.dns.socket {
+            return Some(handle);
+        }
+        let port = self.get_ephemeral_port()?;
+        self.mark_port(port)?;
+        let handle = self.add_udp_socket();
+        if self.get_udp_socket(handle).bind(port).is_err() {
+            self.release(handle);
+            self.erase_port(port);
+            return None;
+        }
+        self.dns.socket = Some(handle);
+        Some(handle)
+    }
+
+    /// Sends the query `id` to the server whose turn it is. A query that
+    /// cannot be sent counts as sent and is retried after the timeout.
+    fn send_query(&mut self, id: u16, now: Instant) {
+        let servers = self.dns_servers().to_vec();
+        let query = match self.dns.queries.get_mut(&id) {
+            Some(query) => query,
+            None => return,
+        };
+        query.attempts += 1;
+        query.deadline = now + QUERY_TIMEOUT.into();
+        if servers.is_empty() {
+            return;
+        }
+        let server = servers[(query.attempts as usize - 1) % servers.len()];
+        query.server = Some(server);
+        let name = query.name.clone();
+
+        let mut buf = [0; MESSAGE_SIZE];
+        let len = match build_query(id, &name, &mut buf) {
+            Some(len) => len,
+            None => return,
+        };
+        let handle = match self.dns_socket() {
+            Some(handle) => handle,
+            None => return,
+        };
+        let endpoint = IpEndpoint::new(IpAddress::Ipv4(server), DNS_PORT);
+        if let Err(e) = self.get_udp_socket(handle).send_slice(&buf[..len], endpoint) {
+            debug!("failed to send a DNS query: {:?}", e);
+        }
+    }
+
+    /// Matches every received response with its query, and caches the
+    /// addresses they carry. A response is only taken from the server its
+    /// query was last sent to. A failure makes the query due to be sent to the
+    /// next server.
+    fn receive_responses(&mut self, now: Instant) {
+        let handle = match self.dns.socket {
+            Some(handle) => handle,
+            None => return,
+        };
+        let mut buf = [0; MESSAGE_SIZE];
+        loop {
+            let (len, source) = match self.get_udp_socket(handle).recv_slice(&mut buf) {
+                Ok((len, endpoint)) if endpoint.port == DNS_PORT => (len, endpoint.addr),
+                Ok(_) => continue,
+                Err(_) => break,
+            };
+            let response = &buf[..len];
+            let id = match message_id(response) {
+                Some(id) => id,
+                None => continue,
+            };
+            let query = match self.dns.queries.get_mut(&id) {
+                Some(query) if query.result.is_none() => query,
+                _ => continue,
+            };
+            if query.server.map(IpAddress::Ipv4) != Some(source) {
+                continue;
+            }
+            let answer = match parse_response(id, response) {
+                Some(answer) => answer,
+                None => continue,
+            };
+            query.result = match answer {
+                Answer::Address(addr, _) => Some(Ok(addr)),
+                Answer::NoSuchName => Some(Err(OsError::NoEntry)),
+                Answer::Failure => {
+                    query.failed = true;
+                    query.server = None;
+                    query.deadline = now;
+                    None
+                }
+            };
+            if let Answer::Address(addr, ttl) = answer {
+                let name = query.name.clone();
+                self.dns.insert(&name, addr, ttl, now);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::*;
+
+/// The query `build_query(0x1234, "example.com", ..)` produces.
+const QUERY: &[u8] = &[
+    0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, //
+    7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, //
+    0, 1, 0, 1,
+];
+
+/// Returns a response to `QUERY` with the flags `flags` and the answer
+/// records `answers`, each of which is written as is after a pointer to the
+/// question's name.
+fn response(flags: u16, answers: &[&[u8]]) -> Vec<u8> {
+    let mut msg = QUERY.to_vec();
+    msg[2..4].copy_from_slice(&flags.to_be_bytes());
+    msg[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+    for answer in answers {
+        msg.extend_from_slice(&[0xc0, 12]);
+        msg.extend_from_slice(answer);
+    }
+    msg
+}
+
+/// An `A` record for 93.184.216.34 with a TTL of 300 seconds.
+const A_RECORD: &[u8] = &[0, 1, 0, 1, 0, 0, 1, 44, 0, 4, 93, 184, 216, 34];
+
+/// A `CNAME` record pointing to `www.example.com`.
+const CNAME_RECORD: &[u8] = &[
+    0, 5, 0, 1, 0, 0, 0, 60, 0, 6, 3, b'w', b'w', b'w', 0xc0, 12,
+];
+
+#[test]
+fn build_query_encodes_labels() {
+    let mut buf = [0; 512];
+    let len = build_query(0x1234, "example.com", &mut buf).expect("valid name");
+    assert_eq!(&buf[..len], QUERY);
+}
+
+#[test]
+fn build_query_rejects_invalid_names() {
+    let mut buf = [0; 512];
+    assert_eq!(build_query(1, "", &mut buf), None);
+    assert_eq!(build_query(1, "a..b", &mut buf), None);
+    let long_label = "a".repeat(64);
+    assert_eq!(build_query(1, &long_label, &mut buf), None);
+    assert_eq!(build_query(1, "example.com", &mut buf[..10]), None);
+}
+
+#[test]
+fn parse_address() {
+    let msg = response(0x8180, &[A_RECORD]);
+    assert_eq!(
+        parse_response(0x1234, &msg),
+        Some(Answer::Address(Ipv4Address::new(93, 184, 216, 34), 300))
+    );
+}
+
+#[test]
+fn parse_skips_cname() {
+    let msg = response(0x8180, &[CNAME_RECORD, A_RECORD]);
+    assert_eq!(
+        parse_response(0x1234, &msg),
+        Some(Answer::Address(Ipv4Address::new(93, 184, 216, 34), 300))
+    );
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(parse_response(0x1234, &response(0x8183, &[])), Some(Answer::NoSuchName));
+    assert_eq!(parse_response(0x1234, &response(0x8182, &[])), Some(Answer::Failure));
+    assert_eq!(parse_response(0x1234, &response(0x8180, &[])), Some(Answer::NoSuchName));
+}
+
+#[test]
+fn parse_rejects_other_messages() {
+    let msg = response(0x8180, &[A_RECORD]);
+    assert_eq!(parse_response(0x4321, &msg), None);
+    assert_eq!(parse_response(0x1234, QUERY), None);
+    assert_eq!(parse_response(0x1234, &msg[..msg.len() - 2]), None);
+    assert_eq!(message_id(&msg), Some(0x1234));
+    assert_eq!(message_id(&[0x12]), None);
+}
+
+#[test]
+fn hosts_table() {
+    let hosts = "# static hosts\n\
+                 127.0.0.1 localhost\n\
+                 ::1 localhost ip6-localhost\n\
+                 192.168.7.1  host gateway # the other end\n";
+    assert_eq!(parse_hosts(hosts, "localhost"), Some(Ipv4Address::new(127, 0, 0, 1)));
+    assert_eq!(parse_hosts(hosts, "GATEWAY"), Some(Ipv4Address::new(192, 168, 7, 1)));
+    assert_eq!(parse_hosts(hosts, "ip6-localhost"), None);
+    assert_eq!(parse_hosts(hosts, "other"), None);
+    assert_eq!(parse_hosts(hosts, "the"), None);
+}
+
+#[test]
+fn normalize_names() {
+    assert_eq!(normalize("Example.COM."), "example.com");
+    assert_eq!(normalize("host"), "host");
+}
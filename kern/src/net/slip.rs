@@ -0,0 +1,237 @@
+///! Network device that carries IP packets over the PL011 UART with SLIP
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use pi::interrupt::{Controller, Interrupt};
+use pi::pl011::Pl011;
+use slip::{Decoder, MAX_PACKET_SIZE};
+use smoltcp::phy::{self, Device, DeviceCapabilities};
+use smoltcp::time::Instant;
+use smoltcp::wire::{ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame};
+use smoltcp::wire::EthernetProtocol;
+
+use crate::mutex::{Mutex, Once};
+use crate::traps::irq::IrqHandlerRegistry;
+use crate::GLOBAL_IRQ;
+
+/// The locally administered MAC address of the SLIP device.
+pub const SLIP_ETH_ADDR: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 2]);
+
+/// The MAC address the SLIP device claims for every host on the other end of
+/// the serial line.
+const PEER_ETH_ADDR: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 3]);
+
+/// The length of an ethernet header.
+const ETH_HEADER_LEN: usize = 14;
+
+/// The maximum number of received packets waiting for the interface.
+const RX_QUEUE_SIZE: usize = 16;
+
+/// The maximum number of encoded bytes waiting for the UART.
+const TX_QUEUE_SIZE: usize = 8192;
+
+/// The state of the serial line, shared by the device and the UART interrupt
+/// handler.
+struct Port {
+    uart: Pl011,
+    decoder: Decoder,
+    /// Received packets, already wrapped in ethernet frames
+    rx: VecDeque<Vec<u8>>,
+    /// Encoded bytes waiting for the UART
+    tx: VecDeque<u8>,
+}
+
+static PORT: Once<Mutex<Port>> = Once::new();
+
+impl Port {
+    /// Moves received bytes through the decoder and queued bytes into the
+    /// UART, and keeps the transmit interrupt enabled only while bytes are
+    /// queued. This method does not block.
+    fn service(&mut self) {
+        while self.uart.has_byte() {
+            let byte = match self.uart.try_read_byte() {
+                Ok(byte) => byte,
+                Err(_) => continue,
+            };
+            if let Some(packet) = self.decoder.push(byte) {
+                match ip_to_ethernet(packet) {
+                    Some(frame) if self.rx.len() < RX_QUEUE_SIZE => self.rx.push_back(frame),
+                    Some(_) => debug!("slip receive queue is full; dropping a packet"),
+                    None => debug!("slip received a non-IPv4 packet"),
+                }
+            }
+        }
+
+        while self.uart.can_write() {
+            match self.tx.pop_front() {
+                Some(byte) => self.uart.write_byte(byte),
+                None => break,
+            }
+        }
+        self.uart.set_interrupts(true, !self.tx.is_empty());
+    }
+
+    /// Handles an ethernet frame from the interface. IPv4 packets are queued
+    /// for the serial line; ARP requests are answered on behalf of the peer.
+    fn transmit(&mut self, frame: &[u8]) {
+        let frame = match EthernetFrame::new_checked(frame) {
+            Ok(frame) => frame,
+            Err(_) => return,
+        };
+        match frame.ethertype() {
+            EthernetProtocol::Ipv4 => {
+                let packet = frame.payload();
+                if self.tx.len() + slip::encoded_len(packet) > TX_QUEUE_SIZE {
+                    debug!("slip transmit queue is full; dropping a packet");
+                    return;
+                }
+                let tx = &mut self.tx;
+                slip::encode(packet, |byte| tx.push_back(byte));
+                self.service();
+            }
+            EthernetProtocol::Arp => {
+                if let Some(reply) = arp_reply(frame.payload()) {
+                    self.rx.push_back(reply);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Wraps the IPv4 packet `packet` in an ethernet frame from the peer to the
+/// SLIP device. Returns `None` if `packet` is not an IPv4 packet.
+fn ip_to_ethernet(packet: &[u8]) -> Option<Vec<u8>> {
+    if packet.first().map(|b| b >> 4) != Some(4) {
+        return None;
+    }
+    let mut buf = vec![0; ETH_HEADER_LEN + packet.len()];
+    let mut frame = EthernetFrame::new_unchecked(&mut buf[..]);
+    frame.set_dst_addr(SLIP_ETH_ADDR);
+    frame.set_src_addr(PEER_ETH_ADDR);
+    frame.set_ethertype(EthernetProtocol::Ipv4);
+    frame.payload_mut().copy_from_slice(packet);
+    Some(buf)
+}
+
+/// Returns an ethernet frame that answers the ARP request `packet` with
+/// `PEER_ETH_ADDR`, since every address is reached through the serial line.
+fn arp_reply(packet: &[u8]) -> Option<Vec<u8>> {
+    let packet = ArpPacket::new_checked(packet).ok()?;
+    let (requester_hw, requester_ip, target_ip) = match ArpRepr::parse(&packet).ok()? {
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        } => (source_hardware_addr, source_protocol_addr, target_protocol_addr),
+        _ => return None,
+    };
+
+    let reply = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Reply,
+        source_hardware_addr: PEER_ETH_ADDR,
+        source_protocol_addr: target_ip,
+        target_hardware_addr: requester_hw,
+        target_protocol_addr: requester_ip,
+    };
+    let mut buf = vec![0; ETH_HEADER_LEN + reply.buffer_len()];
+    let mut frame = EthernetFrame::new_unchecked(&mut buf[..]);
+    frame.set_dst_addr(requester_hw);
+    frame.set_src_addr(PEER_ETH_ADDR);
+    frame.set_ethertype(EthernetProtocol::Arp);
+    reply.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+    Some(buf)
+}
+
+/// Services the serial line if the SLIP device has been created.
+fn service() {
+    if let Some(port) = PORT.get() {
+        port.lock().service();
+    }
+}
+
+/// A device that sends and receives IPv4 packets over the PL011 UART using
+/// SLIP (RFC 1055).
+///
+/// smoltcp only speaks ethernet, so the device strips the ethernet header of
+/// outgoing frames, wraps incoming packets in one, and answers every ARP
+/// request itself. The console must be on the mini UART. On real hardware
+/// both UARTs share GPIO pins 14 and 15, so this device is meant for QEMU,
+/// whose first serial port is the PL011; `lib/sliptap` bridges that port to a
+/// TUN interface on the host.
+#[derive(Debug)]
+pub struct Slip;
+
+impl Slip {
+    /// Initializes the PL011 and registers its interrupt handler.
+    pub fn new() -> Slip {
+        let mut uart = Pl011::new();
+        uart.set_interrupts(true, false);
+        PORT.call_once(|| {
+            Mutex::new(Port {
+                uart,
+                decoder: Decoder::new(),
+                rx: VecDeque::new(),
+                tx: VecDeque::new(),
+            })
+        });
+        GLOBAL_IRQ.register(Interrupt::Uart, Box::new(|_| service()));
+        Controller::new().enable(Interrupt::Uart);
+        Slip
+    }
+}
+
+impl<'a> Device<'a> for Slip {
+    type RxToken = RxToken;
+    type TxToken = TxToken;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capability = DeviceCapabilities::default();
+        capability.max_transmission_unit = ETH_HEADER_LEN + MAX_PACKET_SIZE;
+        capability
+    }
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        let mut port = PORT.get()?.lock();
+        port.service();
+        let frame = port.rx.pop_front()?;
+        Some((RxToken { frame }, TxToken))
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(TxToken)
+    }
+}
+
+pub struct RxToken {
+    frame: Vec<u8>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(&mut self.frame)
+    }
+}
+
+pub struct TxToken;
+
+impl phy::TxToken for TxToken {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+        if let Some(port) = PORT.get() {
+            port.lock().transmit(&frame);
+        }
+        result
+    }
+}
@@ -0,0 +1,324 @@
+///! Remote shell sessions over TCP with a minimal telnet (RFC 854) server
+use alloc::vec::Vec;
+use core::fmt;
+
+use smoltcp::socket::{SocketHandle, TcpState};
+
+use crate::net::EthernetDriver;
+use crate::shell::{self, LineEditor, Status};
+
+#[cfg(test)]
+mod tests;
+
+/// The maximum number of sessions, including the one waiting for a client.
+const MAX_SESSIONS: usize = 4;
+
+/// The prefix of each shell line.
+const PROMPT: &str = "> ";
+
+/// The number of bytes read from a socket at a time.
+const RECV_CHUNK: usize = 256;
+
+/// Telnet commands.
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+/// Telnet options.
+const OPT_ECHO: u8 = 1;
+const OPT_SUPPRESS_GO_AHEAD: u8 = 3;
+
+/// Returns the port in a `telnet=<port>` argument on the kernel command line.
+/// Without one, the server is disabled.
+pub fn port_from_cmdline(cmdline: &str) -> Option<u16> {
+    cmdline
+        .split(|c| c == ' ' || c == '\0')
+        .find_map(|arg| {
+            let mut parts = arg.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("telnet"), Some(port)) => port.parse().ok(),
+                _ => None,
+            }
+        })
+        .filter(|&port| port != 0)
+}
+
+/// What a byte from the client amounts to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Input {
+    /// Nothing yet; the byte is part of a command or a line ending.
+    None,
+    /// A data byte.
+    Byte(u8),
+    /// The end of a line: CR LF, CR NUL or a bare CR or LF.
+    Newline,
+    /// An option negotiation: `WILL`, `WONT`, `DO` or `DONT`, and the option.
+    Negotiate(u8, u8),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum DecodeState {
+    Data,
+    /// After a CR, which may be followed by LF or NUL
+    Cr,
+    /// After `IAC`
+    Command,
+    /// After `IAC` and a negotiation verb
+    Option(u8),
+    /// Inside a subnegotiation, which is skipped
+    Sub,
+    /// After `IAC` inside a subnegotiation
+    SubIac,
+}
+
+/// Separates the data a telnet client sends from its commands.
+#[derive(Debug)]
+pub struct Decoder {
+    state: DecodeState,
+}
+
+impl Decoder {
+    pub const fn new() -> Decoder {
+        Decoder {
+            state: DecodeState::Data,
+        }
+    }
+
+    /// Handles the byte `b` from the client.
+    pub fn push(&mut self, b: u8) -> Input {
+        use self::DecodeState::*;
+
+        let (state, input) = match (self.state, b) {
+            (Data, IAC) | (Cr, IAC) => (Command, Input::None),
+            (Cr, b'\n') | (Cr, 0) => (Data, Input::None),
+            (Data, b'\r') | (Cr, b'\r') => (Cr, Input::Newline),
+            (Data, b'\n') => (Data, Input::Newline),
+            (Data, b) | (Cr, b) => (Data, Input::Byte(b)),
+            (Command, IAC) => (Data, Input::Byte(IAC)),
+            (Command, SB) => (Sub, Input::None),
+            (Command, WILL..=DONT) => (Option(b), Input::None),
+            // Other commands, such as go-ahead or are-you-there, are ignored.
+            (Command, _) => (Data, Input::None),
+            (Option(verb), option) => (Data, Input::Negotiate(verb, option)),
+            (Sub, IAC) => (SubIac, Input::None),
+            (Sub, _) => (Sub, Input::None),
+            (SubIac, SE) => (Data, Input::None),
+            (SubIac, _) => (Sub, Input::None),
+        };
+        self.state = state;
+        input
+    }
+}
+
+/// Appends shell output to a buffer in the form telnet sends it, with
+/// newlines as CR LF. `IAC` never occurs in UTF-8, so it needs no escaping.
+pub struct Output<'a>(pub &'a mut Vec<u8>);
+
+impl fmt::Write for Output<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &b in s.as_bytes() {
+            match b {
+                b'\n' => self.0.extend_from_slice(b"\r\n"),
+                b => self.0.push(b),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Discards everything written to it, in place of the echo the client has
+/// asked us not to send.
+struct Discard;
+
+impl fmt::Write for Discard {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// A connection, or a socket waiting for one.
+struct Session {
+    handle: SocketHandle,
+    /// Set once a client has connected and been greeted
+    connected: bool,
+    decoder: Decoder,
+    editor: LineEditor,
+    /// Whether we echo what the client types
+    echo: bool,
+    /// Output the socket has not accepted yet
+    pending: Vec<u8>,
+    /// Set once the user has typed `exit`
+    exited: bool,
+}
+
+impl Session {
+    fn new(handle: SocketHandle) -> Session {
+        Session {
+            handle,
+            connected: false,
+            decoder: Decoder::new(),
+            editor: LineEditor::new(),
+            echo: true,
+            pending: Vec::new(),
+            exited: false,
+        }
+    }
+
+    /// Queues the option negotiation, a greeting and the first prompt. We
+    /// offer to echo and to suppress go-aheads, which puts clients in
+    /// character-at-a-time mode so that the shell's line editing works.
+    fn greet(&mut self) {
+        self.pending
+            .extend_from_slice(&[IAC, WILL, OPT_ECHO, IAC, WILL, OPT_SUPPRESS_GO_AHEAD]);
+        let mut out = Output(&mut self.pending);
+        let _ = fmt::Write::write_str(&mut out, "Welcome to cs3210!\n");
+        let _ = fmt::Write::write_str(&mut out, PROMPT);
+    }
+
+    /// Answers the client's option request. Only the options we offered are
+    /// enabled; everything else is refused. Acknowledgements of our own
+    /// requests are not answered, so negotiations cannot loop.
+    fn negotiate(&mut self, verb: u8, option: u8) {
+        let reply = match (verb, option) {
+            (DO, OPT_ECHO) if !self.echo => {
+                self.echo = true;
+                WILL
+            }
+            (DO, OPT_ECHO) | (DO, OPT_SUPPRESS_GO_AHEAD) => return,
+            (DO, _) => WONT,
+            (DONT, OPT_ECHO) if self.echo => {
+                self.echo = false;
+                WONT
+            }
+            (WILL, OPT_SUPPRESS_GO_AHEAD) => DO,
+            (WILL, _) => DONT,
+            _ => return,
+        };
+        self.pending.extend_from_slice(&[IAC, reply, option]);
+    }
+
+    /// Handles the byte `b` from the client, running a command once a line is
+    /// complete.
+    fn input(&mut self, b: u8) {
+        let b = match self.decoder.push(b) {
+            Input::None => return,
+            Input::Byte(b) => b,
+            Input::Newline => b'\n',
+            Input::Negotiate(verb, option) => return self.negotiate(verb, option),
+        };
+
+        let mut out = Output(&mut self.pending);
+        let complete = if self.echo {
+            self.editor.push(b, &mut out)
+        } else {
+            self.editor.push(b, &mut Discard)
+        };
+        if complete && !self.exited {
+            match shell::execute(self.editor.line(), &mut out) {
+                Status::Continue => {
+                    let _ = fmt::Write::write_str(&mut out, PROMPT);
+                }
+                Status::Exit => self.exited = true,
+            }
+        }
+    }
+}
+
+/// The telnet server: a listening socket and the sessions of connected
+/// clients, each running its own shell.
+pub struct TelnetServer {
+    port: u16,
+    sessions: Vec<Session>,
+}
+
+impl TelnetServer {
+    /// Creates a server for `port`, which the caller must have marked as
+    /// used.
+    pub fn new(port: u16) -> TelnetServer {
+        TelnetServer {
+            port,
+            sessions: Vec::new(),
+        }
+    }
+}
+
+impl EthernetDriver {
+    /// Drives the telnet server: greets new clients, feeds received bytes to
+    /// their shells, sends the output, and ends sessions that were closed by
+    /// either side. A socket listens for the next client while there is room
+    /// for another session.
+    pub(super) fn poll_telnet(&mut self) {
+        let mut server = match self.telnet.take() {
+            Some(server) => server,
+            None => return,
+        };
+
+        for session in server.sessions.iter_mut() {
+            let mut socket = self.get_socket(session.handle);
+            match socket.state() {
+                TcpState::Listen | TcpState::SynReceived => continue,
+                TcpState::CloseWait => socket.close(),
+                _ => (),
+            }
+            if !session.connected {
+                info!("telnet: session from {}", socket.remote_endpoint());
+                session.connected = true;
+                session.greet();
+            }
+
+            let mut buf = [0u8; RECV_CHUNK];
+            while socket.can_recv() {
+                let n = match socket.recv_slice(&mut buf) {
+                    Ok(n) => n,
+                    Err(_) => break,
+                };
+                for &b in &buf[..n] {
+                    session.input(b);
+                }
+            }
+
+            if !session.pending.is_empty() && socket.can_send() {
+                if let Ok(n) = socket.send_slice(&session.pending) {
+                    session.pending.drain(..n);
+                }
+            }
+            if session.exited && session.pending.is_empty() {
+                socket.close();
+            }
+        }
+
+        // Sessions end once both sides have closed, or on a reset.
+        let mut i = 0;
+        while i < server.sessions.len() {
+            let handle = server.sessions[i].handle;
+            match self.get_socket(handle).state() {
+                TcpState::Closed | TcpState::TimeWait => {
+                    if server.sessions[i].connected {
+                        info!("telnet: session closed");
+                    }
+                    server.sessions.swap_remove(i);
+                    self.release(handle);
+                }
+                _ => i += 1,
+            }
+        }
+
+        let listening = server.sessions.iter().any(|session| !session.connected);
+        if !listening && server.sessions.len() < MAX_SESSIONS {
+            let handle = self.add_socket();
+            match self.get_socket(handle).listen(server.port) {
+                Ok(()) => server.sessions.push(Session::new(handle)),
+                Err(e) => {
+                    debug!("telnet: failed to listen on {}: {:?}", server.port, e);
+                    self.release(handle);
+                }
+            }
+        }
+
+        self.telnet = Some(server);
+    }
+}
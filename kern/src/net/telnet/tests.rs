@@ -0,0 +1,58 @@
+use super::*;
+
+use core::fmt::Write;
+
+/// Feeds `bytes` to a new decoder and returns the inputs that are not
+/// `Input::None`.
+fn decode(bytes: &[u8]) -> Vec<Input> {
+    let mut decoder = Decoder::new();
+    bytes
+        .iter()
+        .map(|&b| decoder.push(b))
+        .filter(|&input| input != Input::None)
+        .collect()
+}
+
+#[test]
+fn line_endings() {
+    use self::Input::*;
+    assert_eq!(decode(b"ab\r\n"), [Byte(b'a'), Byte(b'b'), Newline]);
+    assert_eq!(decode(b"a\r\0b"), [Byte(b'a'), Newline, Byte(b'b')]);
+    assert_eq!(decode(b"a\nb\r"), [Byte(b'a'), Newline, Byte(b'b'), Newline]);
+    assert_eq!(decode(b"\r\r\n"), [Newline, Newline]);
+}
+
+#[test]
+fn commands() {
+    use self::Input::*;
+    assert_eq!(decode(&[b'a', IAC, IAC, b'b']), [Byte(b'a'), Byte(IAC), Byte(b'b')]);
+    assert_eq!(
+        decode(&[IAC, DO, OPT_ECHO, IAC, WONT, 24, b'x']),
+        [Negotiate(DO, OPT_ECHO), Negotiate(WONT, 24), Byte(b'x')]
+    );
+    // Go-ahead and are-you-there carry no data.
+    assert_eq!(decode(&[IAC, 249, IAC, 246, b'x']), [Byte(b'x')]);
+}
+
+#[test]
+fn subnegotiation_is_skipped() {
+    use self::Input::*;
+    let bytes = [b'a', IAC, SB, 24, 0, IAC, IAC, b'v', IAC, SE, b'b'];
+    assert_eq!(decode(&bytes), [Byte(b'a'), Byte(b'b')]);
+}
+
+#[test]
+fn output_translation() {
+    let mut buf = Vec::new();
+    write!(Output(&mut buf), "a\nb\n{}", 'ÿ').unwrap();
+    assert_eq!(buf, b"a\r\nb\r\n\xc3\xbf");
+}
+
+#[test]
+fn cmdline_port() {
+    assert_eq!(port_from_cmdline("console=pl011 telnet=23"), Some(23));
+    assert_eq!(port_from_cmdline("telnet=2323\0"), Some(2323));
+    assert_eq!(port_from_cmdline("telnet=0"), None);
+    assert_eq!(port_from_cmdline("telnet=x net=slip"), None);
+    assert_eq!(port_from_cmdline("net=slip"), None);
+}
@@ -11,10 +11,10 @@ use pi::interrupt::{Controller, Interrupt};
 use pi::timer::spin_sleep;
 use smoltcp::wire::EthernetAddress;
 
-use crate::mutex::Mutex;
+use crate::mutex::{Mutex, MutexGuard, Once};
 use crate::net::Frame;
 use crate::traps::irq::IrqHandlerRegistry;
-use crate::ALLOCATOR;
+use crate::{ALLOCATOR, FIQ, GLOBAL_IRQ};
 
 const DEBUG_USPI: bool = false;
 pub macro uspi_trace {
@@ -71,11 +71,17 @@ mod inner {
     impl !Sync for USPi {}
 
     impl USPi {
+        /// Initializes the USB stack. Returns `None` if USPi fails to find a
+        /// USB host controller, as happens under QEMU.
+        ///
         /// The caller should assure that this function is called only once
         /// during the lifetime of the kernel.
-        pub unsafe fn initialize() -> Self {
-            assert!(USPiInitialize() != 0);
-            USPi(())
+        pub unsafe fn initialize() -> Option<Self> {
+            if USPiInitialize() != 0 {
+                Some(USPi(()))
+            } else {
+                None
+            }
         }
 
         /// Returns whether ethernet is available on RPi
@@ -148,40 +154,56 @@ unsafe fn layout(size: usize) -> Layout {
     Layout::from_size_align_unchecked(size + core::mem::size_of::<usize>(), 16)
 }
 
+/// Allocates `size` bytes for USPi. The size of the allocation is stored in
+/// front of the returned pointer so that `free()` can rebuild the layout.
 #[no_mangle]
 fn malloc(size: u32) -> *mut c_void {
     // Lab 5 2.B
-    unimplemented!("malloc")
+    unsafe {
+        let ptr = ALLOCATOR.alloc(layout(size as usize));
+        if ptr.is_null() {
+            return ptr as *mut c_void;
+        }
+        (ptr as *mut usize).write(size as usize);
+        ptr.add(core::mem::size_of::<usize>()) as *mut c_void
+    }
 }
 
 #[no_mangle]
 fn free(ptr: *mut c_void) {
     // Lab 5 2.B
-    unimplemented!("free")
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        let ptr = (ptr as *mut u8).sub(core::mem::size_of::<usize>());
+        let size = (ptr as *const usize).read();
+        ALLOCATOR.dealloc(ptr, layout(size));
+    }
 }
 
 #[no_mangle]
 pub fn TimerSimpleMsDelay(nMilliSeconds: u32) {
     // Lab 5 2.B
-    unimplemented!("TimerSimpleMsDelay")
+    spin_sleep(Duration::from_millis(nMilliSeconds as u64));
 }
 
 #[no_mangle]
 pub fn TimerSimpleusDelay(nMicroSeconds: u32) {
     // Lab 5 2.B
-    unimplemented!("TimerSimpleusDelay")
+    spin_sleep(Duration::from_micros(nMicroSeconds as u64));
 }
 
 #[no_mangle]
 pub fn MsDelay(nMilliSeconds: u32) {
     // Lab 5 2.B
-    unimplemented!("MsDelay")
+    spin_sleep(Duration::from_millis(nMilliSeconds as u64));
 }
 
 #[no_mangle]
 pub fn usDelay(nMicroSeconds: u32) {
     // Lab 5 2.B
-    unimplemented!("usDelay")
+    spin_sleep(Duration::from_micros(nMicroSeconds as u64));
 }
 
 /// Registers `pHandler` to the kernel's IRQ handler registry.
@@ -193,14 +215,45 @@ pub fn usDelay(nMicroSeconds: u32) {
 #[no_mangle]
 pub unsafe fn ConnectInterrupt(nIRQ: u32, pHandler: TInterruptHandler, pParam: *mut c_void) {
     // Lab 5 2.B
-    unimplemented!("ConnectInterrupt")
+    let handler = match pHandler {
+        Some(handler) => handler,
+        None => return,
+    };
+    // Raw pointers are not `Send`; USPi owns `pParam` for as long as the
+    // handler is registered.
+    let param = pParam as usize;
+    let closure = Box::new(move |_: &mut crate::traps::TrapFrame| {
+        handler(param as *mut c_void);
+    });
+
+    let int = Interrupt::from(nIRQ as usize);
+    let mut controller = Controller::new();
+    if int == Interrupt::Usb {
+        FIQ.register((), closure);
+        controller.enable_fiq(int);
+    } else {
+        GLOBAL_IRQ.register(int, closure);
+        controller.enable(int);
+    }
+}
+
+/// Returns the NUL-terminated C string at `ptr` as a `&str`.
+unsafe fn c_str<'a>(ptr: *const u8) -> &'a str {
+    if ptr.is_null() {
+        return "";
+    }
+    let mut len = 0;
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(slice::from_raw_parts(ptr, len)).unwrap_or("<invalid utf-8>")
 }
 
 /// Writes a log message from USPi using `uspi_trace!` macro.
 #[no_mangle]
 pub unsafe fn DoLogWrite(_pSource: *const u8, _Severity: u32, pMessage: *const u8) {
     // Lab 5 2.B
-    unimplemented!("DoLogWrite")
+    uspi_trace!("[USPi] {}", c_str(pMessage));
 }
 
 #[no_mangle]
@@ -211,70 +264,75 @@ pub fn DebugHexdump(_pBuffer: *const c_void, _nBufLen: u32, _pSource: *const u8)
 #[no_mangle]
 pub unsafe fn uspi_assertion_failed(pExpr: *const u8, pFile: *const u8, nLine: u32) {
     // Lab 5 2.B
-    unimplemented!("uspi_assertion_failed")
+    panic!(
+        "USPi assertion `{}` failed at {}:{}",
+        c_str(pExpr),
+        c_str(pFile),
+        nLine
+    );
 }
 
-pub struct Usb(pub Mutex<Option<USPi>>);
+/// USPi, or `None` if it failed to initialize.
+pub struct Usb(Once<Option<Mutex<USPi>>>);
 
 impl Usb {
     pub const fn uninitialized() -> Usb {
-        Usb(Mutex::new(None))
+        Usb(Once::new())
     }
 
-    pub fn initialize(&self) {
-        let mut inner = self.0.lock();
-        if let None = *inner {
-            *inner = Some(unsafe { USPi::initialize() });
-        }
+    /// Initializes USPi on the first call. Returns `true` if USPi is
+    /// initialized. A failed initialization is not retried.
+    pub fn initialize(&self) -> bool {
+        self.0
+            .call_once(|| unsafe { USPi::initialize() }.map(Mutex::new))
+            .is_some()
     }
 
+    /// Returns `true` if USPi has been initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.0.get().map_or(false, Option::is_some)
+    }
+
+    /// Returns `true` if USPi has been initialized and found an ethernet
+    /// controller.
     pub fn is_eth_available(&self) -> bool {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("USB not initialized")
-            .is_eth_available()
+        match self.0.get() {
+            Some(Some(uspi)) => uspi.lock().is_eth_available(),
+            _ => false,
+        }
+    }
+
+    /// Locks USPi.
+    ///
+    /// # Panics
+    ///
+    /// Panics if USPi has not been initialized.
+    fn uspi(&self) -> MutexGuard<USPi> {
+        match self.0.get() {
+            Some(Some(uspi)) => uspi.lock(),
+            _ => panic!("USB not initialized"),
+        }
     }
 
     pub fn get_eth_addr(&self) -> EthernetAddress {
         let mut buf = [0; 6];
-        self.0
-            .lock()
-            .as_mut()
-            .expect("USB not initialized")
-            .get_mac_address(&mut buf);
+        self.uspi().get_mac_address(&mut buf);
         return EthernetAddress::from_bytes(&buf);
     }
 
     pub fn is_eth_link_up(&self) -> bool {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("USB not initialized")
-            .is_eth_link_up()
+        self.uspi().is_eth_link_up()
     }
 
     pub fn send_frame(&self, frame: &Frame) -> Option<i32> {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("USB not initialized")
-            .send_frame(frame)
+        self.uspi().send_frame(frame)
     }
 
     pub fn recv_frame(&self, frame: &mut Frame) -> Option<i32> {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("USB not initialized")
-            .recv_frame(frame)
+        self.uspi().recv_frame(frame)
     }
 
     pub fn start_kernel_timer(&self, delay: Duration, handler: TKernelTimerHandler) {
-        self.0
-            .lock()
-            .as_mut()
-            .expect("USB not initialized")
-            .start_kernel_timer(delay, handler)
+        self.uspi().start_kernel_timer(delay, handler)
     }
 }
@@ -9,6 +9,8 @@ pub use pi::common::*;
 pub const PAGE_ALIGN: usize = 16;
 pub const PAGE_SIZE: usize = 64 * 1024;
 pub const PAGE_MASK: usize = !(PAGE_SIZE - 1);
+// Spawned programs' arguments are copied into the top page of their stack.
+const_assert_eq!(kernel_api::ARGS_MAX, PAGE_SIZE);
 
 pub const USER_MASK_BITS: usize = 34;
 pub const KERNEL_MASK_BITS: usize = 31;
@@ -32,6 +34,10 @@ pub const KERN_STACK_SIZE: usize = PAGE_SIZE;
 pub const TICK: Duration = Duration::from_millis(100);
 // pub const TICK: Duration = Duration::from_micros(30);
 
+/// The shortest period of a process's interval timer, so that a periodic
+/// `SIGALRM` cannot keep the cores busy with timer interrupts.
+pub const ALARM_MIN_PERIOD: Duration = Duration::from_micros(100);
+
 // Match this value with `HZ` in `timer.h`
 pub const USPI_TIMER_HZ: usize = 10;
 
@@ -1,4 +1,4 @@
-use core::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 
 use crate::param::NCORES;
 use crate::traps::irq::LocalIrq;
@@ -12,6 +12,18 @@ pub struct PerCore {
     mmu_ready: AtomicBool,
     /// Local IRQ handler registry
     irq: LocalIrq,
+    /// ID of the thread this core runs, or `0` before the first one
+    thread: AtomicU64,
+    /// When the current time slice ends, in microseconds
+    slice_end: AtomicU64,
+    /// When the generic timer is armed to fire, in microseconds
+    timer_deadline: AtomicU64,
+    /// When the core went idle, in microseconds, or `0` while it runs a thread
+    idle_since: AtomicU64,
+    /// Time spent idle before `idle_since`, in microseconds
+    idle_time: AtomicU64,
+    /// Number of times the core has gone idle
+    idle_count: AtomicU64,
 }
 
 static PER_CORE_DATA: [PerCore; NCORES] = [
@@ -19,21 +31,45 @@ static PER_CORE_DATA: [PerCore; NCORES] = [
         preemption: AtomicI64::new(0),
         mmu_ready: AtomicBool::new(false),
         irq: LocalIrq::new(),
+        thread: AtomicU64::new(0),
+        slice_end: AtomicU64::new(0),
+        timer_deadline: AtomicU64::new(core::u64::MAX),
+        idle_since: AtomicU64::new(0),
+        idle_time: AtomicU64::new(0),
+        idle_count: AtomicU64::new(0),
     },
     PerCore {
         preemption: AtomicI64::new(0),
         mmu_ready: AtomicBool::new(false),
         irq: LocalIrq::new(),
+        thread: AtomicU64::new(0),
+        slice_end: AtomicU64::new(0),
+        timer_deadline: AtomicU64::new(core::u64::MAX),
+        idle_since: AtomicU64::new(0),
+        idle_time: AtomicU64::new(0),
+        idle_count: AtomicU64::new(0),
     },
     PerCore {
         preemption: AtomicI64::new(0),
         mmu_ready: AtomicBool::new(false),
         irq: LocalIrq::new(),
+        thread: AtomicU64::new(0),
+        slice_end: AtomicU64::new(0),
+        timer_deadline: AtomicU64::new(core::u64::MAX),
+        idle_since: AtomicU64::new(0),
+        idle_time: AtomicU64::new(0),
+        idle_count: AtomicU64::new(0),
     },
     PerCore {
         preemption: AtomicI64::new(0),
         mmu_ready: AtomicBool::new(false),
         irq: LocalIrq::new(),
+        thread: AtomicU64::new(0),
+        slice_end: AtomicU64::new(0),
+        timer_deadline: AtomicU64::new(core::u64::MAX),
+        idle_since: AtomicU64::new(0),
+        idle_time: AtomicU64::new(0),
+        idle_count: AtomicU64::new(0),
     },
 ];
 
@@ -79,3 +115,75 @@ pub fn local_irq() -> &'static LocalIrq {
     let cpu = aarch64::affinity();
     &PER_CORE_DATA[cpu].irq
 }
+
+/// Returns the ID of the thread the current core runs, or `0` if it has not
+/// run one yet.
+pub fn current_thread() -> u64 {
+    let cpu = aarch64::affinity();
+    PER_CORE_DATA[cpu].thread.load(Ordering::Relaxed)
+}
+
+/// Records that the current core runs the thread with ID `id`.
+pub fn set_current_thread(id: u64) {
+    let cpu = aarch64::affinity();
+    PER_CORE_DATA[cpu].thread.store(id, Ordering::Relaxed);
+}
+
+/// Returns when the current time slice of this core ends, in microseconds.
+pub fn slice_end() -> u64 {
+    let cpu = aarch64::affinity();
+    PER_CORE_DATA[cpu].slice_end.load(Ordering::Relaxed)
+}
+
+/// Sets when the current time slice of this core ends, in microseconds.
+pub fn set_slice_end(us: u64) {
+    let cpu = aarch64::affinity();
+    PER_CORE_DATA[cpu].slice_end.store(us, Ordering::Relaxed);
+}
+
+/// Returns when the generic timer of this core is armed to fire, in
+/// microseconds, or `u64::MAX` if it is not armed.
+pub fn timer_deadline() -> u64 {
+    let cpu = aarch64::affinity();
+    PER_CORE_DATA[cpu].timer_deadline.load(Ordering::Relaxed)
+}
+
+/// Records when the generic timer of this core is armed to fire, in
+/// microseconds.
+pub fn set_timer_deadline(us: u64) {
+    let cpu = aarch64::affinity();
+    PER_CORE_DATA[cpu].timer_deadline.store(us, Ordering::Relaxed);
+}
+
+/// Records that the current core has gone idle at `now`, in microseconds.
+pub fn enter_idle(now: u64) {
+    let cpu = aarch64::affinity();
+    PER_CORE_DATA[cpu].idle_since.store(now, Ordering::Relaxed);
+    PER_CORE_DATA[cpu].idle_count.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records that the current core has found a thread to run at `now`, in
+/// microseconds.
+pub fn leave_idle(now: u64) {
+    let cpu = aarch64::affinity();
+    let since = PER_CORE_DATA[cpu].idle_since.swap(0, Ordering::Relaxed);
+    if since != 0 {
+        PER_CORE_DATA[cpu]
+            .idle_time
+            .fetch_add(now.saturating_sub(since), Ordering::Relaxed);
+    }
+}
+
+/// Returns how long core `cpu` has been idle up to `now`, and how many times
+/// it has gone idle, with times in microseconds. Returns `None` if there is
+/// no such core.
+pub fn idle_stats(cpu: usize, now: u64) -> Option<(u64, u64)> {
+    let data = PER_CORE_DATA.get(cpu)?;
+    let count = data.idle_count.load(Ordering::Relaxed);
+    let mut time = data.idle_time.load(Ordering::Relaxed);
+    let since = data.idle_since.load(Ordering::Relaxed);
+    if since != 0 {
+        time += now.saturating_sub(since);
+    }
+    Some((time, count))
+}
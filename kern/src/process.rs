@@ -1,10 +1,22 @@
+mod futex;
+mod pipe;
+mod port;
 mod process;
 mod scheduler;
+mod shm;
+pub mod signal;
 mod stack;
 mod state;
+pub mod thread;
 
-pub use self::process::{Id, Process};
+pub use self::futex::{FutexTable, Futexes, Waiter};
+pub use self::pipe::{End, Pipe, PipeEnd, PIPE_SIZE};
+pub use self::port::{Outcome, Pending, PortTable, Ports, Receiver};
+pub use self::process::{Id, Process, KERNEL_PID, MAX_EXITED};
 pub use self::scheduler::GlobalScheduler;
+pub use self::shm::{Mapping, Region, RegionTable, SharedMemory, SHM_BASE, SHM_MAX_PAGES};
+pub use self::signal::{Action, Signals};
 pub use self::stack::Stack;
 pub use self::state::State;
+pub use self::thread::Thread;
 pub use crate::param::TICK;
@@ -0,0 +1,109 @@
+use alloc::vec::Vec;
+
+use crate::mutex::{Lazy, Mutex};
+use crate::FUTEXES;
+
+#[cfg(test)]
+mod tests;
+
+/// The wait queues of every futex, keyed by the physical address of the futex
+/// word, so that processes mapping the same page at different addresses
+/// share a queue.
+///
+/// A waiter is identified by a token. Waking it moves the token from its
+/// queue to the woken set, where the waiting process picks it up the next time
+/// the scheduler polls it.
+#[derive(Debug)]
+pub struct FutexTable {
+    /// Waiting tokens and the keys they wait on, in the order they arrived
+    waiters: Vec<(usize, u64)>,
+    /// Tokens that have been woken but not picked up yet
+    woken: Vec<u64>,
+    next_token: u64,
+}
+
+impl FutexTable {
+    /// Returns a table without waiters.
+    pub fn new() -> FutexTable {
+        FutexTable {
+            waiters: Vec::new(),
+            woken: Vec::new(),
+            next_token: 1,
+        }
+    }
+
+    /// Adds a waiter to the end of the queue of `key` and returns its token.
+    pub fn wait(&mut self, key: usize) -> u64 {
+        let token = self.next_token;
+        self.next_token += 1;
+        self.waiters.push((key, token));
+        token
+    }
+
+    /// Wakes up to `n` of the oldest waiters on `key` and returns how many
+    /// were woken.
+    pub fn wake(&mut self, key: usize, n: usize) -> usize {
+        let mut woken = 0;
+        let mut i = 0;
+        while i < self.waiters.len() && woken < n {
+            if self.waiters[i].0 == key {
+                let (_, token) = self.waiters.remove(i);
+                self.woken.push(token);
+                woken += 1;
+            } else {
+                i += 1;
+            }
+        }
+        woken
+    }
+
+    /// Returns `true` if `token` has been woken, and forgets it if so.
+    pub fn take_woken(&mut self, token: u64) -> bool {
+        match self.woken.iter().position(|&t| t == token) {
+            Some(i) => {
+                self.woken.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes `token` from its queue, or from the woken set if it has been
+    /// woken already.
+    pub fn cancel(&mut self, token: u64) {
+        self.waiters.retain(|&(_, t)| t != token);
+        self.woken.retain(|&t| t != token);
+    }
+}
+
+impl Default for FutexTable {
+    fn default() -> FutexTable {
+        FutexTable::new()
+    }
+}
+
+/// The futex wait queues of the machine.
+pub type Futexes = Lazy<Mutex<FutexTable>>;
+
+/// A process's place in a futex queue. The waiter leaves the queue when it is
+/// dropped, whether it has been woken, has timed out or has been interrupted.
+#[derive(Debug)]
+pub struct Waiter(u64);
+
+impl Waiter {
+    /// Adds a waiter to the queue of `key`.
+    pub fn new(futexes: &mut FutexTable, key: usize) -> Waiter {
+        Waiter(futexes.wait(key))
+    }
+
+    /// Returns `true` if this waiter has been woken.
+    pub fn is_woken(&self) -> bool {
+        FUTEXES.critical(|futexes| futexes.take_woken(self.0))
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        FUTEXES.critical(|futexes| futexes.cancel(self.0));
+    }
+}
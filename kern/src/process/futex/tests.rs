@@ -0,0 +1,40 @@
+use super::*;
+
+#[test]
+fn wakes_in_order() {
+    let mut table = FutexTable::new();
+    let a = table.wait(0x1000);
+    let b = table.wait(0x2000);
+    let c = table.wait(0x1000);
+    let d = table.wait(0x1000);
+
+    assert_eq!(table.wake(0x1000, 2), 2);
+    assert!(table.take_woken(a));
+    assert!(!table.take_woken(a));
+    assert!(!table.take_woken(b));
+    assert!(table.take_woken(c));
+    assert!(!table.take_woken(d));
+
+    assert_eq!(table.wake(0x1000, 5), 1);
+    assert!(table.take_woken(d));
+    assert_eq!(table.wake(0x2000, 1), 1);
+    assert!(table.take_woken(b));
+    assert_eq!(table.wake(0x2000, 1), 0);
+}
+
+#[test]
+fn cancelled_waiters_are_skipped() {
+    let mut table = FutexTable::new();
+    let a = table.wait(0x1000);
+    let b = table.wait(0x1000);
+    table.cancel(a);
+    assert_eq!(table.wake(0x1000, 1), 1);
+    assert!(table.take_woken(b));
+
+    // A waiter cancelled after it was woken is forgotten too.
+    let c = table.wait(0x1000);
+    assert_eq!(table.wake(0x1000, 1), 1);
+    table.cancel(c);
+    assert!(!table.take_woken(c));
+    assert!(table.waiters.is_empty() && table.woken.is_empty());
+}
@@ -0,0 +1,155 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::cmp::min;
+use core::fmt;
+
+use kernel_api::{OsError, OsResult};
+
+use crate::mutex::{Mutex, MutexGuard};
+
+#[cfg(test)]
+mod tests;
+
+/// The number of bytes a pipe buffers before writers block.
+pub const PIPE_SIZE: usize = 4096;
+
+/// Which end of a pipe a descriptor refers to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum End {
+    Read,
+    Write,
+}
+
+/// A bounded byte buffer with a read end and a write end, each of which may
+/// be held by several processes. The pipe counts the open ends of each kind
+/// so that readers see end-of-file once every writer is gone, and writers
+/// fail once every reader is gone.
+#[derive(Debug)]
+pub struct Pipe {
+    buf: VecDeque<u8>,
+    readers: usize,
+    writers: usize,
+}
+
+impl Pipe {
+    /// Returns an empty pipe with no open ends.
+    pub fn new() -> Pipe {
+        Pipe {
+            buf: VecDeque::new(),
+            readers: 0,
+            writers: 0,
+        }
+    }
+
+    /// Records that an `end` has been opened.
+    pub fn open(&mut self, end: End) {
+        match end {
+            End::Read => self.readers += 1,
+            End::Write => self.writers += 1,
+        }
+    }
+
+    /// Records that an `end` has been closed.
+    pub fn close(&mut self, end: End) {
+        match end {
+            End::Read => self.readers -= 1,
+            End::Write => self.writers -= 1,
+        }
+    }
+
+    /// Moves up to `buf.len()` buffered bytes into `buf`. Returns the number
+    /// of bytes read, `Some(0)` at end-of-file, or `None` if the pipe is empty
+    /// but a writer may still fill it.
+    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.buf.is_empty() && self.writers > 0 && !buf.is_empty() {
+            return None;
+        }
+        let n = min(buf.len(), self.buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.buf.drain(..n)) {
+            *dst = src;
+        }
+        Some(n)
+    }
+
+    /// Appends as much of `buf` as there is room for and returns the number
+    /// of bytes written, which is `0` if the pipe is full.
+    ///
+    /// # Errors
+    /// Returns `OsError::IoErrorBrokenPipe` if every read end is closed.
+    pub fn write(&mut self, buf: &[u8]) -> OsResult<usize> {
+        if self.readers == 0 {
+            return Err(OsError::IoErrorBrokenPipe);
+        }
+        let n = min(buf.len(), PIPE_SIZE - self.buf.len());
+        self.buf.extend(&buf[..n]);
+        Ok(n)
+    }
+
+    /// Returns `true` if a read would not block.
+    pub fn can_read(&self) -> bool {
+        !self.buf.is_empty() || self.writers == 0
+    }
+
+    /// Returns `true` if a write would not block.
+    pub fn can_write(&self) -> bool {
+        self.buf.len() < PIPE_SIZE || self.readers == 0
+    }
+
+    /// Returns `true` if every end of the other kind than `end` is closed.
+    pub fn is_hung_up(&self, end: End) -> bool {
+        match end {
+            End::Read => self.writers == 0,
+            End::Write => self.readers == 0,
+        }
+    }
+}
+
+/// One end of a pipe held by a process. Cloning an end opens it once more;
+/// dropping an end closes it.
+pub struct PipeEnd {
+    pipe: Arc<Mutex<Pipe>>,
+    end: End,
+}
+
+impl PipeEnd {
+    /// Creates a pipe and returns its read end and its write end.
+    pub fn pair() -> (PipeEnd, PipeEnd) {
+        let pipe = Arc::new(Mutex::new(Pipe::new()));
+        let read = PipeEnd::open(pipe.clone(), End::Read);
+        let write = PipeEnd::open(pipe, End::Write);
+        (read, write)
+    }
+
+    fn open(pipe: Arc<Mutex<Pipe>>, end: End) -> PipeEnd {
+        pipe.lock().open(end);
+        PipeEnd { pipe, end }
+    }
+
+    /// Returns which end of the pipe this is.
+    pub fn end(&self) -> End {
+        self.end
+    }
+
+    /// Locks the pipe and returns it.
+    pub fn lock(&self) -> MutexGuard<'_, Pipe> {
+        self.pipe.lock()
+    }
+}
+
+impl Clone for PipeEnd {
+    fn clone(&self) -> PipeEnd {
+        PipeEnd::open(self.pipe.clone(), self.end)
+    }
+}
+
+impl Drop for PipeEnd {
+    fn drop(&mut self) {
+        self.pipe.lock().close(self.end);
+    }
+}
+
+impl fmt::Debug for PipeEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PipeEnd({:?})", self.end)
+    }
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+fn open_pipe() -> Pipe {
+    let mut pipe = Pipe::new();
+    pipe.open(End::Read);
+    pipe.open(End::Write);
+    pipe
+}
+
+#[test]
+fn read_write() {
+    let mut pipe = open_pipe();
+    let mut buf = [0u8; 8];
+    assert_eq!(pipe.read(&mut buf), None);
+    assert!(!pipe.can_read());
+
+    assert_eq!(pipe.write(b"hello"), Ok(5));
+    assert!(pipe.can_read());
+    assert_eq!(pipe.read(&mut buf[..3]), Some(3));
+    assert_eq!(&buf[..3], b"hel");
+    assert_eq!(pipe.write(b" world"), Ok(6));
+    assert_eq!(pipe.read(&mut buf), Some(8));
+    assert_eq!(&buf, b"lo world");
+    assert_eq!(pipe.read(&mut buf), None);
+}
+
+#[test]
+fn full_pipe() {
+    let mut pipe = open_pipe();
+    let data = [7u8; PIPE_SIZE + 10];
+    assert_eq!(pipe.write(&data), Ok(PIPE_SIZE));
+    assert!(!pipe.can_write());
+    assert_eq!(pipe.write(&data), Ok(0));
+
+    let mut buf = [0u8; 16];
+    assert_eq!(pipe.read(&mut buf), Some(16));
+    assert!(pipe.can_write());
+    assert_eq!(pipe.write(&data), Ok(16));
+}
+
+#[test]
+fn eof_after_last_writer() {
+    let mut pipe = open_pipe();
+    pipe.open(End::Write);
+    pipe.write(b"abc").unwrap();
+    pipe.close(End::Write);
+    assert!(!pipe.is_hung_up(End::Read));
+    pipe.close(End::Write);
+    assert!(pipe.is_hung_up(End::Read));
+
+    // Buffered bytes are still delivered before end-of-file.
+    let mut buf = [0u8; 8];
+    assert_eq!(pipe.read(&mut buf), Some(3));
+    assert_eq!(pipe.read(&mut buf), Some(0));
+    assert!(pipe.can_read());
+}
+
+#[test]
+fn broken_pipe() {
+    let mut pipe = open_pipe();
+    pipe.close(End::Read);
+    assert!(pipe.is_hung_up(End::Write));
+    assert!(pipe.can_write());
+    assert_eq!(pipe.write(b"abc"), Err(OsError::IoErrorBrokenPipe));
+}
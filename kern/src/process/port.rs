@@ -0,0 +1,256 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use kernel_api::{Message, OsError, OsResult};
+
+use crate::mutex::{Lazy, Mutex};
+use crate::process::Id;
+use crate::PORTS;
+
+#[cfg(test)]
+mod tests;
+
+/// What has become of a sent message once it has left its port's queue.
+#[derive(Debug)]
+pub enum Outcome {
+    /// A receiver took the message, which expects no reply.
+    Delivered,
+    /// The receiver answered the call with this message.
+    Replied(Message),
+    /// The port was destroyed, or its owner exited, before the message was
+    /// taken or the call answered.
+    Closed,
+}
+
+/// A message waiting in the queue of a port.
+#[derive(Debug)]
+struct Envelope {
+    token: u64,
+    message: Message,
+    /// `true` if the sender waits for a reply
+    call: bool,
+}
+
+/// A port: a queue of messages that only the process owning it receives.
+#[derive(Debug)]
+struct Port {
+    id: u64,
+    owner: Id,
+    queue: VecDeque<Envelope>,
+    /// Threads blocked receiving on the port, oldest first
+    receivers: Vec<Id>,
+}
+
+/// The ports of the machine and the messages in flight.
+///
+/// Every sent message is identified by a token. Its sender waits until the
+/// message has an outcome, which it picks up the next time the scheduler
+/// polls it. A call that a receiver has taken stays pending until the
+/// receiver replies to its token.
+#[derive(Debug)]
+pub struct PortTable {
+    ports: Vec<Port>,
+    /// Calls taken but not answered yet, and the process that took them
+    calls: Vec<(u64, Id)>,
+    /// Outcomes that their senders have not picked up yet
+    done: Vec<(u64, Outcome)>,
+    next_id: u64,
+    next_token: u64,
+}
+
+impl PortTable {
+    /// Returns a table without ports.
+    pub fn new() -> PortTable {
+        PortTable {
+            ports: Vec::new(),
+            calls: Vec::new(),
+            done: Vec::new(),
+            next_id: 1,
+            next_token: 1,
+        }
+    }
+
+    /// Creates a port whose messages the process `owner` receives, and
+    /// returns its ID. IDs are not reused.
+    pub fn create(&mut self, owner: Id) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ports.push(Port {
+            id,
+            owner,
+            queue: VecDeque::new(),
+            receivers: Vec::new(),
+        });
+        id
+    }
+
+    /// Destroys the port `id` of the process `owner`. The messages in its
+    /// queue are `Closed`; calls already taken can still be answered.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::NoEntry)` if there is no such port, or
+    /// `Err(OsError::NoAccess)` if `owner` does not own it.
+    pub fn destroy(&mut self, id: u64, owner: Id) -> OsResult<()> {
+        let i = self.owned_port(id, owner)?;
+        let port = self.ports.remove(i);
+        for envelope in port.queue {
+            self.done.push((envelope.token, Outcome::Closed));
+        }
+        Ok(())
+    }
+
+    /// Destroys every port of the process `owner` and closes the calls it
+    /// has not answered, because it has exited.
+    pub fn release(&mut self, owner: Id) {
+        let PortTable { ports, calls, done, .. } = self;
+        for port in ports.iter_mut().filter(|port| port.owner == owner) {
+            for envelope in port.queue.drain(..) {
+                done.push((envelope.token, Outcome::Closed));
+            }
+        }
+        ports.retain(|port| port.owner != owner);
+        for &(token, _) in calls.iter().filter(|&&(_, taker)| taker == owner) {
+            done.push((token, Outcome::Closed));
+        }
+        calls.retain(|&(_, taker)| taker != owner);
+    }
+
+    /// Queues `message` on the port `id`, as a call if `call` is `true`.
+    /// Returns the token of the message and a thread blocked receiving on
+    /// the port, if any, which the sender may hand the CPU to.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::NoEntry)` if there is no such port.
+    pub fn send(&mut self, id: u64, message: Message, call: bool) -> OsResult<(u64, Option<Id>)> {
+        let token = self.next_token;
+        let port = self.ports.iter_mut().find(|port| port.id == id).ok_or(OsError::NoEntry)?;
+        port.queue.push_back(Envelope { token, message, call });
+        self.next_token += 1;
+        Ok((token, port.receivers.first().cloned()))
+    }
+
+    /// Takes the oldest message from the port `id` of the process `owner`.
+    /// Returns the message and, if it is a call, the token to reply to, or
+    /// `0` otherwise. Returns `Ok(None)` if the queue is empty.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::NoEntry)` if there is no such port, or
+    /// `Err(OsError::NoAccess)` if `owner` does not own it.
+    pub fn receive(&mut self, id: u64, owner: Id) -> OsResult<Option<(Message, u64)>> {
+        let i = self.owned_port(id, owner)?;
+        let envelope = match self.ports[i].queue.pop_front() {
+            Some(envelope) => envelope,
+            None => return Ok(None),
+        };
+        if envelope.call {
+            self.calls.push((envelope.token, owner));
+            Ok(Some((envelope.message, envelope.token)))
+        } else {
+            self.done.push((envelope.token, Outcome::Delivered));
+            Ok(Some((envelope.message, 0)))
+        }
+    }
+
+    /// Answers the call `token`, taken by the process `owner`, with
+    /// `message`.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::NoEntry)` if `owner` has no unanswered call with
+    /// the token, for instance because its caller has given up.
+    pub fn reply(&mut self, token: u64, owner: Id, message: Message) -> OsResult<()> {
+        let i = self
+            .calls
+            .iter()
+            .position(|&call| call == (token, owner))
+            .ok_or(OsError::NoEntry)?;
+        self.calls.swap_remove(i);
+        self.done.push((token, Outcome::Replied(message)));
+        Ok(())
+    }
+
+    /// Returns the outcome of the message `token` and forgets it, if it has
+    /// one yet.
+    pub fn take_outcome(&mut self, token: u64) -> Option<Outcome> {
+        let i = self.done.iter().position(|&(t, _)| t == token)?;
+        Some(self.done.swap_remove(i).1)
+    }
+
+    /// Withdraws the message `token` from its queue, or forgets the call or
+    /// the outcome it has become.
+    pub fn cancel(&mut self, token: u64) {
+        for port in self.ports.iter_mut() {
+            port.queue.retain(|envelope| envelope.token != token);
+        }
+        self.calls.retain(|&(t, _)| t != token);
+        self.done.retain(|&(t, _)| t != token);
+    }
+
+    /// Returns the index of the port `id` if `owner` owns it.
+    fn owned_port(&self, id: u64, owner: Id) -> OsResult<usize> {
+        let i = self.ports.iter().position(|port| port.id == id).ok_or(OsError::NoEntry)?;
+        if self.ports[i].owner != owner {
+            return Err(OsError::NoAccess);
+        }
+        Ok(i)
+    }
+}
+
+impl Default for PortTable {
+    fn default() -> PortTable {
+        PortTable::new()
+    }
+}
+
+/// The ports of the machine.
+pub type Ports = Lazy<Mutex<PortTable>>;
+
+/// A message its sender waits on. The message is withdrawn when this is
+/// dropped, whether it has an outcome or the wait was interrupted.
+#[derive(Debug)]
+pub struct Pending(u64);
+
+impl Pending {
+    /// Tracks the message `token`.
+    pub fn new(token: u64) -> Pending {
+        Pending(token)
+    }
+
+    /// Returns the outcome of the message, if it has one yet.
+    pub fn outcome(&self) -> Option<Outcome> {
+        PORTS.critical(|ports| ports.take_outcome(self.0))
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        PORTS.critical(|ports| ports.cancel(self.0));
+    }
+}
+
+/// A thread blocked receiving on a port. While it exists, senders to the
+/// port hand the CPU straight to the thread.
+#[derive(Debug)]
+pub struct Receiver {
+    port: u64,
+    thread: Id,
+}
+
+impl Receiver {
+    /// Registers `thread` as receiving on the port `port`.
+    pub fn new(ports: &mut PortTable, port: u64, thread: Id) -> Receiver {
+        if let Some(port) = ports.ports.iter_mut().find(|p| p.id == port) {
+            port.receivers.push(thread);
+        }
+        Receiver { port, thread }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        PORTS.critical(|ports| {
+            if let Some(port) = ports.ports.iter_mut().find(|p| p.id == self.port) {
+                port.receivers.retain(|&t| t != self.thread);
+            }
+        });
+    }
+}
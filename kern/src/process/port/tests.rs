@@ -0,0 +1,84 @@
+use super::*;
+
+fn message(label: u64) -> Message {
+    Message::new(label)
+}
+
+#[test]
+fn send_and_receive() {
+    let mut table = PortTable::new();
+    let port = table.create(1);
+    assert_eq!(table.receive(port, 1).unwrap().map(|(m, _)| m.label), None);
+
+    let (a, receiver) = table.send(port, message(10), false).unwrap();
+    assert_eq!(receiver, None);
+    let (b, _) = table.send(port, message(20), false).unwrap();
+    assert!(table.take_outcome(a).is_none());
+
+    let (first, token) = table.receive(port, 1).unwrap().unwrap();
+    assert_eq!((first.label, token), (10, 0));
+    assert!(match table.take_outcome(a) {
+        Some(Outcome::Delivered) => true,
+        _ => false,
+    });
+    assert!(table.take_outcome(a).is_none());
+    assert!(table.take_outcome(b).is_none());
+    assert_eq!(table.receive(port, 1).unwrap().unwrap().0.label, 20);
+
+    // Only the owner receives, and unknown ports are errors.
+    assert_eq!(table.receive(port, 2).err(), Some(OsError::NoAccess));
+    assert_eq!(table.receive(port + 1, 1).err(), Some(OsError::NoEntry));
+    assert_eq!(table.send(port + 1, message(0), false).err(), Some(OsError::NoEntry));
+}
+
+#[test]
+fn call_and_reply() {
+    let mut table = PortTable::new();
+    let port = table.create(1);
+    let (call, _) = table.send(port, message(1), true).unwrap();
+    let (_, token) = table.receive(port, 1).unwrap().unwrap();
+    assert_eq!(token, call);
+    assert!(table.take_outcome(call).is_none());
+
+    assert_eq!(table.reply(token, 2, message(2)), Err(OsError::NoEntry));
+    assert_eq!(table.reply(token, 1, message(2)), Ok(()));
+    assert_eq!(table.reply(token, 1, message(3)), Err(OsError::NoEntry));
+    match table.take_outcome(call) {
+        Some(Outcome::Replied(reply)) => assert_eq!(reply.label, 2),
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+
+    // A caller that gives up cannot be answered.
+    let (call, _) = table.send(port, message(1), true).unwrap();
+    let (_, token) = table.receive(port, 1).unwrap().unwrap();
+    table.cancel(call);
+    assert_eq!(table.reply(token, 1, message(2)), Err(OsError::NoEntry));
+    assert!(table.done.is_empty() && table.calls.is_empty());
+}
+
+#[test]
+fn closed_ports() {
+    let mut table = PortTable::new();
+    let a = table.create(1);
+    let b = table.create(1);
+    let (queued, _) = table.send(a, message(1), false).unwrap();
+    let (taken, _) = table.send(b, message(2), true).unwrap();
+    let (waiting, _) = table.send(b, message(3), true).unwrap();
+    table.receive(b, 1).unwrap().unwrap();
+
+    assert_eq!(table.destroy(a, 2), Err(OsError::NoAccess));
+    assert_eq!(table.destroy(a, 1), Ok(()));
+    assert!(match table.take_outcome(queued) {
+        Some(Outcome::Closed) => true,
+        _ => false,
+    });
+
+    table.release(1);
+    for &token in [taken, waiting].iter() {
+        assert!(match table.take_outcome(token) {
+            Some(Outcome::Closed) => true,
+            _ => false,
+        });
+    }
+    assert_eq!(table.send(b, message(4), false).err(), Some(OsError::NoEntry));
+}
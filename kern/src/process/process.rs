@@ -1,66 +1,100 @@
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use shim::io;
-use shim::path::Path;
+use shim::path::{Component, Path, PathBuf};
 
 use aarch64;
 use smoltcp::socket::SocketHandle;
 
+use crate::net::SocketKind;
 use crate::param::*;
-use crate::process::{Stack, State};
-use crate::traps::TrapFrame;
+use crate::process::{Mapping, PipeEnd, Region, Signals, Stack, Thread, SHM_BASE};
+use crate::timer::Alarm;
 use crate::vm::*;
-use kernel_api::{OsError, OsResult};
+use kernel_api::{OsError, OsResult, ARGS_MAX};
 
-use core::mem::replace;
 use crate::FILESYSTEM;
 
-/// Type alias for the type of a process ID.
+/// Type alias for the type of a process or thread ID.
 pub type Id = u64;
 
-/// A structure that represents the complete state of a process.
+/// The ID of the process kernel threads belong to. It has no user mappings
+/// and is never sent signals.
+pub const KERNEL_PID: Id = 0;
+
+/// The most exit statuses a process keeps for children it has not waited
+/// for. A process that never waits, like a shell with background jobs, loses
+/// the oldest ones instead of growing without bound.
+pub const MAX_EXITED: usize = 64;
+
+/// A structure that represents the state a process's threads share: its
+/// address space, its descriptors and its signals.
 #[derive(Debug)]
 pub struct Process {
-    /// The saved trap frame of a process.
-    pub context: Box<TrapFrame>,
-    /// The memory allocation used for the process's stack.
-    pub stack: Stack,
+    /// The ID of the process, which is also the ID of its first thread.
+    pub id: Id,
     /// The page table describing the Virtual Memory of the process
     pub vmap: Box<UserPageTable>,
-    /// The scheduling state of the process.
-    pub state: State,
+    /// The ID of the process that spawned this one, if it is still alive.
+    pub parent: Option<Id>,
+    /// Exit statuses of children that have exited but not been waited for,
+    /// oldest first, at most `MAX_EXITED`.
+    pub exited: Vec<(Id, u64)>,
+    /// Values of threads of this process that have exited but not been
+    /// joined.
+    pub exited_threads: Vec<(Id, u64)>,
+    /// Set once the process has exited. Its threads running on other cores
+    /// are reclaimed the next time they enter the scheduler.
+    pub exiting: bool,
+    /// The working directory against which relative paths are resolved.
+    pub cwd: PathBuf,
     // Lab 5 2.C
-    // Socket handles held by the current process
-    // pub sockets: Vec<SocketHandle>,
+    /// Socket handles held by the current process, indexed by descriptor
+    pub sockets: Vec<(SocketKind, SocketHandle)>,
+    /// Pipe ends held by the current process, indexed by descriptor. Closed
+    /// descriptors are `None` until they are reused.
+    pub pipes: Vec<Option<PipeEnd>>,
+    /// The pipe console reads come from instead of the console, if any
+    pub stdin: Option<PipeEnd>,
+    /// The pipe console writes go to instead of the console, if any
+    pub stdout: Option<PipeEnd>,
+    /// Pending and blocked signals, and the action of every signal
+    pub signals: Signals,
+    /// The shared-memory regions mapped into the address space
+    pub mappings: Vec<Mapping>,
+    /// The interval timer of the process, if it is armed
+    pub alarm: Option<Alarm>,
 }
 
 impl Process {
-    /// Creates a new process with a zeroed `TrapFrame` (the default), a zeroed
-    /// stack of the default size, and a state of `Ready`.
+    /// Creates a new process with an empty address space and no threads. The
+    /// scheduler assigns its ID when it is added.
     ///
     /// If enough memory could not be allocated to start the process, returns
-    /// `None`. Otherwise returns `Some` of the new `Process`.
+    /// an error. Otherwise returns `Ok` of the new `Process`.
     pub fn new() -> OsResult<Process> {
-        let tf = TrapFrame{
-            ttbr0_el: 0,
-            ttbr1_el: 0,
-            elr_el: 0,
-            spsr_el: 0,
-            sp_el: 0,
-            tpidr_el: 0,
-            qs: [0; 32],
-            xs: [0; 32],
-        };
         return Ok(Process{
-            context: Box::<TrapFrame>::new(tf),
-            stack: Stack::new().unwrap(),
+            id: 0,
             vmap: Box::new(UserPageTable::new()),
-            state: State::Ready,
+            parent: None,
+            exited: Vec::new(),
+            exited_threads: Vec::new(),
+            exiting: false,
+            cwd: Path::new("/").to_path_buf(),
+            sockets: Vec::new(),
+            pipes: Vec::new(),
+            stdin: None,
+            stdout: None,
+            signals: Signals::new(),
+            mappings: Vec::new(),
+            alarm: None,
         });
     }
 
-    /// Loads a program stored in the given path by calling `do_load()` method.
-    /// Sets trapframe `context` corresponding to its page table.
+    /// Loads a program stored in the given path by calling `do_load()` method,
+    /// and returns the process with its first thread. Sets trapframe
+    /// `context` of the thread corresponding to its page table.
     /// `sp` - the address of stack top
     /// `elr` - the address of image base.
     /// `ttbr0` - the base address of kernel page table
@@ -68,18 +102,18 @@ impl Process {
     /// `spsr` - `F`, `A`, `D` bit should be set.
     ///
     /// Returns Os Error if do_load fails.
-    pub fn load<P: AsRef<Path>>(pn: P) -> OsResult<Process> {
+    pub fn load<P: AsRef<Path>>(pn: P) -> OsResult<(Process, Thread)> {
         use crate::VMM;
 
-        let mut p = Process::do_load(pn)?;
+        let p = Process::do_load(pn)?;
 
-        // FIXME: Set trapframe for the process.
-        p.context.elr_el = Process::get_image_base().as_u64();
-        p.context.ttbr0_el = VMM.get_baddr().as_u64();
-        p.context.ttbr1_el = p.vmap.get_baddr().as_u64();
-        p.context.sp_el = Process::get_stack_base().as_u64();
+        let mut thread = Thread::new();
+        thread.context.elr_el = Process::get_image_base().as_u64();
+        thread.context.ttbr0_el = VMM.get_baddr().as_u64();
+        thread.context.ttbr1_el = p.vmap.get_baddr().as_u64();
+        thread.context.sp_el = Process::get_stack_base().as_u64();
 
-        Ok(p)
+        Ok((p, thread))
     }
 
     /// Creates a process and open a file with given path.
@@ -88,13 +122,13 @@ impl Process {
     fn do_load<P: AsRef<Path>>(pn: P) -> OsResult<Process> {
         use fat32::traits::{FileSystem, Entry};
         use shim::io::Read;
-        let entry = FILESYSTEM.open(pn.as_ref()).unwrap();
-        let mut f = entry.into_file().unwrap();
+        let entry = FILESYSTEM.open(pn.as_ref())?;
+        let mut f = entry.into_file().ok_or(OsError::NoEntry)?;
         // let mut buf: [u8; 10000] = [0; 10000];
         // kprintln!("size {}", f.size);
         let mut buf: Vec<u8> = vec![0; f.size as usize];
-        f.read(&mut buf);
-        let mut p = Process::new().unwrap();
+        f.read_exact(&mut buf)?;
+        let mut p = Process::new()?;
         // let stack = p.vmap.alloc(Process::get_stack_base() - VirtualAddr::from(PAGE_SIZE), PagePerm::RW);
         for i in 0..16 {
             p.vmap.alloc(Process::get_stack_base() - VirtualAddr::from(PAGE_SIZE * i), PagePerm::RW);
@@ -117,6 +151,140 @@ impl Process {
         return Ok(p);
     }
 
+    /// Copies `args`, a block of NUL-separated arguments, to the top of the
+    /// process's stack and passes its address and length to the program in
+    /// `x0` and `x1` of its first thread `thread`. The stack pointer is moved
+    /// below the copied block.
+    ///
+    /// Returns `Err(OsError::InvalidArgument)` if `args` is larger than
+    /// `ARGS_MAX`.
+    pub fn set_args(&mut self, thread: &mut Thread, args: &[u8]) -> OsResult<()> {
+        if args.len() > ARGS_MAX {
+            return Err(OsError::InvalidArgument);
+        }
+        let size = (args.len() + Stack::ALIGN - 1) & !(Stack::ALIGN - 1);
+        let sp = Process::get_stack_base() - VirtualAddr::from(size);
+        self.vmap.write_bytes(sp, args)?;
+        thread.context.sp_el = sp.as_u64();
+        thread.context.xs[0] = sp.as_u64();
+        thread.context.xs[1] = args.len() as u64;
+        Ok(())
+    }
+
+    /// Resolves `path` against the working directory of this process and
+    /// returns an absolute path without any `.` or `..` components.
+    pub fn resolve_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let mut resolved = Path::new("/").to_path_buf();
+        for component in self.cwd.join(path).components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                _ => (),
+            }
+        }
+        resolved
+    }
+
+    /// Adds `end` to the pipe descriptors of this process and returns its
+    /// descriptor, which is the lowest one not in use.
+    pub fn add_pipe(&mut self, end: PipeEnd) -> usize {
+        match self.pipes.iter().position(Option::is_none) {
+            Some(fd) => {
+                self.pipes[fd] = Some(end);
+                fd
+            }
+            None => {
+                self.pipes.push(Some(end));
+                self.pipes.len() - 1
+            }
+        }
+    }
+
+    /// Returns the pipe end with the descriptor `fd`.
+    ///
+    /// Returns `Err(OsError::InvalidArgument)` if `fd` is not open.
+    pub fn pipe(&self, fd: usize) -> OsResult<&PipeEnd> {
+        match self.pipes.get(fd) {
+            Some(Some(end)) => Ok(end),
+            _ => Err(OsError::InvalidArgument),
+        }
+    }
+
+    /// Maps the shared region `region`, known by `id`, at `base` or, if `base`
+    /// is `None`, at the lowest free range from `SHM_BASE` on. Returns the
+    /// address of the mapping.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::BadAddress)` if `base` is not page aligned, or if
+    /// the mapping would not lie in user space below the stack or would
+    /// overlap mapped pages. Returns `Err(OsError::NoVmSpace)` if the kernel
+    /// finds no free range large enough.
+    pub fn map_region(&mut self, id: u64, region: Arc<Region>, base: Option<VirtualAddr>) -> OsResult<VirtualAddr> {
+        let size = region.size();
+        let base = match base {
+            Some(base) if self.is_free(base, size) => base,
+            Some(_) => return Err(OsError::BadAddress),
+            None => self.find_free(size).ok_or(OsError::NoVmSpace)?,
+        };
+        for (i, &page) in region.pages().iter().enumerate() {
+            self.vmap.map(base + VirtualAddr::from(i * PAGE_SIZE), page)?;
+        }
+        self.mappings.push(Mapping { id, base, region });
+        Ok(base)
+    }
+
+    /// Unmaps the shared region mapped at `base`. The region is freed if no
+    /// other mapping of it is left.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::InvalidArgument)` if no region is mapped at
+    /// `base`.
+    pub fn unmap_region(&mut self, base: VirtualAddr) -> OsResult<()> {
+        let i = self.mappings.iter().position(|m| m.base == base).ok_or(OsError::InvalidArgument)?;
+        let mapping = self.mappings.swap_remove(i);
+        for i in 0..mapping.region.pages().len() {
+            self.vmap.unmap(base + VirtualAddr::from(i * PAGE_SIZE));
+        }
+        // Other threads of the process may have the pages cached.
+        flush_tlb();
+        Ok(())
+    }
+
+    /// Returns `true` if the `size` bytes at `base` are page aligned, lie in
+    /// user space below the stack, and are not mapped.
+    fn is_free(&self, base: VirtualAddr, size: usize) -> bool {
+        let start = base.as_usize();
+        if start < USER_IMG_BASE || start % PAGE_SIZE != 0 {
+            return false;
+        }
+        match start.checked_add(size) {
+            Some(end) if end <= Process::get_stack_top().as_usize() => (),
+            _ => return false,
+        }
+        (start..start + size)
+            .step_by(PAGE_SIZE)
+            .all(|va| self.vmap.translate(VirtualAddr::from(va)).is_none())
+    }
+
+    /// Returns the lowest address from `SHM_BASE` on where `size` bytes are
+    /// free, if any.
+    fn find_free(&self, size: usize) -> Option<VirtualAddr> {
+        let top = Process::get_stack_top().as_usize();
+        let mut base = SHM_BASE;
+        let mut va = SHM_BASE;
+        while va < top && base + size <= top {
+            if self.vmap.translate(VirtualAddr::from(va)).is_some() {
+                base = va + PAGE_SIZE;
+            } else if va + PAGE_SIZE - base >= size {
+                return Some(VirtualAddr::from(base));
+            }
+            va += PAGE_SIZE;
+        }
+        None
+    }
+
     /// Returns the highest `VirtualAddr` that is supported by this system.
     pub fn get_max_va() -> VirtualAddr {
         return VirtualAddr::from(USER_IMG_BASE) + VirtualAddr::from(USER_MAX_VM_SIZE);
@@ -139,39 +307,4 @@ impl Process {
     pub fn get_stack_top() -> VirtualAddr {
         return VirtualAddr::from(USER_STACK_BASE) - VirtualAddr::from(Stack::SIZE as u64);
     }
-
-    /// Returns `true` if this process is ready to be scheduled.
-    ///
-    /// This functions returns `true` only if one of the following holds:
-    ///
-    ///   * The state is currently `Ready`.
-    ///
-    ///   * An event being waited for has arrived.
-    ///
-    ///     If the process is currently waiting, the corresponding event
-    ///     function is polled to determine if the event being waiting for has
-    ///     occured. If it has, the state is switched to `Ready` and this
-    ///     function returns `true`.
-    ///
-    /// Returns `false` in all other cases.
-    pub fn is_ready(&mut self) -> bool {
-        let mut state = replace(&mut self.state, State::Ready);
-        match &mut state {
-            State::Ready => {
-                return true;
-            },
-            State::Waiting(poll_fun) => {
-                if poll_fun(self) {
-                    return true;
-                } else {
-                    replace(&mut self.state, state);
-                    return false;
-                }
-            }
-            _ => {
-                replace(&mut self.state, state);
-                return false;
-            }
-        }
-    }
 }
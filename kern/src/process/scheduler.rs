@@ -2,7 +2,6 @@ use alloc::boxed::Box;
 use alloc::collections::vec_deque::VecDeque;
 use alloc::vec::Vec;
 
-use core::ffi::c_void;
 use core::fmt;
 use core::mem;
 use core::time::Duration;
@@ -11,14 +10,17 @@ use aarch64::*;
 use pi::local_interrupt::LocalInterrupt;
 use smoltcp::time::Instant;
 
-use crate::mutex::Mutex;
-use crate::net::uspi::TKernelTimerHandle;
+use crate::mutex::{Mutex, Once};
+use crate::net::SocketKind;
 use crate::param::*;
-use crate::percore::{get_preemptive_counter, is_mmu_ready, local_irq};
-use crate::process::{Id, Process, State};
+use crate::percore::{
+    current_thread, enter_idle, get_preemptive_counter, is_mmu_ready, leave_idle, local_irq, set_current_thread,
+};
+use crate::process::{signal, thread, Id, Process, State, Thread, KERNEL_PID, MAX_EXITED};
 use crate::traps::irq::IrqHandlerRegistry;
 use crate::traps::TrapFrame;
-use crate::{ETHERNET, USB};
+use crate::timer;
+use crate::{ETHERNET, FILESYSTEM, PORTS, TIMERS};
 
 use crate::VMM;
 use crate::GLOBAL_IRQ;
@@ -28,15 +30,16 @@ use crate::console::{kprintln, kprint};
 use pi::timer::*;
 use pi::interrupt::*;
 use pi::local_interrupt::*;
+use kernel_api::SIGCHLD;
 
 /// Process scheduler for the entire machine.
 #[derive(Debug)]
-pub struct GlobalScheduler(Mutex<Option<Box<Scheduler>>>);
+pub struct GlobalScheduler(Once<Mutex<Box<Scheduler>>>);
 
 impl GlobalScheduler {
     /// Returns an uninitialized wrapper around a local scheduler.
     pub const fn uninitialized() -> GlobalScheduler {
-        GlobalScheduler(Mutex::new(None))
+        GlobalScheduler(Once::new())
     }
 
     /// Enters a critical region and execute the provided closure with a mutable
@@ -45,19 +48,26 @@ impl GlobalScheduler {
     where
         F: FnOnce(&mut Scheduler) -> R,
     {
-        let mut guard = self.0.lock();
-        f(guard.as_mut().expect("scheduler uninitialized"))
+        let mut guard = self.0.get().expect("scheduler uninitialized").lock();
+        f(&mut guard)
     }
 
-    /// Adds a process to the scheduler's queue and returns that process's ID.
-    /// For more details, see the documentation on `Scheduler::add()`.
-    pub fn add(&self, process: Process) -> Option<Id> {
-        self.critical(move |scheduler| scheduler.add(process))
+    /// Adds a process and its first thread to the scheduler's queue and returns
+    /// that process's ID. For more details, see the documentation on
+    /// `Scheduler::add()`.
+    pub fn add(&self, process: Process, thread: Thread) -> Option<Id> {
+        self.critical(move |scheduler| scheduler.add(process, thread))
+    }
+
+    /// Adds a thread to the process with ID `pid` and returns the thread's ID.
+    /// For more details, see the documentation on `Scheduler::add_thread()`.
+    pub fn add_thread(&self, pid: Id, thread: Thread) -> Option<Id> {
+        self.critical(move |scheduler| scheduler.add_thread(pid, thread))
     }
 
     /// Performs a context switch using `tf` by setting the state of the current
-    /// process to `new_state`, saving `tf` into the current process, and
-    /// restoring the next process's trap frame into `tf`. For more details, see
+    /// thread to `new_state`, saving `tf` into the current thread, and
+    /// restoring the next thread's trap frame into `tf`. For more details, see
     /// the documentation on `Scheduler::schedule_out()` and `Scheduler::switch_to()`.
     pub fn switch(&self, new_state: State, tf: &mut TrapFrame) -> Id {
         self.critical(|scheduler| scheduler.schedule_out(new_state, tf));
@@ -66,12 +76,32 @@ impl GlobalScheduler {
         self.switch_to(tf)
     }
 
-    /// Loops until it finds the next process to schedule.
-    /// Call `wfi()` in the loop when no process is ready.
+    /// Performs a context switch like `switch()`, but runs the thread `target`
+    /// next if it is ready, so that a waiting receiver gets a message without
+    /// waiting for its turn in the queue. For more details, see the
+    /// documentation on `Scheduler::switch_to_thread()`.
+    pub fn handoff(&self, new_state: State, target: Id, tf: &mut TrapFrame) -> Id {
+        let rtn = self.critical(|scheduler| {
+            scheduler.schedule_out(new_state, tf);
+            scheduler.switch_to_thread(target, tf)
+        });
+        sev();
+        match rtn {
+            Some(id) => id,
+            None => self.switch_to(tf),
+        }
+    }
+
+    /// Loops until it finds the next thread to schedule.
+    /// Runs expired kernel timers and calls `wfe()` in the loop when no
+    /// thread is ready. The core is tickless meanwhile: its timer only fires
+    /// for kernel timers, and the time it spends idle is accounted in
+    /// `percore`.
     /// For more details, see the documentation on `Scheduler::switch_to()`.
     ///
-    /// Returns the process's ID when a ready process is found.
+    /// Returns the thread's ID when a ready thread is found.
     pub fn switch_to(&self, tf: &mut TrapFrame) -> Id {
+        let mut idle = false;
         loop {
             // kprint!("{}", affinity());
             let rtn = self.critical(|scheduler| scheduler.switch_to(tf));
@@ -86,19 +116,65 @@ impl GlobalScheduler {
                     tf.xs[28],
                     tf.xs[27]
                 );
+                if idle {
+                    leave_idle(current_time().as_micros() as u64);
+                    TIMERS.start();
+                }
                 return id;
             }
 
+            if !idle {
+                idle = true;
+                enter_idle(current_time().as_micros() as u64);
+            }
+            // The timer interrupt is masked here, so expired timers would
+            // otherwise wait for another core to run them. Re-arming the timer
+            // also clears an interrupt that has fired, which would keep `wfe()`
+            // from waiting.
+            TIMERS.fire(timer::now());
+            TIMERS.idle();
             aarch64::wfe();
             // aarch64::wfi();
         }
     }
 
-    /// Kills currently running process and returns that process's ID.
-    /// For more details, see the documentation on `Scheduler::kill()`.
+    /// Kills the process of the currently running thread with exit status
+    /// `status` and returns that process's ID. For more details, see the
+    /// documentation on `Scheduler::kill()`.
+    #[must_use]
+    pub fn kill(&self, status: u64, tf: &mut TrapFrame) -> Option<Id> {
+        self.critical(|scheduler| scheduler.kill(status, tf))
+    }
+
+    /// Ends the currently running thread with the value `value` and returns
+    /// its ID. For more details, see the documentation on
+    /// `Scheduler::exit_thread()`.
     #[must_use]
-    pub fn kill(&self, tf: &mut TrapFrame) -> Option<Id> {
-        self.critical(|scheduler| scheduler.kill(tf))
+    pub fn exit_thread(&self, value: u64, tf: &mut TrapFrame) -> Option<Id> {
+        self.critical(|scheduler| scheduler.exit_thread(value, tf))
+    }
+
+    /// Delivers the pending signals of the process whose thread is about to
+    /// return to user space with the registers in `tf`. If a signal terminates
+    /// the process, or if another thread has ended it, the next thread is
+    /// switched to and receives its signals in turn.
+    pub fn deliver_signals(&self, tf: &mut TrapFrame) {
+        loop {
+            let status = self.critical(|scheduler| {
+                let (t, p) = scheduler.current();
+                if p.exiting {
+                    return Some(0);
+                }
+                signal::deliver(t, p, tf)
+            });
+            match status {
+                Some(status) => {
+                    let _ = self.kill(status, tf);
+                    self.switch_to(tf);
+                }
+                None => return,
+            }
+        }
     }
 
     /// Starts executing processes in user space using timer interrupt based
@@ -130,9 +206,6 @@ impl GlobalScheduler {
         // kprintln!("STARTED {}", affinity());
         // kprintln!("QER {}", tf.tpidr_el);
 
-        if affinity() == 0 {
-            self.initialize_global_timer_interrupt();
-        }
         self.initialize_local_timer_interrupt();
 
         // let mut ptr: u64 = 17455984178246451368;
@@ -179,42 +252,40 @@ impl GlobalScheduler {
         }
     }
 
-    /// # Lab 4
-    /// Initializes the global timer interrupt with `pi::timer`. The timer
-    /// should be configured in a way that `Timer1` interrupt fires every
-    /// `TICK` duration, which is defined in `param.rs`.
-    ///
-    /// # Lab 5
-    /// Registers a timer handler with `Usb::start_kernel_timer` which will
-    /// invoke `poll_ethernet` after 1 second.
-    pub fn initialize_global_timer_interrupt(&self) {
-        /*
-        let mut int_controller = Controller::new();
-        int_controller.enable(Interrupt::Timer1);
-        tick_in(TICK);
-        GLOBAL_IRQ.register(Interrupt::Timer1, Box::new(timer_handle));
-        */
-    }
-
     /// Initializes the per-core local timer interrupt with `pi::local_interrupt`.
-    /// The timer should be configured in a way that `CntpnsIrq` interrupt fires
-    /// every `TICK` duration, which is defined in `param.rs`.
+    /// The timer is configured in a way that `CntpnsIrq` interrupt fires at
+    /// the end of every `TICK` time slice, which is defined in `param.rs`, and
+    /// at the deadline of every kernel timer in `TIMERS`.
     pub fn initialize_local_timer_interrupt(&self) {
         // Lab 5 2.C
         let mut int_controller = LocalController::new(affinity());
         int_controller.enable_local_timer();
-        local_tick_in(affinity(), TICK);
-        local_irq().register(LocalInterrupt::LocalTimer, Box::new(local_timer_handle));
+        local_irq().register(LocalInterrupt::CNTPNSIRQ, Box::new(local_timer_handle));
+        TIMERS.start();
     }
 
-    /// Initializes the scheduler and add userspace processes to the Scheduler.
+    /// Initializes the scheduler, adds userspace processes to the Scheduler,
+    /// and starts the kernel threads.
     pub unsafe fn initialize(&self) {
-        *self.0.lock() = Some(Scheduler::new());
+        self.0.call_once(|| Mutex::new(Scheduler::new()));
         use shim::path::Path;
-        for i in 0..6 {
-            let p = Process::load(Path::new("/fib.bin")).unwrap();
-            self.add(p);
+        let (p, main) = Process::load(Path::new("/shell.bin")).expect("failed to load /shell.bin");
+        self.add(p, main);
+
+        if ETHERNET.is_initialized() {
+            self.start_kernel_thread(poll_ethernet);
         }
+        self.start_kernel_thread(flush_filesystem);
+    }
+
+    /// Adds a kernel thread that runs `entry`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread's stack cannot be allocated.
+    fn start_kernel_thread(&self, entry: fn() -> !) {
+        let thread = Thread::kernel(entry).expect("failed to create a kernel thread");
+        self.add_thread(KERNEL_PID, thread);
     }
 
     // The following method may be useful for testing Lab 4 Phase 3:
@@ -235,131 +306,382 @@ impl GlobalScheduler {
     // }
 }
 
-/// Poll the ethernet driver and re-register a timer handler using
-/// `Usb::start_kernel_timer`.
-extern "C" fn poll_ethernet(_: TKernelTimerHandle, _: *mut c_void, _: *mut c_void) {
+/// How often `flush_filesystem` writes the file system cache back.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Kernel thread that polls the ethernet driver, sleeping for the advisory
+/// delay between polls.
+fn poll_ethernet() -> ! {
     // Lab 5 2.B
-    unimplemented!("poll_ethernet")
+    loop {
+        let now = Instant::from_millis(current_time().as_millis() as i64);
+        ETHERNET.poll(now);
+        thread::sleep(ETHERNET.poll_delay(now));
+    }
+}
+
+/// Kernel thread that writes the sectors modified in the file system cache
+/// back to the disk every `FLUSH_INTERVAL`.
+fn flush_filesystem() -> ! {
+    loop {
+        thread::sleep(FLUSH_INTERVAL);
+        if let Err(e) = FILESYSTEM.flush() {
+            debug!("file system flush failed: {:?}", e);
+        }
+    }
 }
 
 /// Internal scheduler struct which is not thread-safe.
 pub struct Scheduler {
-    processes: VecDeque<Process>,
+    threads: VecDeque<Thread>,
+    processes: Vec<Process>,
     last_id: Option<Id>,
 }
 
+/// Returns `true` if `thread` is running on a core.
+fn is_running(thread: &Thread) -> bool {
+    match thread.state {
+        State::Running => true,
+        _ => false,
+    }
+}
+
 impl Scheduler {
-    /// Returns a new `Scheduler` with an empty queue.
+    /// Returns a new `Scheduler` with an empty queue and the kernel process.
     fn new() -> Box<Scheduler> {
+        let mut kernel = Process::new().expect("failed to create the kernel process");
+        kernel.id = KERNEL_PID;
         return Box::new(Scheduler {
-            processes: VecDeque::new(),
+            threads: VecDeque::new(),
+            processes: vec![kernel],
             last_id: None,
         });
     }
 
-    /// Adds a process to the scheduler's queue and returns that process's ID if
-    /// a new process can be scheduled. The process ID is newly allocated for
-    /// the process and saved in its `trap_frame`. If no further processes can
-    /// be scheduled, returns `None`.
+    /// Allocates a process or thread ID. Returns `None` if they have run out.
+    fn next_id(&mut self) -> Option<Id> {
+        let id = match self.last_id {
+            None => 1,
+            Some(id) => id.checked_add(1)?,
+        };
+        self.last_id = Some(id);
+        Some(id)
+    }
+
+    /// Adds a process and its first thread to the scheduler's queue and
+    /// returns that process's ID if a new process can be scheduled. The ID is
+    /// newly allocated and given to both the process and the thread. If no
+    /// further processes can be scheduled, returns `None`.
     ///
     /// It is the caller's responsibility to ensure that the first time `switch`
-    /// is called, that process is executing on the CPU.
-    fn add(&mut self, mut process: Process) -> Option<Id> {
-        match self.last_id {
-            None => self.last_id = Some(1),
-            Some(id) => self.last_id = Some(id + 1),
-        }
-        process.context.tpidr_el = self.last_id.unwrap();
-        process.state = State::Ready;
-        self.processes.push_back(process);
-        // kprintln!("{:?}",self.processes);
-        return self.last_id;
+    /// is called, that thread is executing on the CPU.
+    fn add(&mut self, mut process: Process, mut thread: Thread) -> Option<Id> {
+        let id = self.next_id()?;
+        process.id = id;
+        thread.id = id;
+        thread.pid = id;
+        thread.state = State::Ready;
+        self.processes.push(process);
+        self.threads.push_back(thread);
+        Some(id)
     }
 
-    /// Finds the currently running process, sets the current process's state
-    /// to `new_state`, prepares the context switch on `tf` by saving `tf`
-    /// into the current process, and push the current process back to the
-    /// end of `processes` queue.
+    /// Adds `thread` to the process with ID `pid`, in whose address space it
+    /// runs, and returns the newly allocated ID of the thread. Returns `None`
+    /// if there is no such process or if no further threads can be scheduled.
+    fn add_thread(&mut self, pid: Id, mut thread: Thread) -> Option<Id> {
+        let process = self.processes.iter().find(|p| p.id == pid && !p.exiting)?;
+        thread.context.ttbr1_el = process.vmap.get_baddr().as_u64();
+        let id = self.next_id()?;
+        thread.id = id;
+        thread.pid = pid;
+        thread.state = State::Ready;
+        self.threads.push_back(thread);
+        Some(id)
+    }
+
+    /// Finds the thread running on this core, sets its state to `new_state`,
+    /// prepares the context switch on `tf` by saving `tf` into the thread, and
+    /// push the thread back to the end of `threads` queue.
     ///
-    /// If the `processes` queue is empty or there is no current process,
-    /// returns `false`. Otherwise, returns `true`.
+    /// If the `threads` queue is empty or there is no current thread, returns
+    /// `false`. Otherwise, returns `true`.
     fn schedule_out(&mut self, new_state: State, tf: &mut TrapFrame) -> bool {
-        for i in 0..self.processes.len() {
-            match self.processes[i].state {
-                State::Running => {
-                    // kprintln!("{} {}", self.processes[i].context.tpidr_el, tf.tpidr_el);
-                    if tf.tpidr_el == self.processes[i].context.tpidr_el {
-                        let mut p = self.processes.remove(i).unwrap();
-                        p.state = new_state;
-                        *p.context = *tf;
-                        self.processes.push_back(p);
-                        return true;
-                    }
-                },
-                _ => (),
+        let id = current_thread();
+        match self.threads.iter().position(|t| t.id == id && is_running(t)) {
+            Some(i) => {
+                let mut thread = self.threads.remove(i).unwrap();
+                thread.state = new_state;
+                *thread.context = *tf;
+                self.threads.push_back(thread);
+                true
             }
+            None => false,
         }
-        return false;
     }
 
-    /// Finds the next process to switch to, brings the next process to the
-    /// front of the `processes` queue, changes the next process's state to
-    /// `Running`, and performs context switch by restoring the next process`s
+    /// Finds the next thread to switch to, brings the next thread to the
+    /// front of the `threads` queue, changes the next thread's state to
+    /// `Running`, and performs context switch by restoring the next thread's
     /// trap frame into `tf`.
     ///
-    /// If there is no process to switch to, returns `None`. Otherwise, returns
-    /// `Some` of the next process`s process ID.
+    /// If there is no thread to switch to, returns `None`. Otherwise, returns
+    /// `Some` of the next thread's ID.
     fn switch_to(&mut self, tf: &mut TrapFrame) -> Option<Id> {
-        for i in 0..self.processes.len() {
-            if self.processes[i].is_ready() {
-                self.processes[i].state = State::Running;
-                *tf = *self.processes[i].context;
-                // kprintln!("{}", tf.tpidr_el);
-                let id = self.processes[i].context.tpidr_el;
-                let p = self.processes.remove(i).unwrap();
-                self.processes.push_front(p);
-                // kprintln!("TAKE {} {}", affinity(), id);
-                return Some(id);
+        self.reap();
+        for i in 0..self.threads.len() {
+            if self.poll(i) {
+                return Some(self.run(i, tf));
             }
         }
         return None;
     }
 
-    /// Kills currently running process by scheduling out the current process
-    /// as `Dead` state. Releases all process resources held by the process,
-    /// removes the dead process from the queue, drops the dead process's
-    /// instance, and returns the dead process's process ID.
-    fn kill(&mut self, tf: &mut TrapFrame) -> Option<Id> {
-        unimplemented!("Scheduler::kill()")
+    /// Switches to the thread `target` like `switch_to()` if it is ready, and
+    /// returns its ID. Returns `None` without switching otherwise.
+    fn switch_to_thread(&mut self, target: Id, tf: &mut TrapFrame) -> Option<Id> {
+        let i = self.threads.iter().position(|t| t.id == target && !is_running(t))?;
+        if self.poll(i) {
+            Some(self.run(i, tf))
+        } else {
+            None
+        }
     }
 
-    /// Releases all process resources held by the current process such as sockets.
+    /// Returns `true` if the thread at index `i` of the queue is ready. See
+    /// `Thread::is_ready()`.
+    fn poll(&mut self, i: usize) -> bool {
+        let interrupt = self.signal_target(self.threads[i].pid) == Some(i);
+        let Scheduler { threads, processes, .. } = self;
+        let thread = &mut threads[i];
+        let process = processes
+            .iter_mut()
+            .find(|p| p.id == thread.pid)
+            .expect("thread without a process");
+        thread.is_ready(process, interrupt)
+    }
+
+    /// Returns the index in the queue of the thread of the process `pid` that
+    /// should abandon its wait to take a signal waiting to be delivered to
+    /// the process: its first waiting thread, unless one of its threads is
+    /// running or ready and takes the signal anyway. Returns `None` if no
+    /// wait should be interrupted.
+    fn signal_target(&self, pid: Id) -> Option<usize> {
+        let p = self.processes.iter().find(|p| p.id == pid)?;
+        if !p.signals.is_deliverable() {
+            return None;
+        }
+        let mut target = None;
+        for (i, thread) in self.threads.iter().enumerate().filter(|(_, t)| t.pid == pid) {
+            match thread.state {
+                State::Ready | State::Running => return None,
+                State::Waiting(_) if target.is_none() => target = Some(i),
+                _ => (),
+            }
+        }
+        target
+    }
+
+    /// Runs the thread at index `i` of the queue on this core: sets its state
+    /// to `Running`, restores its trap frame into `tf`, brings it to the front
+    /// of the queue, and returns its ID.
+    fn run(&mut self, i: usize, tf: &mut TrapFrame) -> Id {
+        let mut thread = self.threads.remove(i).unwrap();
+        thread.state = State::Running;
+        *tf = *thread.context;
+        let id = thread.id;
+        self.threads.push_front(thread);
+        set_current_thread(id);
+        id
+    }
+
+    /// Drops the threads of exited processes that are not running on a core,
+    /// and the processes that have no threads left.
+    fn reap(&mut self) {
+        let Scheduler { threads, processes, .. } = self;
+        threads.retain(|t| is_running(t) || !processes.iter().any(|p| p.id == t.pid && p.exiting));
+        processes.retain(|p| p.id == KERNEL_PID || threads.iter().any(|t| t.pid == p.id));
+    }
+
+    /// Kills the process of the currently running thread. Releases all
+    /// process resources held by the process, schedules out the current
+    /// thread as `Dead`, drops every other thread of the process that is not
+    /// running on another core, and returns the dead process's ID. Threads of
+    /// the process running on other cores are dropped the next time they
+    /// enter the scheduler or return to user space.
+    ///
+    /// Children of the dead process are orphaned, and `status` is recorded in
+    /// the parent's list of exited children so that a pending `wait` returns,
+    /// dropping the oldest entry if the list holds `MAX_EXITED` already.
+    /// The parent is sent `SIGCHLD`.
+    ///
+    /// A kernel thread only ends itself, as with `exit_thread()`.
+    fn kill(&mut self, status: u64, tf: &mut TrapFrame) -> Option<Id> {
+        let pid = self.current_thread().pid;
+        if pid == KERNEL_PID {
+            return self.exit_thread(status, tf);
+        }
+        if self.current_process().exiting {
+            // Another thread has already ended the process.
+            if !self.schedule_out(State::Dead, tf) {
+                return None;
+            }
+            self.threads.pop_back();
+            return Some(pid);
+        }
+
+        self.release_process_resources(tf);
+        if !self.schedule_out(State::Dead, tf) {
+            return None;
+        }
+        self.threads.pop_back()?;
+        let dead = self.process(pid)?;
+        dead.exiting = true;
+        let parent = dead.parent;
+        self.reap();
+
+        for p in self.processes.iter_mut() {
+            if p.parent == Some(pid) {
+                p.parent = None;
+            }
+        }
+        if let Some(parent) = parent {
+            if let Some(p) = self.process_mut(parent) {
+                if p.exited.len() == MAX_EXITED {
+                    p.exited.remove(0);
+                }
+                p.exited.push((pid, status));
+                let _ = p.signals.raise(SIGCHLD);
+            }
+        }
+        Some(pid)
+    }
+
+    /// Ends the currently running thread, schedules it out as `Dead` and
+    /// returns its ID. `value` is kept for `NR_THREAD_JOIN` until another
+    /// thread of the process joins it. If it is the last thread of its
+    /// process, the process exits with `value` as its status, as with
+    /// `kill()`.
+    fn exit_thread(&mut self, value: u64, tf: &mut TrapFrame) -> Option<Id> {
+        let (id, pid) = {
+            let thread = self.current_thread();
+            (thread.id, thread.pid)
+        };
+        let last = !self.threads.iter().any(|t| t.pid == pid && t.id != id);
+        if pid != KERNEL_PID && last {
+            return self.kill(value, tf).map(|_| id);
+        }
+
+        if !self.schedule_out(State::Dead, tf) {
+            return None;
+        }
+        self.threads.pop_back()?;
+        if pid != KERNEL_PID {
+            if let Some(p) = self.process(pid) {
+                p.exited_threads.push((id, value));
+            }
+        }
+        Some(id)
+    }
+
+    /// Returns the process with ID `pid`, including the kernel process.
+    fn process(&mut self, pid: Id) -> Option<&mut Process> {
+        self.processes.iter_mut().find(|p| p.id == pid)
+    }
+
+    /// Returns the user process with ID `id`, if it is alive or unreaped.
+    pub fn process_mut(&mut self, id: Id) -> Option<&mut Process> {
+        self.processes.iter_mut().find(|p| p.id == id && p.id != KERNEL_PID)
+    }
+
+    /// Returns `true` if the process `child` is alive or unreaped and was
+    /// spawned by the process `parent`.
+    pub fn is_child(&self, parent: Id, child: Id) -> bool {
+        self.processes.iter().any(|p| {
+            (p.id == child && p.parent == Some(parent))
+                || (p.id == parent && p.exited.iter().any(|&(id, _)| id == child))
+        })
+    }
+
+    /// Returns `true` if the thread `tid` belongs to the process `pid` and is
+    /// alive or has exited without being joined.
+    pub fn is_thread_of(&self, pid: Id, tid: Id) -> bool {
+        self.threads.iter().any(|t| t.id == tid && t.pid == pid)
+            || self
+                .processes
+                .iter()
+                .any(|p| p.id == pid && p.exited_threads.iter().any(|&(id, _)| id == tid))
+    }
+
+    /// Releases all process resources held by the current process such as
+    /// sockets, pipe ends and ports.
     fn release_process_resources(&mut self, tf: &mut TrapFrame) {
+        let process = self.current_process();
+        process.pipes.clear();
+        process.stdin = None;
+        process.stdout = None;
+        process.alarm = None;
+        let pid = process.id;
+        PORTS.critical(|ports| ports.release(pid));
+
         // Lab 5 2.C
-        unimplemented!("release_process_resources")
+        let sockets = mem::replace(&mut self.current_process().sockets, Vec::new());
+        if sockets.is_empty() {
+            return;
+        }
+        ETHERNET.critical(|ethernet| {
+            for (kind, handle) in sockets {
+                let port = ethernet.local_port(kind, handle);
+                ethernet.erase_port(port);
+                match kind {
+                    SocketKind::Tcp => ethernet.get_socket(handle).close(),
+                    SocketKind::Udp => ethernet.get_udp_socket(handle).close(),
+                    SocketKind::Icmp => (),
+                }
+                ethernet.release(handle);
+            }
+            ethernet.prune();
+        });
     }
 
-    /// Finds a process corresponding with tpidr saved in a trap frame.
+    /// Finds the thread running on this core and its process.
     /// Panics if the search fails.
-    pub fn find_process(&mut self, tf: &TrapFrame) -> &mut Process {
-        for i in 0..self.processes.len() {
-            if self.processes[i].context.tpidr_el == tf.tpidr_el {
-                return &mut self.processes[i];
-            }
-        }
-        panic!("Invalid TrapFrame");
+    pub fn current(&mut self) -> (&mut Thread, &mut Process) {
+        let id = current_thread();
+        let Scheduler { threads, processes, .. } = self;
+        let thread = match threads.iter_mut().find(|t| t.id == id) {
+            Some(thread) => thread,
+            None => panic!("no thread {} on core {}", id, affinity()),
+        };
+        let process = processes
+            .iter_mut()
+            .find(|p| p.id == thread.pid)
+            .expect("thread without a process");
+        (thread, process)
+    }
+
+    /// Finds the thread running on this core.
+    /// Panics if the search fails.
+    pub fn current_thread(&mut self) -> &mut Thread {
+        self.current().0
+    }
+
+    /// Finds the process of the thread running on this core.
+    /// Panics if the search fails.
+    pub fn current_process(&mut self) -> &mut Process {
+        self.current().1
     }
 }
 
 impl fmt::Debug for Scheduler {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let len = self.processes.len();
-        write!(f, "  [Scheduler] {} processes in the queue\n", len)?;
+        let len = self.threads.len();
+        write!(f, "  [Scheduler] {} threads in the queue\n", len)?;
         for i in 0..len {
             write!(
                 f,
-                "    queue[{}]: proc({:3})-{:?} \n",
-                i, self.processes[i].context.tpidr_el, self.processes[i].state
+                "    queue[{}]: thread({:3}) of proc({:3})-{:?} \n",
+                i, self.threads[i].id, self.threads[i].pid, self.threads[i].state
             )?;
         }
         Ok(())
@@ -392,8 +714,10 @@ pub fn timer_handle(tf: &mut TrapFrame) {
 }
 
 
+/// Runs the expired kernel timers and switches threads if the time slice is
+/// over or a timer may have readied a thread.
 pub fn local_timer_handle(tf: &mut TrapFrame) {
-    local_tick_in(affinity(), TICK);
-    SCHEDULER.switch(State::Ready, tf);
-    return;
+    if TIMERS.tick(timer::now()) {
+        SCHEDULER.switch(State::Ready, tf);
+    }
 }
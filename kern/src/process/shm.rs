@@ -0,0 +1,146 @@
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::alloc::GlobalAlloc;
+use core::fmt;
+
+use kernel_api::{OsError, OsResult};
+
+use crate::mutex::{Lazy, Mutex};
+use crate::param::{PAGE_SIZE, USER_IMG_BASE, USER_MAX_VM_SIZE};
+use crate::vm::{Page, PhysicalAddr, VirtualAddr};
+use crate::ALLOCATOR;
+
+#[cfg(test)]
+mod tests;
+
+/// The lowest address the kernel places a shared region at when the caller
+/// does not choose one: the upper half of the user address space, well above
+/// the program image and below the stack.
+pub const SHM_BASE: usize = USER_IMG_BASE + USER_MAX_VM_SIZE / 2;
+
+/// The largest region, in pages, that can be created.
+pub const SHM_MAX_PAGES: usize = 1024;
+
+/// Physical pages that several processes map into their address spaces. The
+/// pages are freed when the region is dropped, which happens once the last
+/// mapping of it is gone.
+pub struct Region {
+    pages: Vec<PhysicalAddr>,
+}
+
+impl Region {
+    /// Allocates a region of `npages` zeroed pages.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::InvalidArgument)` if `npages` is `0` or larger
+    /// than `SHM_MAX_PAGES`, or `Err(OsError::NoMemory)` if the pages could
+    /// not be allocated.
+    pub fn new(npages: usize) -> OsResult<Region> {
+        if npages == 0 || npages > SHM_MAX_PAGES {
+            return Err(OsError::InvalidArgument);
+        }
+        // Pages allocated before a failure are freed when `region` drops.
+        let mut region = Region { pages: Vec::with_capacity(npages) };
+        for _ in 0..npages {
+            let ptr = unsafe { ALLOCATOR.alloc(Page::layout()) };
+            if ptr.is_null() {
+                return Err(OsError::NoMemory);
+            }
+            unsafe { ptr.write_bytes(0, PAGE_SIZE) };
+            region.pages.push(PhysicalAddr::from(ptr as u64));
+        }
+        Ok(region)
+    }
+
+    /// Returns the physical addresses of the pages of the region, in order.
+    pub fn pages(&self) -> &[PhysicalAddr] {
+        &self.pages
+    }
+
+    /// Returns the size of the region in bytes.
+    pub fn size(&self) -> usize {
+        self.pages.len() * PAGE_SIZE
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        for page in self.pages.iter() {
+            unsafe { ALLOCATOR.dealloc(page.as_usize() as *mut u8, Page::layout()) }
+        }
+    }
+}
+
+impl fmt::Debug for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Region").field("pages", &self.pages.len()).finish()
+    }
+}
+
+/// A shared region mapped into a process at `base`.
+#[derive(Debug)]
+pub struct Mapping {
+    /// The ID the region is known by
+    pub id: u64,
+    /// The address of the first page of the mapping
+    pub base: VirtualAddr,
+    /// The mapped region, which the mapping keeps alive
+    pub region: Arc<Region>,
+}
+
+impl Mapping {
+    /// Returns `true` if the mapping overlaps the `size` bytes at `base`.
+    pub fn overlaps(&self, base: VirtualAddr, size: usize) -> bool {
+        let start = self.base.as_usize();
+        let end = start + self.region.size();
+        base.as_usize() < end && start < base.as_usize() + size
+    }
+}
+
+/// The shared regions of the machine, by ID. The table does not keep regions
+/// alive: an entry goes away with the last mapping of its region.
+#[derive(Debug)]
+pub struct RegionTable {
+    regions: Vec<(u64, Weak<Region>)>,
+    next_id: u64,
+}
+
+impl RegionTable {
+    /// Returns a table without regions.
+    pub fn new() -> RegionTable {
+        RegionTable {
+            regions: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Adds `region` to the table and returns its newly allocated ID. IDs are
+    /// not reused.
+    pub fn insert(&mut self, region: &Arc<Region>) -> u64 {
+        self.prune();
+        let id = self.next_id;
+        self.next_id += 1;
+        self.regions.push((id, Arc::downgrade(region)));
+        id
+    }
+
+    /// Returns the region with ID `id` if it is still mapped somewhere.
+    pub fn get(&mut self, id: u64) -> Option<Arc<Region>> {
+        self.prune();
+        self.regions.iter().find(|&&(i, _)| i == id)?.1.upgrade()
+    }
+
+    /// Forgets the regions that have been freed.
+    fn prune(&mut self) {
+        self.regions.retain(|(_, region)| region.upgrade().is_some());
+    }
+}
+
+impl Default for RegionTable {
+    fn default() -> RegionTable {
+        RegionTable::new()
+    }
+}
+
+/// The shared-memory regions of the machine.
+pub type SharedMemory = Lazy<Mutex<RegionTable>>;
@@ -0,0 +1,46 @@
+use super::*;
+
+fn empty_region() -> Arc<Region> {
+    Arc::new(Region { pages: Vec::new() })
+}
+
+#[test]
+fn regions_live_while_mapped() {
+    let mut table = RegionTable::new();
+    let a = empty_region();
+    let b = empty_region();
+    let id_a = table.insert(&a);
+    let id_b = table.insert(&b);
+    assert_ne!(id_a, id_b);
+
+    let found = table.get(id_a).expect("region a is mapped");
+    assert!(Arc::ptr_eq(&found, &a));
+    drop(found);
+
+    drop(a);
+    assert!(table.get(id_a).is_none());
+    assert!(table.get(id_b).is_some());
+    assert_eq!(table.regions.len(), 1);
+
+    // The ID of a freed region is not handed out again.
+    let c = empty_region();
+    let id_c = table.insert(&c);
+    assert!(id_c != id_a && id_c != id_b);
+    assert!(table.get(id_a).is_none());
+}
+
+#[test]
+fn mapping_overlap() {
+    let mapping = Mapping {
+        id: 1,
+        base: VirtualAddr::from(SHM_BASE),
+        region: Arc::new(Region { pages: vec![PhysicalAddr::from(0u64); 2] }),
+    };
+    let size = 2 * PAGE_SIZE;
+    assert!(mapping.overlaps(VirtualAddr::from(SHM_BASE), PAGE_SIZE));
+    assert!(mapping.overlaps(VirtualAddr::from(SHM_BASE + PAGE_SIZE), size));
+    assert!(mapping.overlaps(VirtualAddr::from(SHM_BASE - PAGE_SIZE), size));
+    assert!(!mapping.overlaps(VirtualAddr::from(SHM_BASE + size), PAGE_SIZE));
+    assert!(!mapping.overlaps(VirtualAddr::from(SHM_BASE - PAGE_SIZE), PAGE_SIZE));
+    core::mem::forget(mapping);
+}
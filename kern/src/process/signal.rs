@@ -0,0 +1,256 @@
+use core::mem;
+
+use kernel_api::*;
+
+use crate::process::{Process, Thread};
+use crate::traps::TrapFrame;
+use crate::vm::VirtualAddr;
+
+#[cfg(test)]
+mod tests;
+
+/// Signals that cannot be caught, ignored or blocked.
+const UNBLOCKABLE: u64 = 1 << SIGKILL;
+
+/// The condition flags of `SPSR_EL1`, the only bits a signal handler may
+/// change in the state it returns to.
+const SPSR_NZCV: u64 = 0xf << 28;
+
+/// What happens when a signal is delivered.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    /// The default action of the signal: see `default_terminates()`.
+    Default,
+    /// The signal is discarded.
+    Ignore,
+    /// The user function at `handler` runs on the process's stack and returns
+    /// to `restorer`, which must issue `NR_SIGRETURN`. The signals in `mask`
+    /// and the signal itself are blocked while it runs.
+    Handler { handler: u64, restorer: u64, mask: u64 },
+}
+
+/// Returns `true` if the default action of `sig` terminates the process, and
+/// `false` if it ignores the signal.
+pub fn default_terminates(sig: u64) -> bool {
+    sig != SIGCHLD
+}
+
+/// Returns the mask of the signal `sig`, or `None` if there is no such signal.
+fn bit(sig: u64) -> Option<u64> {
+    if sig == 0 || sig >= NSIG {
+        None
+    } else {
+        Some(1 << sig)
+    }
+}
+
+/// The signal state of a process: the signals sent to it but not delivered
+/// yet, the signals it has blocked, and the action of every signal.
+#[derive(Debug)]
+pub struct Signals {
+    pending: u64,
+    blocked: u64,
+    actions: [Action; NSIG as usize],
+}
+
+impl Signals {
+    /// Returns the state of a new process: nothing pending or blocked, and
+    /// the default action for every signal.
+    pub fn new() -> Signals {
+        Signals {
+            pending: 0,
+            blocked: 0,
+            actions: [Action::Default; NSIG as usize],
+        }
+    }
+
+    /// Returns the mask of the signals that are blocked.
+    pub fn blocked(&self) -> u64 {
+        self.blocked
+    }
+
+    /// Marks `sig` as pending. A signal that is already pending is delivered
+    /// once.
+    ///
+    /// Returns `Err(OsError::InvalidArgument)` if there is no such signal.
+    pub fn raise(&mut self, sig: u64) -> OsResult<()> {
+        self.pending |= bit(sig).ok_or(OsError::InvalidArgument)?;
+        Ok(())
+    }
+
+    /// Marks `sig` as pending for a fault that the process cannot continue
+    /// past unless the signal is handled. If `sig` is blocked or ignored, it
+    /// is unblocked and its action is reset to the default, which terminates
+    /// the process.
+    pub fn force(&mut self, sig: u64) {
+        let bit = match bit(sig) {
+            Some(bit) => bit,
+            None => return,
+        };
+        if self.blocked & bit != 0 || self.actions[sig as usize] == Action::Ignore {
+            self.blocked &= !bit;
+            self.actions[sig as usize] = Action::Default;
+        }
+        self.pending |= bit;
+    }
+
+    /// Sets the action of `sig` and returns the previous one.
+    ///
+    /// Returns `Err(OsError::InvalidArgument)` if there is no such signal or
+    /// if its action cannot be changed.
+    pub fn set_action(&mut self, sig: u64, action: Action) -> OsResult<Action> {
+        let bit = bit(sig).ok_or(OsError::InvalidArgument)?;
+        if bit & UNBLOCKABLE != 0 {
+            return Err(OsError::InvalidArgument);
+        }
+        Ok(mem::replace(&mut self.actions[sig as usize], action))
+    }
+
+    /// Changes the blocked signals as `NR_SIGPROCMASK` does and returns the
+    /// previous mask. Unblockable signals are never blocked.
+    ///
+    /// Returns `Err(OsError::InvalidArgument)` if `how` is not valid.
+    pub fn set_blocked(&mut self, how: u64, mask: u64) -> OsResult<u64> {
+        let old = self.blocked;
+        self.blocked = match how {
+            SIG_BLOCK => old | mask,
+            SIG_UNBLOCK => old & !mask,
+            SIG_SETMASK => mask,
+            _ => return Err(OsError::InvalidArgument),
+        } & !UNBLOCKABLE & !1;
+        Ok(old)
+    }
+
+    /// Returns the mask of the pending signals that would do something if
+    /// delivered now: those that are not blocked and not ignored.
+    fn deliverable(&self) -> u64 {
+        let mut mask = self.pending & !self.blocked;
+        for sig in 1..NSIG {
+            let ignored = match self.actions[sig as usize] {
+                Action::Ignore => true,
+                Action::Default => !default_terminates(sig),
+                Action::Handler { .. } => false,
+            };
+            if ignored {
+                mask &= !(1 << sig);
+            }
+        }
+        mask
+    }
+
+    /// Returns `true` if a signal is waiting to be delivered, which
+    /// interrupts a blocking system call.
+    pub fn is_deliverable(&self) -> bool {
+        self.deliverable() != 0
+    }
+
+    /// Removes the lowest pending signal that is not blocked from the pending
+    /// set and returns it with its action. Signals that are ignored are
+    /// discarded on the way.
+    pub fn take(&mut self) -> Option<(u64, Action)> {
+        let deliverable = self.deliverable();
+        // Pending signals that are not blocked but ignored are dropped.
+        self.pending &= self.blocked | deliverable;
+        if deliverable == 0 {
+            return None;
+        }
+        let sig = deliverable.trailing_zeros() as u64;
+        self.pending &= !(1 << sig);
+        Some((sig, self.actions[sig as usize]))
+    }
+}
+
+/// The registers and the signal state saved on the user stack while a
+/// handler runs, which `NR_SIGRETURN` restores.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SignalFrame {
+    qs: [u128; 32],
+    xs: [u64; 32],
+    elr: u64,
+    spsr: u64,
+    sp: u64,
+    blocked: u64,
+    /// The frame of the handler this one interrupted, or `0`
+    prev: u64,
+    _reserved: u64,
+}
+
+impl SignalFrame {
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, mem::size_of::<Self>()) }
+    }
+
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as *mut u8, mem::size_of::<Self>()) }
+    }
+}
+
+/// Delivers the pending signals of `p` to its thread `t`, whose registers are
+/// in `tf` because it is about to return to user space.
+///
+/// The first signal with a handler rewrites `tf` so that the thread enters
+/// the handler with the signal number in `x0`. The state it was interrupted
+/// in is saved in a frame below its stack pointer.
+///
+/// Returns the exit status of the process if a signal terminates it.
+pub fn deliver(t: &mut Thread, p: &mut Process, tf: &mut TrapFrame) -> Option<u64> {
+    loop {
+        let (sig, action) = p.signals.take()?;
+        let (handler, restorer, mask) = match action {
+            Action::Default => return Some(signal_status(sig)),
+            Action::Ignore => continue,
+            Action::Handler { handler, restorer, mask } => (handler, restorer, mask),
+        };
+
+        let frame = SignalFrame {
+            qs: tf.qs,
+            xs: tf.xs,
+            elr: tf.elr_el,
+            spsr: tf.spsr_el,
+            sp: tf.sp_el,
+            blocked: p.signals.blocked,
+            prev: t.signal_frame,
+            _reserved: 0,
+        };
+        let size = mem::size_of::<SignalFrame>() as u64;
+        let addr = tf.sp_el.wrapping_sub(size) & !0xf;
+        if p.vmap.write_bytes(VirtualAddr::from(addr), frame.as_bytes()).is_err() {
+            // There is no stack left to run the handler on.
+            return Some(signal_status(SIGSEGV));
+        }
+
+        t.signal_frame = addr;
+        p.signals.blocked |= (mask | (1 << sig)) & !UNBLOCKABLE;
+        tf.sp_el = addr;
+        tf.elr_el = handler;
+        tf.xs[0] = sig;
+        tf.xs[30] = restorer;
+        return None;
+    }
+}
+
+/// Returns from the innermost signal handler of the thread `t` of `p`, whose
+/// registers are in `tf`, by restoring the state saved by `deliver()`. Only
+/// the condition flags of the saved `SPSR` are restored, so that a handler
+/// cannot forge a state with more privileges.
+///
+/// # Errors
+/// Returns `Err(OsError::InvalidArgument)` if no handler is running, or
+/// `Err(OsError::BadAddress)` if the frame is not mapped.
+pub fn restore(t: &mut Thread, p: &mut Process, tf: &mut TrapFrame) -> OsResult<()> {
+    if t.signal_frame == 0 {
+        return Err(OsError::InvalidArgument);
+    }
+    let mut frame: SignalFrame = unsafe { mem::zeroed() };
+    p.vmap.read_bytes(VirtualAddr::from(t.signal_frame), frame.as_bytes_mut())?;
+
+    tf.qs = frame.qs;
+    tf.xs = frame.xs;
+    tf.elr_el = frame.elr;
+    tf.spsr_el = (tf.spsr_el & !SPSR_NZCV) | (frame.spsr & SPSR_NZCV);
+    tf.sp_el = frame.sp;
+    p.signals.blocked = frame.blocked & !UNBLOCKABLE & !1;
+    t.signal_frame = frame.prev;
+    Ok(())
+}
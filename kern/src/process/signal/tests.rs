@@ -0,0 +1,93 @@
+use super::*;
+
+const HANDLER: Action = Action::Handler {
+    handler: 0x1000,
+    restorer: 0x2000,
+    mask: 0,
+};
+
+#[test]
+fn default_actions() {
+    let mut signals = Signals::new();
+    assert_eq!(signals.take(), None);
+
+    signals.raise(SIGCHLD).unwrap();
+    assert!(!signals.is_deliverable());
+    assert_eq!(signals.take(), None);
+
+    signals.raise(SIGTERM).unwrap();
+    assert!(signals.is_deliverable());
+    assert_eq!(signals.take(), Some((SIGTERM, Action::Default)));
+    assert_eq!(signals.take(), None);
+}
+
+#[test]
+fn invalid_signals() {
+    let mut signals = Signals::new();
+    assert_eq!(signals.raise(0), Err(OsError::InvalidArgument));
+    assert_eq!(signals.raise(NSIG), Err(OsError::InvalidArgument));
+    assert_eq!(signals.set_action(NSIG, Action::Ignore), Err(OsError::InvalidArgument));
+    assert_eq!(signals.set_action(SIGKILL, Action::Ignore), Err(OsError::InvalidArgument));
+    assert_eq!(signals.set_blocked(3, 0), Err(OsError::InvalidArgument));
+}
+
+#[test]
+fn lowest_signal_first() {
+    let mut signals = Signals::new();
+    signals.set_action(SIGUSR1, HANDLER).unwrap();
+    signals.raise(SIGTERM).unwrap();
+    signals.raise(SIGUSR1).unwrap();
+    signals.raise(SIGUSR1).unwrap();
+    assert_eq!(signals.take(), Some((SIGUSR1, HANDLER)));
+    assert_eq!(signals.take(), Some((SIGTERM, Action::Default)));
+    assert_eq!(signals.take(), None);
+}
+
+#[test]
+fn blocked_signals_stay_pending() {
+    let mut signals = Signals::new();
+    assert_eq!(signals.set_blocked(SIG_BLOCK, 1 << SIGUSR1 | 1 << SIGKILL), Ok(0));
+    assert_eq!(signals.blocked(), 1 << SIGUSR1);
+
+    signals.raise(SIGUSR1).unwrap();
+    assert!(!signals.is_deliverable());
+    assert_eq!(signals.take(), None);
+
+    assert_eq!(signals.set_blocked(SIG_UNBLOCK, 1 << SIGUSR1), Ok(1 << SIGUSR1));
+    assert_eq!(signals.take(), Some((SIGUSR1, Action::Default)));
+
+    signals.raise(SIGKILL).unwrap();
+    signals.set_blocked(SIG_SETMASK, !0).unwrap();
+    assert_eq!(signals.take(), Some((SIGKILL, Action::Default)));
+}
+
+#[test]
+fn ignored_signals_are_discarded() {
+    let mut signals = Signals::new();
+    assert_eq!(signals.set_action(SIGTERM, Action::Ignore), Ok(Action::Default));
+    signals.raise(SIGTERM).unwrap();
+    assert!(!signals.is_deliverable());
+    assert_eq!(signals.take(), None);
+
+    // The signal was discarded rather than left pending.
+    signals.set_action(SIGTERM, Action::Default).unwrap();
+    assert_eq!(signals.take(), None);
+}
+
+#[test]
+fn forced_signals() {
+    let mut signals = Signals::new();
+    signals.set_action(SIGSEGV, Action::Ignore).unwrap();
+    signals.set_blocked(SIG_BLOCK, 1 << SIGBUS).unwrap();
+
+    signals.force(SIGSEGV);
+    assert_eq!(signals.take(), Some((SIGSEGV, Action::Default)));
+    signals.force(SIGBUS);
+    assert_eq!(signals.blocked(), 0);
+    assert_eq!(signals.take(), Some((SIGBUS, Action::Default)));
+
+    // A handler stays in place.
+    signals.set_action(SIGSEGV, HANDLER).unwrap();
+    signals.force(SIGSEGV);
+    assert_eq!(signals.take(), Some((SIGSEGV, HANDLER)));
+}
@@ -2,24 +2,24 @@ use core::fmt;
 
 use alloc::boxed::Box;
 
-use crate::process::Process;
+use crate::process::{Process, Thread};
 
-/// Type of a function used to determine if a process is ready to be scheduled
-/// again. The scheduler calls this function when it is the process's turn to
-/// execute. If the function returns `true`, the process is scheduled. If it
-/// returns `false`, the process is not scheduled, and this function will be
-/// called on the next time slice.
-pub type EventPollFn = Box<dyn FnMut(&mut Process) -> bool + Send>;
+/// Type of a function used to determine if a thread is ready to be scheduled
+/// again. The scheduler calls this function with the thread and its process
+/// when it is the thread's turn to execute. If the function returns `true`,
+/// the thread is scheduled. If it returns `false`, the thread is not
+/// scheduled, and this function will be called on the next time slice.
+pub type EventPollFn = Box<dyn FnMut(&mut Thread, &mut Process) -> bool + Send>;
 
-/// The scheduling state of a process.
+/// The scheduling state of a thread.
 pub enum State {
-    /// The process is ready to be scheduled.
+    /// The thread is ready to be scheduled.
     Ready,
-    /// The process is waiting on an event to occur before it can be scheduled.
+    /// The thread is waiting on an event to occur before it can be scheduled.
     Waiting(EventPollFn),
-    /// The process is currently running.
+    /// The thread is currently running.
     Running,
-    /// The process is currently dead (ready to be reclaimed).
+    /// The thread is currently dead (ready to be reclaimed).
     Dead,
 }
 
@@ -0,0 +1,140 @@
+use alloc::boxed::Box;
+use core::cmp::min;
+use core::mem::replace;
+use core::time::Duration;
+
+use kernel_api::{OsError, OsResult, NR_SLEEP};
+
+use crate::process::{Id, Process, Stack, State, KERNEL_PID};
+use crate::traps::TrapFrame;
+use crate::VMM;
+
+/// `SPSR_EL1` of a kernel thread: `EL1t` with every exception masked, so that
+/// a kernel thread is never preempted while it holds a lock. It runs until it
+/// sleeps.
+const KERNEL_SPSR: u64 = 0b1111 << 6 | 0b0100;
+
+/// A flow of control in a process: its registers, its scheduling state and,
+/// for a kernel thread, its stack. The address space and the descriptors are
+/// shared by every thread of the process.
+#[derive(Debug)]
+pub struct Thread {
+    /// The ID of the thread. The first thread of a process has the ID of the
+    /// process.
+    pub id: Id,
+    /// The ID of the process the thread belongs to, `KERNEL_PID` for a kernel
+    /// thread.
+    pub pid: Id,
+    /// The saved trap frame of the thread.
+    pub context: Box<TrapFrame>,
+    /// The stack of a kernel thread. User threads run on stacks in their
+    /// address space.
+    pub stack: Option<Stack>,
+    /// The scheduling state of the thread.
+    pub state: State,
+    /// The address of the frame of the innermost signal handler the thread
+    /// runs, or `0` if it runs none
+    pub signal_frame: u64,
+}
+
+impl Thread {
+    /// Creates a new thread with a zeroed `TrapFrame` and a state of `Ready`.
+    /// The scheduler assigns its ID when it is added.
+    pub fn new() -> Thread {
+        let tf = TrapFrame {
+            ttbr0_el: 0,
+            ttbr1_el: 0,
+            elr_el: 0,
+            spsr_el: 0,
+            sp_el: 0,
+            tpidr_el: 0,
+            qs: [0; 32],
+            xs: [0; 32],
+        };
+        Thread {
+            id: 0,
+            pid: KERNEL_PID,
+            context: Box::new(tf),
+            stack: None,
+            state: State::Ready,
+            signal_frame: 0,
+        }
+    }
+
+    /// Creates a kernel thread that runs `entry` on a stack of its own.
+    ///
+    /// Returns `Err(OsError::NoMemory)` if the stack could not be allocated.
+    pub fn kernel(entry: fn() -> !) -> OsResult<Thread> {
+        let stack = Stack::new().ok_or(OsError::NoMemory)?;
+        let mut thread = Thread::new();
+        thread.context.elr_el = entry as usize as u64;
+        thread.context.sp_el = stack.top().as_u64();
+        thread.context.spsr_el = KERNEL_SPSR;
+        thread.context.ttbr0_el = VMM.get_baddr().as_u64();
+        thread.stack = Some(stack);
+        Ok(thread)
+    }
+
+    /// Returns `true` if this is a kernel thread.
+    pub fn is_kernel(&self) -> bool {
+        self.pid == KERNEL_PID
+    }
+
+    /// Returns `true` if this thread of the process `p` is ready to be
+    /// scheduled.
+    ///
+    /// This functions returns `true` only if one of the following holds:
+    ///
+    ///   * The state is currently `Ready`.
+    ///
+    ///   * An event being waited for has arrived.
+    ///
+    ///     If the thread is currently waiting, the corresponding event
+    ///     function is polled to determine if the event being waiting for has
+    ///     occured. If it has, the state is switched to `Ready` and this
+    ///     function returns `true`.
+    ///
+    ///   * The thread is waiting and `interrupt` is `true`, because a signal
+    ///     is waiting to be delivered to its process and the scheduler has
+    ///     picked this thread to take it.
+    ///
+    ///     The wait is abandoned and the system call fails with
+    ///     `OsError::Interrupted`, so that the signal is delivered promptly.
+    ///
+    /// Returns `false` in all other cases.
+    pub fn is_ready(&mut self, p: &mut Process, interrupt: bool) -> bool {
+        let mut state = replace(&mut self.state, State::Ready);
+        match &mut state {
+            State::Ready => true,
+            State::Waiting(_) if interrupt => {
+                self.context.xs[7] = OsError::Interrupted as u64;
+                true
+            }
+            State::Waiting(poll_fun) => {
+                if poll_fun(self, p) {
+                    true
+                } else {
+                    self.state = state;
+                    false
+                }
+            }
+            _ => {
+                self.state = state;
+                false
+            }
+        }
+    }
+}
+
+/// Puts the calling kernel thread to sleep for at least `span`, letting other
+/// threads run on its core.
+pub fn sleep(span: Duration) {
+    let ms = min(span.as_millis(), core::u32::MAX as u128) as u64;
+    unsafe {
+        asm!("mov x0, $0
+              svc $1"
+             :: "r"(ms), "i"(NR_SLEEP)
+             : "x0", "x7"
+             : "volatile");
+    }
+}
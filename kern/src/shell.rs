@@ -1,3 +1,5 @@
+use core::fmt::{self, Write};
+
 use shim::io;
 use shim::path::{Path, PathBuf};
 
@@ -9,8 +11,11 @@ use fat32::traits::FileSystem;
 use fat32::traits::{Dir, Entry};
 
 use crate::console::{kprint, kprintln, CONSOLE};
+use crate::traps::TrapFrame;
+use crate::vm::VirtualAddr;
 use crate::ALLOCATOR;
 use crate::FILESYSTEM;
+use crate::SCHEDULER;
 
 /// Error type for `Command` parse failures.
 #[derive(Debug)]
@@ -51,51 +56,181 @@ impl<'a> Command<'a> {
     }
 }
 
-/// Starts a shell using `prefix` as the prefix for each line. This function
-/// never returns.
+/// The longest line the shell accepts.
+const MAX_LINE_LEN: usize = 512;
+
+/// A line being typed into the shell.
+pub struct LineEditor {
+    buf: [u8; MAX_LINE_LEN],
+    len: usize,
+    /// Set once a newline has completed the line
+    done: bool,
+}
+
+impl LineEditor {
+    pub const fn new() -> LineEditor {
+        LineEditor {
+            buf: [0; MAX_LINE_LEN],
+            len: 0,
+            done: false,
+        }
+    }
+
+    /// Handles the input byte `b`, writing the echo to `echo`. Backspace and
+    /// delete erase the last character; other control characters and bytes
+    /// past the end of the buffer ring the bell. Returns `true` once a CR or
+    /// LF completes the line, which `line()` then returns. The next byte
+    /// starts a new line.
+    pub fn push(&mut self, b: u8, echo: &mut dyn fmt::Write) -> bool {
+        if self.done {
+            self.len = 0;
+            self.done = false;
+        }
+
+        let _ = match b {
+            b'\r' | b'\n' => {
+                self.done = true;
+                echo.write_char('\n')
+            }
+            8 | 127 if self.len > 0 => {
+                self.len -= 1;
+                write!(echo, "{} {}", 8 as char, 8 as char)
+            }
+            8 | 127 => Ok(()),
+            _ if self.len == self.buf.len() || !(b.is_ascii_graphic() || b == b' ') => {
+                echo.write_char(7 as char)
+            }
+            _ => {
+                self.buf[self.len] = b;
+                self.len += 1;
+                echo.write_char(b as char)
+            }
+        };
+        self.done
+    }
+
+    /// Returns the line typed so far, without the trailing newline.
+    pub fn line(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+/// Writes to the console through `kprint!`, so that the console is locked
+/// only while a string is being written.
+struct ConsoleWriter;
+
+impl fmt::Write for ConsoleWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        kprint!("{}", s);
+        Ok(())
+    }
+}
+
+/// Reads a line from the console, echoing every byte back, and returns the
+/// line without the trailing newline.
+///
+/// The console is locked only while a byte is being read so that the caller
+/// can keep printing with `kprint!` in between.
+fn read_line(editor: &mut LineEditor) -> &str {
+    while !editor.push(CONSOLE.lock().read_byte(), &mut ConsoleWriter) {}
+    editor.line()
+}
+
+/// What the shell does after a command.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Status {
+    /// Read the next command.
+    Continue,
+    /// The user asked to leave the shell.
+    Exit,
+}
+
+/// Runs the command on `line`, writing its output to `out`.
+///
+/// Supported commands:
+///
+///   * `echo <args>`: prints its arguments.
+///   * `exit`: returns `Status::Exit`.
+pub fn execute(line: &str, out: &mut dyn fmt::Write) -> Status {
+    let mut buffer = [""; 64];
+    let _ = match Command::parse(line, &mut buffer) {
+        Err(Error::Empty) => Ok(()),
+        Err(Error::TooManyArgs) => writeln!(out, "error: too many arguments"),
+        Ok(cmd) => match cmd.path() {
+            "exit" => return Status::Exit,
+            "echo" => {
+                for arg in &cmd.args[1..] {
+                    let _ = write!(out, "{} ", arg);
+                }
+                writeln!(out)
+            }
+            _ => writeln!(out, "unknown command {}", cmd.path()),
+        },
+    };
+    Status::Continue
+}
+
+/// Starts a shell on the console using `prefix` as the prefix for each line.
+/// This function never returns; `exit` does nothing here.
 pub fn shell(prefix: &str) -> ! {
-    let mut console = CONSOLE.lock();
+    let mut editor = LineEditor::new();
     loop {
-        let mut buffer = [""; 520];
-        let mut s : [u8; 520] = [0u8; 520];
         kprint!("{}", prefix);
-        let mut idx: usize = 0;
-        loop {
-            let b = console.read_byte();
-            if b == b'\r' || b == b'\n' {
-                s[idx] = ' ' as u8;
-                break;
-            }
-            if b == 8 {
-                kprint!("{}", 8 as char);
-                kprint!("{}", ' ');
-                kprint!("{}", 8 as char);
-                idx -= 1;
-                s[idx] = 0;
-                continue;
-            }
-            kprint!("{}", b as char);
-            s[idx] = b;
-            idx += 1;
-        }
-        let ss = &core::str::from_utf8(&s).unwrap()[..];
-        match Command::parse(ss, &mut buffer) {
-            Err(_) => {
-                kprintln!();
-                kprintln!("ASDF");
+        execute(read_line(&mut editor), &mut ConsoleWriter);
+    }
+}
+
+/// How a process stopped in the debug shell should be resumed.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Resume {
+    /// Resume normally.
+    Continue,
+    /// Execute a single instruction and stop again.
+    Step,
+}
+
+/// The maximum number of frames `bt` walks before giving up.
+const MAX_BACKTRACE_DEPTH: usize = 32;
+
+/// Starts a debugger shell for the process whose state is saved in `tf`,
+/// using `prefix` as the prefix for each line. Returns once the user asks to
+/// `continue` or `step` the process.
+///
+/// Supported commands:
+///
+///   * `regs`: dumps the trap frame.
+///   * `x <addr> <len>`: hexdumps `len` bytes of user memory at `addr`.
+///   * `bt`: walks the frame pointer chain of the process.
+///   * `continue`, `c`: resumes the process.
+///   * `step`, `s`: executes one instruction and stops again.
+pub fn debug_shell(prefix: &str, tf: &mut TrapFrame) -> Resume {
+    let mut editor = LineEditor::new();
+    loop {
+        let mut buffer = [""; 64];
+        kprint!("{}", prefix);
+        let line = read_line(&mut editor);
+        match Command::parse(line, &mut buffer) {
+            Err(Error::Empty) => (),
+            Err(Error::TooManyArgs) => {
+                kprintln!("error: too many arguments");
             },
             Ok(cmd) => {
                 match cmd.path() {
-                    "exit" => (),
-                    "echo" => {
-                        kprintln!();
-                        for i in 1..cmd.args.len() {
-                            kprint!("{} ", cmd.args[i]);
+                    "continue" | "c" => return Resume::Continue,
+                    "step" | "s" => return Resume::Step,
+                    "regs" => dump_regs(tf),
+                    "x" => {
+                        if cmd.args.len() != 3 {
+                            kprintln!("usage: x <addr> <len>");
+                            continue;
+                        }
+                        match (parse_num(cmd.args[1]), parse_num(cmd.args[2])) {
+                            (Some(addr), Some(len)) => hexdump(addr, len),
+                            _ => kprintln!("error: invalid number"),
                         }
-                        kprintln!();
                     },
+                    "bt" => backtrace(tf),
                     _ => {
-                        kprintln!();
                         kprintln!("unknown command {}", cmd.path());
                     }
                 }
@@ -103,3 +238,108 @@ pub fn shell(prefix: &str) -> ! {
         }
     }
 }
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+fn parse_num(s: &str) -> Option<usize> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        usize::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Prints every register saved in `tf`.
+fn dump_regs(tf: &TrapFrame) {
+    kprintln!("elr   {:016x}  spsr  {:016x}", tf.elr_el, tf.spsr_el);
+    kprintln!("sp    {:016x}  tpidr {:016x}", tf.sp_el, tf.tpidr_el);
+    kprintln!("ttbr0 {:016x}  ttbr1 {:016x}", tf.ttbr0_el, tf.ttbr1_el);
+    for i in 0..31 {
+        kprint!("x{:02}   {:016x}", i, tf.xs[i]);
+        if i % 2 == 1 || i == 30 {
+            kprintln!();
+        } else {
+            kprint!("  ");
+        }
+    }
+}
+
+/// Reads a byte of user memory at `va` through the page table of the process
+/// whose thread trapped on this core. Returns `None` if `va` is not mapped.
+fn read_user_byte(va: usize) -> Option<u8> {
+    let pa = SCHEDULER.critical(|scheduler| {
+        scheduler.current_process().vmap.translate(VirtualAddr::from(va))
+    })?;
+    Some(unsafe { (pa.as_usize() as *const u8).read_volatile() })
+}
+
+/// Reads a little-endian `u64` of user memory at `va`. Returns `None` if any
+/// byte of it is not mapped.
+fn read_user_u64(va: usize) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    for i in 0..8 {
+        bytes[i] = read_user_byte(va.checked_add(i)?)?;
+    }
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Hexdumps `len` bytes of user memory starting at `addr`, 16 bytes a line.
+fn hexdump(addr: usize, len: usize) {
+    let mut line = [0u8; 16];
+    let end = addr.saturating_add(len);
+    for base in (addr..end).step_by(16) {
+        let n = core::cmp::min(16, end - base);
+        for i in 0..n {
+            match read_user_byte(base + i) {
+                Some(b) => line[i] = b,
+                None => {
+                    kprintln!("{:016x}: <unmapped>", base + i);
+                    return;
+                }
+            }
+        }
+
+        kprint!("{:016x}: ", base);
+        for i in 0..16 {
+            if i < n {
+                kprint!("{:02x} ", line[i]);
+            } else {
+                kprint!("   ");
+            }
+        }
+        kprint!(" |");
+        for &b in &line[..n] {
+            let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' };
+            kprint!("{}", c);
+        }
+        kprintln!("|");
+    }
+}
+
+/// Prints the call stack of the process by walking the frame records linked
+/// through `x29`. Each record holds the caller's frame pointer followed by
+/// the return address.
+fn backtrace(tf: &TrapFrame) {
+    kprintln!("#0  {:016x}", tf.elr_el);
+    let mut fp = tf.xs[29] as usize;
+    for depth in 1..MAX_BACKTRACE_DEPTH {
+        if fp == 0 || fp % 8 != 0 {
+            return;
+        }
+        let (next, lr) = match (read_user_u64(fp), read_user_u64(fp + 8)) {
+            (Some(next), Some(lr)) => (next as usize, lr),
+            _ => {
+                kprintln!("    <frame {:016x} unmapped>", fp);
+                return;
+            }
+        };
+        if lr == 0 {
+            return;
+        }
+        kprintln!("#{:<2} {:016x}  (fp {:016x})", depth, lr, fp);
+        // The stack grows down, so caller frames live at higher addresses.
+        if next <= fp {
+            return;
+        }
+        fp = next;
+    }
+}
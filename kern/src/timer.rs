@@ -0,0 +1,399 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+use aarch64::{affinity, sev};
+use pi::local_interrupt::{counter_time, local_tick_in, local_timer_stop};
+
+use kernel_api::SIGALRM;
+
+use crate::mutex::{Mutex, Once};
+use crate::param::TICK;
+use crate::percore::{slice_end, set_slice_end, set_timer_deadline, timer_deadline};
+use crate::process::Id;
+use crate::{SCHEDULER, TIMERS};
+
+#[cfg(test)]
+mod tests;
+
+/// Returns the current time of the clock kernel timers are measured with,
+/// the generic timer's counter, which is also what the per-core timer
+/// interrupts are armed against.
+pub fn now() -> Duration {
+    counter_time()
+}
+
+/// Identifies a timer registered with `Timers`.
+pub type TimerId = u64;
+
+/// The code a timer runs when it expires. It runs in the timer interrupt of
+/// whichever core notices the expiry first, without the timer queue locked,
+/// so it may register and cancel timers but must not block.
+pub type Callback = Box<dyn FnMut() + Send>;
+
+struct Timer {
+    id: TimerId,
+    deadline: Duration,
+    /// The interval a periodic timer is re-armed with
+    period: Option<Duration>,
+    callback: Callback,
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timer")
+            .field("id", &self.id)
+            .field("deadline", &self.deadline)
+            .field("period", &self.period)
+            .finish()
+    }
+}
+
+/// Pending timers sorted by deadline. Timers with the same deadline expire in
+/// the order they were added.
+#[derive(Debug)]
+pub struct TimerQueue {
+    timers: Vec<Timer>,
+    /// Timers whose callbacks are running, and whether they were cancelled
+    /// meanwhile
+    running: Vec<(TimerId, bool)>,
+    next_id: TimerId,
+}
+
+impl TimerQueue {
+    /// Returns a queue without timers.
+    pub fn new() -> TimerQueue {
+        TimerQueue {
+            timers: Vec::new(),
+            running: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Adds a timer that runs `callback` at `deadline` and, if `period` is
+    /// `Some`, every `period` after that. Returns the ID of the timer.
+    pub fn add(&mut self, deadline: Duration, period: Option<Duration>, callback: Callback) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.insert(Timer { id, deadline, period, callback });
+        id
+    }
+
+    fn insert(&mut self, timer: Timer) {
+        let i = self
+            .timers
+            .iter()
+            .position(|t| t.deadline > timer.deadline)
+            .unwrap_or(self.timers.len());
+        self.timers.insert(i, timer);
+    }
+
+    /// Cancels the timer `id`. A timer whose callback is running is not
+    /// re-armed. Returns `false` if there is no such timer.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        if let Some(i) = self.timers.iter().position(|t| t.id == id) {
+            self.timers.remove(i);
+            return true;
+        }
+        match self.running.iter_mut().find(|running| running.0 == id) {
+            Some(running) => {
+                running.1 = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the deadline of the timer `id`, or `None` if there is no such
+    /// timer or its callback is running.
+    pub fn deadline(&self, id: TimerId) -> Option<Duration> {
+        self.timers.iter().find(|t| t.id == id).map(|t| t.deadline)
+    }
+
+    /// Returns the earliest deadline of the queue.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.timers.first().map(|t| t.deadline)
+    }
+
+    /// Removes the earliest timer if it has expired at `now`, and records
+    /// that its callback runs.
+    fn pop_expired(&mut self, now: Duration) -> Option<Timer> {
+        if self.next_deadline()? > now {
+            return None;
+        }
+        let timer = self.timers.remove(0);
+        self.running.push((timer.id, false));
+        Some(timer)
+    }
+
+    /// Records that the callback of `timer`, which expired at or before
+    /// `now`, has returned, and re-arms the timer if it is periodic and has
+    /// not been cancelled. Missed periods are skipped.
+    fn finish(&mut self, mut timer: Timer, now: Duration) {
+        let cancelled = match self.running.iter().position(|&(t, _)| t == timer.id) {
+            Some(i) => self.running.swap_remove(i).1,
+            None => false,
+        };
+        if let (Some(period), false) = (timer.period, cancelled) {
+            while timer.deadline <= now {
+                timer.deadline += period;
+            }
+            self.insert(timer);
+        }
+    }
+}
+
+/// The kernel timers of the machine. Every core's generic timer is armed for
+/// the earlier of the end of its time slice and the earliest kernel timer,
+/// and the first core to take its interrupt after a deadline runs the
+/// expired callbacks. An idle core has no time slice, so its timer is armed
+/// for the earliest kernel timer only, or stopped if there is none.
+pub struct Timers {
+    queue: Once<Mutex<TimerQueue>>,
+    /// The earliest deadline in microseconds, or `u64::MAX`, read without the
+    /// lock
+    next: AtomicU64,
+}
+
+fn as_micros(t: Duration) -> u64 {
+    min(t.as_micros(), core::u64::MAX as u128) as u64
+}
+
+/// Returns when the time slice of this core ends.
+fn slice_end_time() -> Duration {
+    Duration::from_micros(slice_end())
+}
+
+impl Timers {
+    /// Returns a set of timers without timers.
+    pub const fn new() -> Timers {
+        Timers {
+            queue: Once::new(),
+            next: AtomicU64::new(core::u64::MAX),
+        }
+    }
+
+    /// Enters a critical region and executes the provided closure with a
+    /// mutable reference to the queue.
+    fn critical<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut TimerQueue) -> R,
+    {
+        let mut queue = self.queue.call_once(|| Mutex::new(TimerQueue::new())).lock();
+        let rtn = f(&mut queue);
+        let next = queue.next_deadline().map_or(core::u64::MAX, as_micros);
+        self.next.store(next, Ordering::Relaxed);
+        rtn
+    }
+
+    /// Runs `callback` at `deadline`, measured like `now()`, and, if `period`
+    /// is `Some`, every `period` after that until the timer is cancelled.
+    /// Returns the ID of the timer.
+    pub fn add(&self, deadline: Duration, period: Option<Duration>, callback: Callback) -> TimerId {
+        let id = self.critical(|queue| queue.add(deadline, period, callback));
+        self.arm();
+        id
+    }
+
+    /// Runs `callback` once at `deadline` and returns the ID of the timer.
+    pub fn at(&self, deadline: Duration, callback: Callback) -> TimerId {
+        self.add(deadline, None, callback)
+    }
+
+    /// Runs `callback` once after `delay`.
+    pub fn after(&self, delay: Duration, callback: Callback) -> TimerId {
+        self.at(now() + delay, callback)
+    }
+
+    /// Runs `callback` every `period`, starting one period from now, until
+    /// the timer is cancelled.
+    pub fn every(&self, period: Duration, callback: Callback) -> TimerId {
+        self.add(now() + period, Some(period), callback)
+    }
+
+    /// Cancels the timer `id`. Returns `false` if there is no such timer,
+    /// for instance because it was a one-shot timer that has run.
+    pub fn cancel(&self, id: TimerId) -> bool {
+        self.critical(|queue| queue.cancel(id))
+    }
+
+    /// Returns the deadline of the timer `id`, or `None` if there is no such
+    /// timer or its callback is running.
+    pub fn deadline(&self, id: TimerId) -> Option<Duration> {
+        self.critical(|queue| queue.deadline(id))
+    }
+
+    /// Returns the earliest deadline of the timers, if any.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        match self.next.load(Ordering::Relaxed) {
+            core::u64::MAX => None,
+            us => Some(Duration::from_micros(us)),
+        }
+    }
+
+    /// Runs the callbacks of the timers that have expired at `now` and
+    /// returns how many ran. Cores waiting for events are woken so that they
+    /// see what the callbacks changed.
+    pub fn fire(&self, now: Duration) -> usize {
+        if as_micros(now) < self.next.load(Ordering::Relaxed) {
+            return 0;
+        }
+        let mut fired = 0;
+        while let Some(mut timer) = self.critical(|queue| queue.pop_expired(now)) {
+            (timer.callback)();
+            fired += 1;
+            self.critical(|queue| queue.finish(timer, now));
+        }
+        if fired > 0 {
+            sev();
+        }
+        fired
+    }
+
+    /// Starts a time slice of this core and arms its generic timer, when the
+    /// core starts scheduling and whenever it stops being idle. The timer
+    /// interrupt must have been enabled.
+    pub fn start(&self) {
+        set_slice_end(as_micros(now() + TICK));
+        set_timer_deadline(core::u64::MAX);
+        self.arm();
+    }
+
+    /// Ends the time slice of this idle core and arms its generic timer for
+    /// the earliest deadline only, or stops it if there are no timers.
+    pub fn idle(&self) {
+        set_slice_end(core::u64::MAX);
+        match self.next_deadline() {
+            Some(next) => self.set(next),
+            None => {
+                set_timer_deadline(core::u64::MAX);
+                local_timer_stop(affinity());
+            }
+        }
+    }
+
+    /// Arms this core's generic timer for the earlier of the end of its time
+    /// slice and the earliest deadline, unless it is armed earlier already.
+    pub fn arm(&self) {
+        let deadline = match self.next_deadline() {
+            Some(next) => min(next, slice_end_time()),
+            None => slice_end_time(),
+        };
+        if as_micros(deadline) < timer_deadline() {
+            self.set(deadline);
+        }
+    }
+
+    /// Arms this core's generic timer for `deadline`.
+    fn set(&self, deadline: Duration) {
+        let now = now();
+        let delay = if deadline > now { deadline - now } else { Duration::from_micros(1) };
+        set_timer_deadline(as_micros(deadline));
+        local_tick_in(affinity(), delay);
+    }
+
+    /// Handles this core's timer interrupt at `now`: runs the expired
+    /// callbacks, starts a new time slice if the current one is over, and
+    /// re-arms the timer. Returns `true` if the core should switch threads,
+    /// because its slice is over or a callback may have readied a thread.
+    pub fn tick(&self, now: Duration) -> bool {
+        let fired = self.fire(now);
+        let expired = now >= slice_end_time();
+        if expired {
+            set_slice_end(as_micros(now + TICK));
+        }
+        let deadline = match self.next_deadline() {
+            Some(next) => min(next, slice_end_time()),
+            None => slice_end_time(),
+        };
+        self.set(deadline);
+        expired || fired > 0
+    }
+}
+
+impl fmt::Debug for Timers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timers")
+    }
+}
+
+/// A one-shot timer that a waiting thread polls. The timer is cancelled when
+/// this is dropped, so a wait that ends early leaves nothing behind.
+#[derive(Debug)]
+pub struct Wakeup {
+    id: TimerId,
+    expired: Arc<AtomicBool>,
+}
+
+impl Wakeup {
+    /// Returns a wakeup that expires at `deadline`, measured like `now()`.
+    pub fn at(deadline: Duration) -> Wakeup {
+        let expired = Arc::new(AtomicBool::new(false));
+        let flag = expired.clone();
+        let id = TIMERS.at(deadline, Box::new(move || flag.store(true, Ordering::Release)));
+        Wakeup { id, expired }
+    }
+
+    /// Returns a wakeup that expires after `delay`.
+    pub fn after(delay: Duration) -> Wakeup {
+        Wakeup::at(now() + delay)
+    }
+
+    /// Returns `true` once the deadline has passed.
+    pub fn has_expired(&self) -> bool {
+        self.expired.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for Wakeup {
+    fn drop(&mut self) {
+        TIMERS.cancel(self.id);
+    }
+}
+
+/// The interval timer of a process. It raises `SIGALRM` in the process when
+/// it expires and, if it is periodic, every period after that. The timer is
+/// cancelled when this is dropped.
+#[derive(Debug)]
+pub struct Alarm {
+    id: TimerId,
+    period: Option<Duration>,
+}
+
+impl Alarm {
+    /// Returns an alarm for the process `pid` that expires after `delay` and
+    /// then every `period`, if it is `Some`.
+    pub fn new(pid: Id, delay: Duration, period: Option<Duration>) -> Alarm {
+        let callback = Box::new(move || {
+            SCHEDULER.critical(|scheduler| {
+                if let Some(p) = scheduler.process_mut(pid) {
+                    let _ = p.signals.raise(SIGALRM);
+                }
+            })
+        });
+        let id = TIMERS.add(now() + delay, period, callback);
+        Alarm { id, period }
+    }
+
+    /// Returns the time left until the alarm next expires, or `None` if it
+    /// is a one-shot alarm that has expired.
+    pub fn remaining(&self) -> Option<Duration> {
+        let deadline = TIMERS.deadline(self.id)?;
+        Some(deadline.checked_sub(now()).unwrap_or_default())
+    }
+
+    /// Returns the period of the alarm, if it is periodic.
+    pub fn period(&self) -> Option<Duration> {
+        self.period
+    }
+}
+
+impl Drop for Alarm {
+    fn drop(&mut self) {
+        TIMERS.cancel(self.id);
+    }
+}
@@ -0,0 +1,67 @@
+use super::*;
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+fn nop() -> Callback {
+    Box::new(|| ())
+}
+
+/// Pops and finishes every timer expired at `now`, returning their IDs in
+/// the order they ran.
+fn expire(queue: &mut TimerQueue, now: Duration) -> Vec<TimerId> {
+    let mut ran = Vec::new();
+    while let Some(mut timer) = queue.pop_expired(now) {
+        (timer.callback)();
+        ran.push(timer.id);
+        queue.finish(timer, now);
+    }
+    ran
+}
+
+#[test]
+fn expires_in_deadline_order() {
+    let mut queue = TimerQueue::new();
+    let c = queue.add(ms(30), None, nop());
+    let a = queue.add(ms(10), None, nop());
+    let b1 = queue.add(ms(20), None, nop());
+    let b2 = queue.add(ms(20), None, nop());
+    assert_eq!(queue.next_deadline(), Some(ms(10)));
+    assert_eq!(queue.deadline(b2), Some(ms(20)));
+
+    assert!(expire(&mut queue, ms(5)).is_empty());
+    assert_eq!(expire(&mut queue, ms(20)), vec![a, b1, b2]);
+    assert_eq!(queue.next_deadline(), Some(ms(30)));
+    assert!(queue.cancel(c));
+    assert!(!queue.cancel(c));
+    assert_eq!(queue.next_deadline(), None);
+    assert!(expire(&mut queue, ms(100)).is_empty());
+}
+
+#[test]
+fn periodic_timers() {
+    let mut queue = TimerQueue::new();
+    let tick = queue.add(ms(10), Some(ms(10)), nop());
+    assert_eq!(expire(&mut queue, ms(10)), vec![tick]);
+    assert_eq!(queue.next_deadline(), Some(ms(20)));
+
+    // Missed periods are skipped rather than run back to back.
+    assert_eq!(expire(&mut queue, ms(45)), vec![tick]);
+    assert_eq!(queue.next_deadline(), Some(ms(50)));
+
+    assert!(queue.cancel(tick));
+    assert_eq!(queue.next_deadline(), None);
+}
+
+#[test]
+fn cancel_while_running() {
+    let mut queue = TimerQueue::new();
+    let tick = queue.add(ms(10), Some(ms(10)), nop());
+    let timer = queue.pop_expired(ms(10)).unwrap();
+    assert_eq!(queue.deadline(tick), None);
+    assert!(queue.cancel(tick));
+    queue.finish(timer, ms(10));
+    assert_eq!(queue.next_deadline(), None);
+    assert!(queue.running.is_empty());
+}
@@ -8,15 +8,17 @@ pub use self::frame::TrapFrame;
 use pi::interrupt::{Controller, Interrupt};
 use pi::local_interrupt::{LocalController, LocalInterrupt};
 
-use self::syndrome::Syndrome;
+use self::syndrome::{Fault, Syndrome};
 use self::syscall::handle_syscall;
 use crate::percore;
 use crate::traps::irq::IrqHandlerRegistry;
 
 use crate::console::{kprintln, kprint};
 use crate::shell;
-use crate::GLOBAL_IRQ;
+use crate::shell::Resume;
+use crate::{FIQ, GLOBAL_IRQ, SCHEDULER};
 use crate::percore::*;
+use kernel_api::{SIGBUS, SIGILL, SIGSEGV, SIGTRAP};
 
 #[repr(u16)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -43,12 +45,60 @@ pub struct Info {
     kind: Kind,
 }
 
+/// Arms a software step so that the process saved in `tf` traps back into
+/// the kernel after executing exactly one instruction.
+fn enable_single_step(tf: &mut TrapFrame) {
+    use aarch64::*;
+    unsafe {
+        // Software step exceptions are disabled while the OS lock is held.
+        OSLAR_EL1.set(0);
+        MDSCR_EL1.set(MDSCR_EL1.get() | MDSCR_EL1::SS);
+    }
+    tf.spsr_el |= SPSR_EL1::SS;
+}
+
+/// Disarms a software step previously set up by `enable_single_step()`.
+fn disable_single_step(tf: &mut TrapFrame) {
+    use aarch64::*;
+    unsafe {
+        MDSCR_EL1.set(MDSCR_EL1.get() & !MDSCR_EL1::SS);
+    }
+    tf.spsr_el &= !SPSR_EL1::SS;
+}
+
+/// Returns the signal that a synchronous exception other than a system call
+/// or a debug event raises in the user process that caused it.
+fn fault_signal(syn: Syndrome) -> u64 {
+    match syn {
+        Syndrome::DataAbort { kind: Fault::Alignment, .. } => SIGBUS,
+        Syndrome::InstructionAbort { .. } | Syndrome::DataAbort { .. } => SIGSEGV,
+        Syndrome::PCAlignmentFault | Syndrome::SpAlignmentFault => SIGBUS,
+        Syndrome::Breakpoint | Syndrome::Watchpoint => SIGTRAP,
+        _ => SIGILL,
+    }
+}
+
+/// Returns `true` if `tf` returns to user space, that is, to `EL0t`.
+fn is_user(tf: &TrapFrame) -> bool {
+    tf.spsr_el & 0b1111 == 0
+}
+
 /// This function is called when an exception occurs. The `info` parameter
 /// specifies the source and kind of exception that has occurred. The `esr` is
 /// the value of the exception syndrome register. Finally, `tf` is a pointer to
 /// the trap frame for the exception.
+///
+/// Before returning to user space, pending signals are delivered to the
+/// process that runs next, which may not be the one that took the exception.
 #[no_mangle]
 pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
+    handle(info, esr, tf);
+    if is_user(tf) {
+        SCHEDULER.deliver_signals(tf);
+    }
+}
+
+fn handle(info: Info, esr: u32, tf: &mut TrapFrame) {
     // kprintln!("{:?} {:?} {:b}", info.source, info.kind, esr);
     match info.kind {
         Kind::Synchronous => {
@@ -56,8 +106,18 @@ pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
            // kprintln!("{:?}", syn);
             match syn {
                 Syndrome::Brk(k) => {
+                    let resume = shell::debug_shell("Brk! ", tf);
                     tf.elr_el += 4;
-                    shell::shell("Brk! ");
+                    if resume == Resume::Step {
+                        enable_single_step(tf);
+                    }
+                    return;
+                },
+                Syndrome::Step => {
+                    disable_single_step(tf);
+                    if shell::debug_shell("Step! ", tf) == Resume::Step {
+                        enable_single_step(tf);
+                    }
                     return;
                 },
                 Syndrome::Svc(n) => {
@@ -65,19 +125,40 @@ pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
                     return;
                 }
                 k => {
-                    // kprintln!("Error");
+                    // A fault in user space becomes a signal to the process.
+                    if info.source == Source::LowerAArch64 {
+                        let sig = fault_signal(k);
+                        SCHEDULER.critical(|scheduler| {
+                            let p = scheduler.current_process();
+                            debug!("process {} faulted: {:?} at {:#x}", p.id, k, tf.elr_el);
+                            p.signals.force(sig);
+                        });
+                    }
                     return;
                 },
             }
         },
         Kind::Irq => {
-            // GLOBAL_IRQ.invoke(Interrupt::Timer1, tf);
-            // kprintln!("sss {}", tf.tpidr_el);
             use aarch64::*;
-            local_irq().invoke(LocalInterrupt::LocalTimer, tf);
-            // kprint!("{}", affinity());
-
-            // kprintln!("ttt {}", tf.tpidr_el);
+            let local = LocalController::new(affinity());
+            // Peripheral interrupts are routed to a single core and show up
+            // there as the local `Gpu` source.
+            if local.is_pending(LocalInterrupt::Gpu) {
+                let global = Controller::new();
+                for int in Interrupt::iter() {
+                    if global.is_pending(int) {
+                        GLOBAL_IRQ.invoke(int, tf);
+                    }
+                }
+            }
+            // The timer handler may switch to another process, so it runs last.
+            if local.is_pending(LocalInterrupt::CNTPNSIRQ) {
+                local_irq().invoke(LocalInterrupt::CNTPNSIRQ, tf);
+            }
+            return;
+        }
+        Kind::Fiq => {
+            FIQ.invoke((), tf);
             return;
         }
         _ => {
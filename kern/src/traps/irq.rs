@@ -33,6 +33,7 @@ impl GlobalIrq {
             Mutex::new(None),
             Mutex::new(None),
             Mutex::new(None),
+            Mutex::new(None),
         ])
     }
 }
@@ -76,6 +77,7 @@ impl Index<Interrupt> for GlobalIrq {
             Gpio2 => 5,
             Gpio3 => 6,
             Uart => 7,
+            Aux => 8,
         };
         &self.0[index]
     }
@@ -86,13 +88,7 @@ impl Index<LocalInterrupt> for LocalIrq {
 
     fn index(&self, int: LocalInterrupt) -> &IrqHandlerMutex {
         // Lab 5 1.C
-        use LocalInterrupt::*;
-        let index = match int {
-            LocalInterrupt::LocalTimer => 11,
-            _ => 0,
-        };
-        &self.0[index]
-        // unimplemented!("LocalInterrupt Index")
+        &self.0[int as usize]
     }
 }
 
@@ -101,7 +97,7 @@ impl Index<()> for Fiq {
 
     fn index(&self, _: ()) -> &IrqHandlerMutex {
         // Lab 5 2.B
-        unimplemented!("FIQ Index")
+        &self.0
     }
 }
 
@@ -56,15 +56,6 @@ impl From<u32> for Syndrome {
     fn from(esr: u32) -> Syndrome {
         use self::Syndrome::*;
 
-        if (esr & 0b11111100000000000000000000000000 == 0b11110000000000000000000000000000) {
-            return Brk((esr & 0b1111111111111111) as u16);
-        }
-        if (esr & 0b11111100000000000000000000000000 == 0b01010100000000000000000000000000) {
-            return Svc((esr & 0b1111111111111111) as u16);
-        }
-        return Unknown;
-        use self::Syndrome::*;
-
         let exception_class = esr >> 26;
         let iss = esr & 0xFFFFFF;
 
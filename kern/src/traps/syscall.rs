@@ -1,14 +1,24 @@
 use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::min;
 use core::time::Duration;
 use pi::timer::*;
 
-use smoltcp::wire::{IpAddress, IpEndpoint};
+use smoltcp::socket::{SocketHandle, TcpState};
+use smoltcp::time::Instant;
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
 
 use crate::console::{kprint, kprintln, CONSOLE};
-use crate::param::USER_IMG_BASE;
-use crate::process::{Process, State};
+use crate::net::dns;
+use crate::net::{EthernetDriver, SocketKind};
+use crate::param::{ALARM_MIN_PERIOD, USER_IMG_BASE};
+use crate::percore::idle_stats;
+use crate::process::{signal, Action, End, Outcome, Pending, PipeEnd, Process, Receiver, Region, State, Thread, Waiter};
+use crate::timer::{Alarm, Wakeup};
 use crate::traps::TrapFrame;
-use crate::{ETHERNET, SCHEDULER};
+use crate::vm::VirtualAddr;
+use crate::{ETHERNET, FILESYSTEM, FUTEXES, PORTS, SCHEDULER, SHARED_MEMORY};
 
 use kernel_api::*;
 
@@ -21,16 +31,16 @@ use kernel_api::*;
 /// when `sleep` returned.
 pub fn sys_sleep(ms: u32, tf: &mut TrapFrame) {
     let start = current_time();
-    let end = start + Duration::from_millis(ms as u64);
-    let f = Box::new(move |p: &mut Process| {
-        if current_time() < end {
-            false
-        } else {
-            true
+    let wakeup = Wakeup::after(Duration::from_millis(ms as u64));
+    let f = Box::new(move |t: &mut Thread, _: &mut Process| {
+        if !wakeup.has_expired() {
+            return false;
         }
+        t.context.xs[0] = (current_time() - start).as_millis() as u64;
+        t.context.xs[7] = OsError::Ok as u64;
+        true
     });
     SCHEDULER.switch(State::Waiting(f), tf);
-    tf.xs[0] = (current_time() - start).as_millis() as u64;
 }
 
 /// Returns current time.
@@ -46,22 +56,1139 @@ pub fn sys_time(tf: &mut TrapFrame) {
     tf.xs[0] = t.as_micros() as u64;
 }
 
-/// Kills the current process.
+/// Arms or disarms the interval timer of the current process, which raises
+/// `SIGALRM` when it expires.
+///
+/// This system call takes two parameters: the time until the timer expires,
+/// in microseconds, or `0` to disarm it; and the period it expires again
+/// with, in microseconds, or `0` if it expires once.
+///
+/// In addition to the usual status value, this system call returns two
+/// parameters describing the timer it replaced:
+///  - the time until it would have expired, in microseconds, or `0`
+///  - its period, in microseconds, or `0`.
+///
+/// # Errors
+/// This function can return following errors:
 ///
-/// This system call does not take paramer and does not return any value.
-pub fn sys_exit(tf: &mut TrapFrame) {
-    unimplemented!("sys_exit()")
+/// - `OsError::InvalidArgument`: The period is shorter than `ALARM_MIN_PERIOD`.
+pub fn sys_alarm(value: u64, interval: u64, tf: &mut TrapFrame) {
+    let period = match interval {
+        0 => None,
+        us => Some(Duration::from_micros(us)),
+    };
+    if period.map_or(false, |period| period < ALARM_MIN_PERIOD) {
+        tf.xs[7] = OsError::InvalidArgument as u64;
+        return;
+    }
+
+    let old = SCHEDULER.critical(|scheduler| {
+        let p = scheduler.current_process();
+        let alarm = match value {
+            0 => None,
+            us => Some(Alarm::new(p.id, Duration::from_micros(us), period)),
+        };
+        core::mem::replace(&mut p.alarm, alarm)
+    });
+    let micros = |t: Option<Duration>| t.map_or(0, |t| t.as_micros() as u64);
+    tf.xs[0] = micros(old.as_ref().and_then(Alarm::remaining));
+    tf.xs[1] = micros(old.as_ref().and_then(Alarm::period));
+    tf.xs[7] = OsError::Ok as u64;
 }
 
-/// Writes to console.
+/// Returns the idle-time counters of a core.
+///
+/// This system call takes one parameter: the number of the core.
+///
+/// In addition to the usual status value, this system call returns two
+/// parameters:
+///  - the time the core has spent idle since boot, in microseconds
+///  - the number of times the core has gone idle.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::InvalidArgument`: There is no such core.
+pub fn sys_idle_time(core: usize, tf: &mut TrapFrame) {
+    match idle_stats(core, current_time().as_micros() as u64) {
+        Some((time, count)) => {
+            tf.xs[0] = time;
+            tf.xs[1] = count;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        None => tf.xs[7] = OsError::InvalidArgument as u64,
+    }
+}
+
+/// Kills the current process with all of its threads.
+///
+/// This system call takes one parameter: the exit status reported to the
+/// parent's `wait`. It does not return.
+pub fn sys_exit(status: u64, tf: &mut TrapFrame) {
+    let _ = SCHEDULER.kill(status, tf);
+    SCHEDULER.switch_to(tf);
+}
+
+/// Returns `true` if the console writes of the process that owns `tf` go to
+/// a pipe.
+fn is_stdout_redirected(tf: &TrapFrame) -> bool {
+    SCHEDULER.critical(|scheduler| scheduler.current_process().stdout.is_some())
+}
+
+/// Writes to console, or to the pipe the process's output is redirected to.
 ///
 /// This system call takes one parameter: a u8 character to print.
 ///
 /// It only returns the usual status value.
 pub fn sys_write(b: u8, tf: &mut TrapFrame) {
+    if is_stdout_redirected(tf) {
+        pipe_write(PipeRef::Stdout, vec![b], tf);
+        return;
+    }
     kprint!("{}", b as char);
 }
 
+/// The maximum number of bytes `sys_read_console` copies in a single call.
+const READ_CONSOLE_MAX: usize = 128;
+
+/// Reads bytes from the console.
+///
+/// This system call takes the address of the buffer as the first parameter,
+/// the length of the buffer as the second parameter, and flags as the third
+/// parameter. Bytes are served from the console's receive buffer. Unless
+/// `READ_CONSOLE_NONBLOCK` is set, the process waits until at least one byte
+/// has been received.
+///
+/// If the process's input is redirected to a pipe, bytes are read from the
+/// pipe instead, and `0` is returned once every writer has closed it.
+///
+/// In addition to the usual status value, this system call returns the number
+/// of bytes read.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice.
+/// - `OsError::IoErrorTimedOut`: `READ_CONSOLE_NONBLOCK` is set and no byte has been received.
+pub fn sys_read_console(va: usize, len: usize, flags: u64, tf: &mut TrapFrame) {
+    if SCHEDULER.critical(|scheduler| scheduler.current_process().stdin.is_some()) {
+        let block = flags & READ_CONSOLE_NONBLOCK == 0;
+        pipe_read(PipeRef::Stdin, va, len, block, tf);
+        return;
+    }
+
+    let user_buf = match unsafe { to_user_slice_mut(va, len) } {
+        Ok(buf) => buf,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let len = min(len, READ_CONSOLE_MAX);
+    if flags & READ_CONSOLE_NONBLOCK != 0 {
+        let n = CONSOLE.lock().read_buffered(&mut user_buf[..len]);
+        if n == 0 && len > 0 {
+            tf.xs[7] = OsError::IoErrorTimedOut as u64;
+        } else {
+            tf.xs[0] = n as u64;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        return;
+    }
+
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let mut buf = [0u8; READ_CONSOLE_MAX];
+        let n = CONSOLE.lock().read_buffered(&mut buf[..len]);
+        if n == 0 && len > 0 {
+            return false;
+        }
+
+        match p.vmap.write_bytes(VirtualAddr::from(va), &buf[..n]) {
+            Ok(()) => {
+                t.context.xs[0] = n as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => t.context.xs[7] = e as u64,
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// The maximum number of bytes the pipe system calls copy in a single call.
+const PIPE_IO_MAX: usize = 16384;
+
+/// A pipe end of the current process: a pipe descriptor, or the pipe the
+/// console reads or writes are redirected to.
+#[derive(Copy, Clone, Debug)]
+enum PipeRef {
+    Fd(usize),
+    Stdin,
+    Stdout,
+}
+
+impl PipeRef {
+    /// Returns the pipe end `self` refers to in `p`.
+    ///
+    /// # Errors
+    /// Returns `Err(OsError::InvalidArgument)` if there is no such pipe end.
+    fn get(self, p: &Process) -> OsResult<&PipeEnd> {
+        match self {
+            PipeRef::Fd(fd) => p.pipe(fd),
+            PipeRef::Stdin => p.stdin.as_ref().ok_or(OsError::InvalidArgument),
+            PipeRef::Stdout => p.stdout.as_ref().ok_or(OsError::InvalidArgument),
+        }
+    }
+
+    /// Returns `Ok(())` if `self` is an `end` end of a pipe in the process
+    /// that owns `tf`.
+    fn check(self, end: End, tf: &TrapFrame) -> OsResult<()> {
+        SCHEDULER.critical(|scheduler| match self.get(scheduler.current_process())?.end() {
+            e if e == end => Ok(()),
+            _ => Err(OsError::InvalidArgument),
+        })
+    }
+}
+
+/// Reads from the read end `pipe` into the user buffer at `va` of length
+/// `len`. The process waits until at least one byte is buffered or every
+/// writer has closed the pipe, in which case `0` is returned. Unless `block`
+/// is set, `OsError::IoErrorTimedOut` is returned instead of waiting.
+fn pipe_read(pipe: PipeRef, va: usize, len: usize, block: bool, tf: &mut TrapFrame) {
+    let result = unsafe { to_user_slice_mut(va, len) }
+        .and_then(|buf| pipe.check(End::Read, tf).map(|_| buf));
+    let user_buf = match result {
+        Ok(buf) => buf,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let len = min(len, PIPE_IO_MAX);
+    if !block {
+        let result = SCHEDULER.critical(|scheduler| {
+            let end = pipe.get(scheduler.current_process())?;
+            let n = end.lock().read(&mut user_buf[..len]);
+            n.ok_or(OsError::IoErrorTimedOut)
+        });
+        match result {
+            Ok(n) => {
+                tf.xs[0] = n as u64;
+                tf.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => tf.xs[7] = e as u64,
+        }
+        return;
+    }
+
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let mut buf = vec![0u8; len];
+        let n = match pipe.get(p).map(|end| end.lock().read(&mut buf)) {
+            Ok(Some(n)) => n,
+            Ok(None) => return false,
+            Err(e) => {
+                t.context.xs[7] = e as u64;
+                return true;
+            }
+        };
+
+        match p.vmap.write_bytes(VirtualAddr::from(va), &buf[..n]) {
+            Ok(()) => {
+                t.context.xs[0] = n as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => t.context.xs[7] = e as u64,
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Writes `data` to the write end `pipe`. The process waits until all of
+/// `data` has been buffered, and then returns its length. If every reader
+/// closes the pipe first, the number of bytes written so far is returned, or
+/// `OsError::IoErrorBrokenPipe` if there are none.
+fn pipe_write(pipe: PipeRef, data: Vec<u8>, tf: &mut TrapFrame) {
+    let mut written = 0;
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let result = pipe.get(p).and_then(|end| end.lock().write(&data[written..]));
+        match result {
+            Ok(n) => {
+                written += n;
+                if written < data.len() {
+                    return false;
+                }
+                t.context.xs[0] = written as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(_) if written > 0 => {
+                t.context.xs[0] = written as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => t.context.xs[7] = e as u64,
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Creates a pipe and adds both of its ends to the current process's pipe
+/// descriptors. Bytes written to the write end can be read from the read end
+/// in the same order; a pipe buffers up to `PIPE_SIZE` bytes.
+///
+/// This system call does not take parameter.
+///
+/// In addition to the usual status value, this system call returns two
+/// parameters: the descriptors of the read end and of the write end.
+pub fn sys_pipe(tf: &mut TrapFrame) {
+    let (read, write) = PipeEnd::pair();
+    let (read_fd, write_fd) = SCHEDULER.critical(|scheduler| {
+        let p = scheduler.current_process();
+        (p.add_pipe(read), p.add_pipe(write))
+    });
+    tf.xs[0] = read_fd as u64;
+    tf.xs[1] = write_fd as u64;
+    tf.xs[7] = OsError::Ok as u64;
+}
+
+/// Reads from the read end of a pipe.
+///
+/// This system call takes a pipe descriptor as the first parameter, the
+/// address of the buffer as the second parameter, and the length of the
+/// buffer as the third parameter. The process waits until at least one byte
+/// is buffered.
+///
+/// In addition to the usual status value, this system call returns the number
+/// of bytes read, which is `0` once every write end has been closed and the
+/// pipe is empty.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice.
+/// - `OsError::InvalidArgument`: The descriptor is not the read end of a pipe.
+pub fn sys_pipe_read(fd: usize, va: usize, len: usize, tf: &mut TrapFrame) {
+    pipe_read(PipeRef::Fd(fd), va, len, true, tf);
+}
+
+/// Writes to the write end of a pipe.
+///
+/// This system call takes a pipe descriptor as the first parameter, the
+/// address of the buffer as the second parameter, and the length of the
+/// buffer as the third parameter. At most `PIPE_IO_MAX` bytes are written; the
+/// process waits until there is room for all of them.
+///
+/// In addition to the usual status value, this system call returns the number
+/// of bytes written.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice.
+/// - `OsError::InvalidArgument`: The descriptor is not the write end of a pipe.
+/// - `OsError::IoErrorBrokenPipe`: Every read end has been closed.
+pub fn sys_pipe_write(fd: usize, va: usize, len: usize, tf: &mut TrapFrame) {
+    let pipe = PipeRef::Fd(fd);
+    let result = unsafe { to_user_slice(va, len) }
+        .and_then(|slice| pipe.check(End::Write, tf).map(|_| slice[..min(len, PIPE_IO_MAX)].to_vec()));
+    match result {
+        Ok(data) => pipe_write(pipe, data, tf),
+        Err(e) => tf.xs[7] = e as u64,
+    }
+}
+
+/// Closes a pipe descriptor. The pipe goes away once every process has
+/// closed both of its ends; ends held by other processes stay open.
+///
+/// This system call takes the pipe descriptor as its only parameter.
+///
+/// # Errors
+/// This function returns `OsError::InvalidArgument` if the descriptor is not
+/// open.
+pub fn sys_pipe_close(fd: usize, tf: &mut TrapFrame) {
+    let end = SCHEDULER.critical(|scheduler| {
+        scheduler
+            .current_process()
+            .pipes
+            .get_mut(fd)
+            .and_then(Option::take)
+    });
+    tf.xs[7] = match end {
+        Some(_) => OsError::Ok as u64,
+        None => OsError::InvalidArgument as u64,
+    };
+}
+
+/// Spawns a new process as a child of the current process.
+///
+/// This system call takes six parameters: the address and the length of the
+/// UTF-8 path of the program, the address and the length of a block of
+/// NUL-separated arguments, and the pipe descriptors the child's console input
+/// and output are redirected to. Relative paths are resolved against the
+/// current working directory, which the child inherits.
+///
+/// The child inherits every pipe descriptor of the current process as well.
+/// Its input and output are redirected like the current process's if the
+/// corresponding descriptor is `STDIO_INHERIT`.
+///
+/// In addition to the usual status value, this system call returns the ID of
+/// the new process.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: An address and length pair does not form a valid userspace slice.
+/// - `OsError::InvalidArgument`: The path is not UTF-8 encoded, the arguments are larger than
+///   `ARGS_MAX`, or a stdio descriptor is not a read end for input or a write end for output.
+/// - `OsError::NoEntry`: The program does not exist or is not a file.
+/// - `OsError::NoVmSpace`: The scheduler cannot accept a new process.
+pub fn sys_spawn(
+    path_va: usize,
+    path_len: usize,
+    args_va: usize,
+    args_len: usize,
+    stdin: u64,
+    stdout: u64,
+    tf: &mut TrapFrame,
+) {
+    let result = unsafe { to_user_slice(path_va, path_len) }
+        .and_then(|slice| core::str::from_utf8(slice).map_err(|_| OsError::InvalidArgument))
+        .and_then(|path| {
+            let args = unsafe { to_user_slice(args_va, args_len) }?;
+            let (parent, path, cwd, pipes, stdin, stdout) = SCHEDULER.critical(|scheduler| -> OsResult<_> {
+                let p = scheduler.current_process();
+                let stdin = stdio_end(p, stdin, End::Read)?;
+                let stdout = stdio_end(p, stdout, End::Write)?;
+                Ok((p.id, p.resolve_path(path), p.cwd.clone(), p.pipes.clone(), stdin, stdout))
+            })?;
+
+            let (mut child, mut thread) = Process::load(&path)?;
+            child.set_args(&mut thread, args)?;
+            child.parent = Some(parent);
+            child.cwd = cwd;
+            child.pipes = pipes;
+            child.stdin = stdin;
+            child.stdout = stdout;
+            SCHEDULER.add(child, thread).ok_or(OsError::NoVmSpace)
+        });
+
+    match result {
+        Ok(pid) => {
+            tf.xs[0] = pid;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => {
+            tf.xs[7] = e as u64;
+        }
+    }
+}
+
+/// Returns the pipe end a child's console input (`End::Read`) or output
+/// (`End::Write`) is redirected to: the `end` end with descriptor `fd` in `p`,
+/// or the one `p` itself is redirected to if `fd` is `STDIO_INHERIT`.
+fn stdio_end(p: &Process, fd: u64, end: End) -> OsResult<Option<PipeEnd>> {
+    if fd == STDIO_INHERIT {
+        return Ok(match end {
+            End::Read => p.stdin.clone(),
+            End::Write => p.stdout.clone(),
+        });
+    }
+    match p.pipe(fd as usize)? {
+        pipe if pipe.end() == end => Ok(Some(pipe.clone())),
+        _ => Err(OsError::InvalidArgument),
+    }
+}
+
+/// Waits for a child process to exit.
+///
+/// This system call takes one parameter: the ID of the child process. It
+/// blocks until the child has exited.
+///
+/// In addition to the usual status value, this system call returns the exit
+/// status of the child.
+///
+/// # Errors
+/// This function returns `OsError::NoEntry` if the process is not a child of
+/// the current process or has already been waited for, or if its status was
+/// dropped because `MAX_EXITED` younger children exited unwaited.
+pub fn sys_wait(pid: u64, tf: &mut TrapFrame) {
+    let is_child = SCHEDULER.critical(|scheduler| {
+        let parent = scheduler.current_process().id;
+        scheduler.is_child(parent, pid)
+    });
+    if !is_child {
+        tf.xs[7] = OsError::NoEntry as u64;
+        return;
+    }
+
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        match p.exited.iter().position(|&(id, _)| id == pid) {
+            Some(i) => {
+                let (_, status) = p.exited.remove(i);
+                t.context.xs[0] = status;
+                t.context.xs[7] = OsError::Ok as u64;
+                true
+            }
+            None => false,
+        }
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Creates a thread in the current process.
+///
+/// This system call takes four parameters: the address of the function the
+/// thread runs, the argument passed to it in `x0`, the top of the stack it
+/// runs on, and the address the function returns to, which must issue
+/// `NR_THREAD_EXIT` with the return value. The thread shares the address
+/// space, the descriptors and the signals of the process. Its `TPIDR_EL0` is
+/// free for the program's per-thread data and starts at `0`.
+///
+/// In addition to the usual status value, this system call returns the ID of
+/// the new thread.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The function, the stack or the return address is not a user address.
+/// - `OsError::NoVmSpace`: No further threads can be scheduled.
+pub fn sys_thread_create(entry: u64, arg: u64, sp: u64, restorer: u64, tf: &mut TrapFrame) {
+    let user = |addr: u64| addr >= USER_IMG_BASE as u64;
+    if !user(entry) || !user(sp) || !user(restorer) {
+        tf.xs[7] = OsError::BadAddress as u64;
+        return;
+    }
+
+    let mut thread = Thread::new();
+    thread.context.ttbr0_el = tf.ttbr0_el;
+    thread.context.elr_el = entry;
+    thread.context.sp_el = sp & !0xf;
+    thread.context.xs[0] = arg;
+    thread.context.xs[30] = restorer;
+    let pid = SCHEDULER.critical(|scheduler| scheduler.current_process().id);
+    match SCHEDULER.add_thread(pid, thread) {
+        Some(tid) => {
+            tf.xs[0] = tid;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        None => tf.xs[7] = OsError::NoVmSpace as u64,
+    }
+}
+
+/// Waits for another thread of the current process to exit.
+///
+/// This system call takes one parameter: the ID of the thread. It blocks
+/// until the thread has exited.
+///
+/// In addition to the usual status value, this system call returns the value
+/// the thread exited with.
+///
+/// # Errors
+/// This function returns `OsError::NoEntry` if the thread is the current one,
+/// is not a thread of the current process, or has already been joined.
+pub fn sys_thread_join(tid: u64, tf: &mut TrapFrame) {
+    let joinable = SCHEDULER.critical(|scheduler| {
+        let current = scheduler.current_thread().id;
+        let pid = scheduler.current_process().id;
+        tid != current && scheduler.is_thread_of(pid, tid)
+    });
+    if !joinable {
+        tf.xs[7] = OsError::NoEntry as u64;
+        return;
+    }
+
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        match p.exited_threads.iter().position(|&(id, _)| id == tid) {
+            Some(i) => {
+                let (_, value) = p.exited_threads.remove(i);
+                t.context.xs[0] = value;
+                t.context.xs[7] = OsError::Ok as u64;
+                true
+            }
+            None => false,
+        }
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Ends the current thread.
+///
+/// This system call takes one parameter: the value returned to a thread that
+/// joins this one. If this is the last thread of the process, the process
+/// exits with the value as its status. It does not return.
+pub fn sys_thread_exit(value: u64, tf: &mut TrapFrame) {
+    let _ = SCHEDULER.exit_thread(value, tf);
+    SCHEDULER.switch_to(tf);
+}
+
+/// Sends a signal to a process.
+///
+/// This system call takes two parameters: the ID of the process and the
+/// signal. Signal `0` only checks that the process exists. The signal is
+/// delivered when a thread of the process next returns to user space. If
+/// none of its threads is running or ready, the blocking system call its
+/// first waiting thread is in fails with `OsError::Interrupted`, unless the
+/// signal is blocked or ignored; its other threads keep waiting.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::NoEntry`: There is no process with the ID.
+/// - `OsError::InvalidArgument`: There is no such signal.
+pub fn sys_kill(pid: u64, sig: u64, tf: &mut TrapFrame) {
+    let result = SCHEDULER.critical(|scheduler| {
+        let p = scheduler.process_mut(pid).ok_or(OsError::NoEntry)?;
+        match sig {
+            0 => Ok(()),
+            sig => p.signals.raise(sig),
+        }
+    });
+    tf.xs[7] = match result {
+        Ok(()) => OsError::Ok as u64,
+        Err(e) => e as u64,
+    };
+}
+
+/// Sets the action of a signal in the current process.
+///
+/// This system call takes four parameters: the signal, the handler, the
+/// restorer and a mask of signals. The handler is `SIG_DFL`, `SIG_IGN`, or
+/// the address of a function that takes the signal number. The function runs
+/// with the signals in the mask and the signal itself blocked, and returns to
+/// the restorer, which must issue `NR_SIGRETURN`.
+///
+/// # Errors
+/// This function returns `OsError::InvalidArgument` if there is no such
+/// signal, if its action cannot be changed, or if the handler or the restorer
+/// is not a user address.
+pub fn sys_sigaction(sig: u64, handler: u64, restorer: u64, mask: u64, tf: &mut TrapFrame) {
+    let user = |addr: u64| addr >= USER_IMG_BASE as u64;
+    let action = match handler {
+        SIG_DFL => Ok(Action::Default),
+        SIG_IGN => Ok(Action::Ignore),
+        handler if user(handler) && user(restorer) => Ok(Action::Handler { handler, restorer, mask }),
+        _ => Err(OsError::InvalidArgument),
+    };
+    let result = action.and_then(|action| {
+        SCHEDULER.critical(|scheduler| scheduler.current_process().signals.set_action(sig, action))
+    });
+    tf.xs[7] = match result {
+        Ok(_) => OsError::Ok as u64,
+        Err(e) => e as u64,
+    };
+}
+
+/// Returns from a signal handler to the state the process was in when the
+/// signal was delivered, including every register and the blocked signals.
+///
+/// This system call does not take parameter. On success, it returns nothing,
+/// not even the status value, since the registers are restored. If the state
+/// cannot be restored, the process is sent `SIGSEGV`.
+pub fn sys_sigreturn(tf: &mut TrapFrame) {
+    SCHEDULER.critical(|scheduler| {
+        let (t, p) = scheduler.current();
+        if let Err(e) = signal::restore(t, p, tf) {
+            debug!("process {} failed to return from a signal: {:?}", p.id, e);
+            p.signals.force(SIGSEGV);
+        }
+    });
+}
+
+/// Changes the signals the current process blocks.
+///
+/// This system call takes two parameters: `SIG_BLOCK`, `SIG_UNBLOCK` or
+/// `SIG_SETMASK`, and a mask of signals to add to, remove from or replace the
+/// blocked signals with. `SIGKILL` cannot be blocked.
+///
+/// In addition to the usual status value, this system call returns the
+/// previous mask of blocked signals.
+///
+/// # Errors
+/// This function returns `OsError::InvalidArgument` if the first parameter is
+/// not valid.
+pub fn sys_sigprocmask(how: u64, mask: u64, tf: &mut TrapFrame) {
+    let result = SCHEDULER.critical(|scheduler| scheduler.current_process().signals.set_blocked(how, mask));
+    match result {
+        Ok(old) => {
+            tf.xs[0] = old;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => tf.xs[7] = e as u64,
+    }
+}
+
+/// Returns the physical address of the futex word at `va` in the process that
+/// owns `tf`, which keys its wait queue.
+///
+/// # Errors
+/// Returns `Err(OsError::BadAddress)` if the word is not aligned or not
+/// mapped.
+fn futex_key(va: usize, tf: &TrapFrame) -> OsResult<usize> {
+    if va % 4 != 0 {
+        return Err(OsError::BadAddress);
+    }
+    unsafe { to_user_slice(va, 4) }?;
+    SCHEDULER.critical(|scheduler| {
+        let pa = scheduler.current_process().vmap.translate(VirtualAddr::from(va));
+        pa.map(|pa| pa.as_usize()).ok_or(OsError::BadAddress)
+    })
+}
+
+/// Waits on a futex word.
+///
+/// This system call takes three parameters: the address of an aligned 32-bit
+/// word, the value it is expected to hold, and a timeout in milliseconds,
+/// where `u64::MAX` waits indefinitely. If the word still holds the expected
+/// value, the process waits until `NR_FUTEX_WAKE` is called on the same word,
+/// possibly through another mapping of it. Otherwise it returns at once.
+///
+/// It only returns the usual status value. A successful return does not mean
+/// that the word has changed; callers check it again.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The word is not aligned or not mapped.
+/// - `OsError::IoErrorTimedOut`: The timeout expired before a wake.
+/// - `OsError::Interrupted`: A signal arrived before a wake.
+pub fn sys_futex_wait(va: usize, expected: u32, timeout_ms: u64, tf: &mut TrapFrame) {
+    let key = match futex_key(va, tf) {
+        Ok(key) => key,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    // The word is compared and the waiter queued under the lock `NR_FUTEX_WAKE`
+    // takes, so a wake that follows a store to the word is never missed.
+    let waiter = FUTEXES.critical(|futexes| {
+        let value = unsafe { core::ptr::read_volatile(key as *const u32) };
+        if value == expected {
+            Some(Waiter::new(futexes, key))
+        } else {
+            None
+        }
+    });
+    let waiter = match waiter {
+        Some(waiter) => waiter,
+        None => {
+            tf.xs[7] = OsError::Ok as u64;
+            return;
+        }
+    };
+
+    let wakeup = match timeout_ms {
+        core::u64::MAX => None,
+        ms => Some(Wakeup::after(Duration::from_millis(ms))),
+    };
+    let f = Box::new(move |t: &mut Thread, _: &mut Process| {
+        if waiter.is_woken() {
+            t.context.xs[7] = OsError::Ok as u64;
+        } else if wakeup.as_ref().map_or(false, Wakeup::has_expired) {
+            t.context.xs[7] = OsError::IoErrorTimedOut as u64;
+        } else {
+            return false;
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Wakes processes waiting on a futex word.
+///
+/// This system call takes two parameters: the address of an aligned 32-bit
+/// word and the maximum number of processes to wake. The processes that have
+/// waited longest are woken first.
+///
+/// In addition to the usual status value, this system call returns the number
+/// of processes woken.
+///
+/// # Errors
+/// This function returns `OsError::BadAddress` if the word is not aligned or
+/// not mapped.
+pub fn sys_futex_wake(va: usize, n: usize, tf: &mut TrapFrame) {
+    match futex_key(va, tf) {
+        Ok(key) => {
+            tf.xs[0] = FUTEXES.critical(|futexes| futexes.wake(key, n)) as u64;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => tf.xs[7] = e as u64,
+    }
+}
+
+/// Returns the address a shared region should be mapped at: `None`, for the
+/// kernel to choose, if the caller passed `0`.
+fn region_base(addr: u64) -> Option<VirtualAddr> {
+    match addr {
+        0 => None,
+        addr => Some(VirtualAddr::from(addr)),
+    }
+}
+
+/// Creates a shared-memory region and maps it into the current process.
+///
+/// This system call takes two parameters: the number of pages in the region
+/// and the address to map it at, or `0` for the kernel to choose one. The
+/// pages are zeroed.
+///
+/// In addition to the usual status value, this system call returns three
+/// parameters: the ID other processes map the region by, the address it is
+/// mapped at, and its size in bytes.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::InvalidArgument`: The number of pages is `0` or too large.
+/// - `OsError::NoMemory`: The pages could not be allocated.
+/// - `OsError::BadAddress`: The address is not page aligned or not free.
+/// - `OsError::NoVmSpace`: No free range of addresses is large enough.
+pub fn sys_shm_create(npages: usize, addr: u64, tf: &mut TrapFrame) {
+    let region = match Region::new(npages) {
+        Ok(region) => Arc::new(region),
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+    let size = region.size();
+    let id = SHARED_MEMORY.critical(|regions| regions.insert(&region));
+    let result = SCHEDULER.critical(|scheduler| {
+        scheduler.current_process().map_region(id, region, region_base(addr))
+    });
+    match result {
+        Ok(base) => {
+            tf.xs[0] = id;
+            tf.xs[1] = base.as_u64();
+            tf.xs[2] = size as u64;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => tf.xs[7] = e as u64,
+    }
+}
+
+/// Maps a shared-memory region created by any process into the current
+/// process. The region's pages are shared, not copied.
+///
+/// This system call takes two parameters: the ID of the region and the address
+/// to map it at, or `0` for the kernel to choose one.
+///
+/// In addition to the usual status value, this system call returns two
+/// parameters: the address the region is mapped at and its size in bytes.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::NoEntry`: There is no region with the ID.
+/// - `OsError::BadAddress`: The address is not page aligned or not free.
+/// - `OsError::NoVmSpace`: No free range of addresses is large enough.
+pub fn sys_shm_map(id: u64, addr: u64, tf: &mut TrapFrame) {
+    let region = match SHARED_MEMORY.critical(|regions| regions.get(id)) {
+        Some(region) => region,
+        None => {
+            tf.xs[7] = OsError::NoEntry as u64;
+            return;
+        }
+    };
+    let size = region.size();
+    let result = SCHEDULER.critical(|scheduler| {
+        scheduler.current_process().map_region(id, region, region_base(addr))
+    });
+    match result {
+        Ok(base) => {
+            tf.xs[0] = base.as_u64();
+            tf.xs[1] = size as u64;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => tf.xs[7] = e as u64,
+    }
+}
+
+/// Unmaps a shared-memory region from the current process. The region is
+/// freed once no process maps it; regions still mapped when a process exits
+/// are unmapped then.
+///
+/// This system call takes one parameter: the address the region is mapped
+/// at. It only returns the usual status value.
+///
+/// # Errors
+/// This function returns `OsError::InvalidArgument` if no region is mapped at
+/// the address.
+pub fn sys_shm_unmap(addr: u64, tf: &mut TrapFrame) {
+    let result = SCHEDULER.critical(|scheduler| {
+        scheduler.current_process().unmap_region(VirtualAddr::from(addr))
+    });
+    tf.xs[7] = match result {
+        Ok(()) => OsError::Ok as u64,
+        Err(e) => e as u64,
+    };
+}
+
+/// Creates a port whose messages the current process receives.
+///
+/// This system call does not take parameter.
+///
+/// In addition to the usual status value, this system call returns the ID of
+/// the port, by which any process can send to it.
+pub fn sys_port_create(tf: &mut TrapFrame) {
+    let pid = SCHEDULER.critical(|scheduler| scheduler.current_process().id);
+    tf.xs[0] = PORTS.critical(|ports| ports.create(pid));
+    tf.xs[7] = OsError::Ok as u64;
+}
+
+/// Destroys a port of the current process. Senders waiting on it fail with
+/// `OsError::IoErrorBrokenPipe`. The ports of a process are destroyed when
+/// it exits.
+///
+/// This system call takes one parameter: the ID of the port. It only returns
+/// the usual status value.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::NoEntry`: There is no port with the ID.
+/// - `OsError::NoAccess`: The port belongs to another process.
+pub fn sys_port_destroy(port: u64, tf: &mut TrapFrame) {
+    let pid = SCHEDULER.critical(|scheduler| scheduler.current_process().id);
+    tf.xs[7] = match PORTS.critical(|ports| ports.destroy(port, pid)) {
+        Ok(()) => OsError::Ok as u64,
+        Err(e) => e as u64,
+    };
+}
+
+/// Reads the message at `va` from the current process and stamps it with the
+/// process's ID.
+///
+/// # Errors
+/// Returns `Err(OsError::BadAddress)` if the message is not mapped, or
+/// `Err(OsError::InvalidArgument)` if its payload length is too large.
+fn read_message(va: u64) -> OsResult<Message> {
+    SCHEDULER.critical(|scheduler| {
+        let p = scheduler.current_process();
+        let mut message = Message::default();
+        p.vmap.read_bytes(VirtualAddr::from(va), message.as_bytes_mut())?;
+        if message.len > MSG_PAYLOAD_SIZE as u64 {
+            return Err(OsError::InvalidArgument);
+        }
+        message.sender = p.id;
+        Ok(message)
+    })
+}
+
+/// Sends the message at `va` to `port` and blocks until it is received or,
+/// for a call, answered. The reply to a call is written over the message. A
+/// thread blocked receiving on the port is run right away on this core.
+fn send_message(port: u64, va: u64, call: bool, tf: &mut TrapFrame) {
+    let sent = read_message(va).and_then(|message| PORTS.critical(|ports| ports.send(port, message, call)));
+    let (pending, receiver) = match sent {
+        Ok((token, receiver)) => (Pending::new(token), receiver),
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let status = match pending.outcome() {
+            None => return false,
+            Some(Outcome::Delivered) => OsError::Ok,
+            Some(Outcome::Replied(reply)) => match p.vmap.write_bytes(VirtualAddr::from(va), reply.as_bytes()) {
+                Ok(()) => OsError::Ok,
+                Err(e) => e,
+            },
+            Some(Outcome::Closed) => OsError::IoErrorBrokenPipe,
+        };
+        t.context.xs[7] = status as u64;
+        true
+    });
+    match receiver {
+        Some(thread) => SCHEDULER.handoff(State::Waiting(f), thread, tf),
+        None => SCHEDULER.switch(State::Waiting(f), tf),
+    };
+}
+
+/// Sends a message to a port.
+///
+/// This system call takes two parameters: the ID of the port and the address
+/// of a `Message`. It blocks until a receiver has taken the message, and
+/// only returns the usual status value.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::NoEntry`: There is no port with the ID.
+/// - `OsError::BadAddress`: The message is not mapped.
+/// - `OsError::InvalidArgument`: The payload length of the message is too large.
+/// - `OsError::IoErrorBrokenPipe`: The port was destroyed before the message was received.
+/// - `OsError::Interrupted`: A signal arrived first; the message is withdrawn.
+pub fn sys_port_send(port: u64, va: u64, tf: &mut TrapFrame) {
+    send_message(port, va, false, tf);
+}
+
+/// Sends a message to a port and waits for the reply.
+///
+/// This system call takes two parameters: the ID of the port and the address
+/// of a `Message`, which the reply overwrites. It blocks until a receiver has
+/// replied with `NR_PORT_REPLY`, and only returns the usual status value.
+///
+/// # Errors
+/// This function returns the errors of `sys_port_send()`. It also returns
+/// `OsError::IoErrorBrokenPipe` if the receiver exited without replying.
+pub fn sys_port_call(port: u64, va: u64, tf: &mut TrapFrame) {
+    send_message(port, va, true, tf);
+}
+
+/// Takes the oldest message from `port` into the buffer at `va` of the
+/// process `p`, and sets the results of `NR_PORT_RECEIVE` in `xs`. Returns
+/// `false`, leaving everything as it is, if the port has no message.
+fn receive_message(port: u64, va: u64, p: &mut Process, xs: &mut [u64; 32]) -> bool {
+    // Check the buffer first so that a message is never taken and lost.
+    let mut message = Message::default();
+    if let Err(e) = p.vmap.read_bytes(VirtualAddr::from(va), message.as_bytes_mut()) {
+        xs[7] = e as u64;
+        return true;
+    }
+    match PORTS.critical(|ports| ports.receive(port, p.id)) {
+        Ok(Some((message, token))) => match p.vmap.write_bytes(VirtualAddr::from(va), message.as_bytes()) {
+            Ok(()) => {
+                xs[0] = token;
+                xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => xs[7] = e as u64,
+        },
+        Ok(None) => return false,
+        Err(e) => xs[7] = e as u64,
+    }
+    true
+}
+
+/// Receives a message from a port of the current process.
+///
+/// This system call takes two parameters: the ID of the port and the address
+/// of a `Message` to receive into. It blocks until a message arrives.
+///
+/// In addition to the usual status value, this system call returns the token
+/// to pass to `NR_PORT_REPLY` if the message is a call, or `0` otherwise.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::NoEntry`: There is no port with the ID, or it was destroyed while waiting.
+/// - `OsError::NoAccess`: The port belongs to another process.
+/// - `OsError::BadAddress`: The buffer is not mapped.
+/// - `OsError::Interrupted`: A signal arrived before a message.
+pub fn sys_port_receive(port: u64, va: u64, tf: &mut TrapFrame) {
+    let (done, thread) = SCHEDULER.critical(|scheduler| {
+        let (t, p) = scheduler.current();
+        (receive_message(port, va, p, &mut tf.xs), t.id)
+    });
+    if done {
+        return;
+    }
+
+    let receiver = PORTS.critical(|ports| Receiver::new(ports, port, thread));
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let _ = &receiver;
+        receive_message(port, va, p, &mut t.context.xs)
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Answers a call received from a port.
+///
+/// This system call takes two parameters: the token `NR_PORT_RECEIVE`
+/// returned with the call, and the address of the reply `Message`. The caller
+/// resumes with the reply. It only returns the usual status value.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::NoEntry`: The current process has no unanswered call with the token, or the caller gave up.
+/// - `OsError::BadAddress`: The reply is not mapped.
+/// - `OsError::InvalidArgument`: The payload length of the reply is too large.
+pub fn sys_port_reply(token: u64, va: u64, tf: &mut TrapFrame) {
+    let result = read_message(va).and_then(|reply| PORTS.critical(|ports| ports.reply(token, reply.sender, reply)));
+    tf.xs[7] = match result {
+        Ok(()) => OsError::Ok as u64,
+        Err(e) => e as u64,
+    };
+}
+
+/// Returns the current working directory.
+///
+/// This system call takes the address of the buffer as the first parameter and
+/// the length of the buffer as the second parameter.
+///
+/// In addition to the usual status value, this system call returns the length
+/// of the path written into the buffer.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice.
+/// - `OsError::InvalidArgument`: The buffer is too small to hold the path.
+pub fn sys_getcwd(va: usize, len: usize, tf: &mut TrapFrame) {
+    let result = unsafe { to_user_slice_mut(va, len) }.and_then(|buf| {
+        SCHEDULER.critical(|scheduler| {
+            let cwd = scheduler.current_process().cwd.to_str().ok_or(OsError::InvalidArgument)?;
+            if cwd.len() > buf.len() {
+                return Err(OsError::InvalidArgument);
+            }
+            buf[..cwd.len()].copy_from_slice(cwd.as_bytes());
+            Ok(cwd.len())
+        })
+    });
+
+    match result {
+        Ok(len) => {
+            tf.xs[0] = len as u64;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => {
+            tf.xs[7] = e as u64;
+        }
+    }
+}
+
+/// Changes the current working directory.
+///
+/// This system call takes the address and the length of a UTF-8 path. Relative
+/// paths are resolved against the current working directory.
+///
+/// It only returns the usual status value.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice.
+/// - `OsError::NoEntry`: The path does not exist.
+/// - `OsError::InvalidArgument`: The path is not UTF-8 encoded or is not a directory.
+pub fn sys_chdir(va: usize, len: usize, tf: &mut TrapFrame) {
+    use fat32::traits::{Entry, FileSystem};
+
+    let result = unsafe { to_user_slice(va, len) }
+        .and_then(|slice| core::str::from_utf8(slice).map_err(|_| OsError::InvalidArgument))
+        .and_then(|path| {
+            let path = SCHEDULER.critical(|scheduler| scheduler.current_process().resolve_path(path));
+            if !FILESYSTEM.open(&path)?.is_dir() {
+                return Err(OsError::InvalidArgument);
+            }
+            SCHEDULER.critical(|scheduler| scheduler.current_process().cwd = path);
+            Ok(())
+        });
+
+    match result {
+        Ok(()) => {
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => {
+            tf.xs[7] = e as u64;
+        }
+    }
+}
+
 /// Returns the current process's ID.
 ///
 /// This system call does not take parameter.
@@ -69,17 +1196,34 @@ pub fn sys_write(b: u8, tf: &mut TrapFrame) {
 /// In addition to the usual status value, this system call returns a
 /// parameter: the current process's ID.
 pub fn sys_getpid(tf: &mut TrapFrame) {
-    tf.xs[0] = tf.tpidr_el;
+    tf.xs[0] = SCHEDULER.critical(|scheduler| scheduler.current_process().id);
 }
 
 /// Creates a socket and saves the socket handle in the current process's
 /// socket list.
 ///
-/// This function does neither take any parameter nor return anything,
-/// except the usual return code that indicates successful syscall execution.
+/// This system call does not take parameter.
+///
+/// In addition to the usual status value, this system call returns the
+/// descriptor of the new socket.
+///
+/// # Errors
+/// This function returns `OsError::NoEntry` if networking is not available.
 pub fn sys_sock_create(tf: &mut TrapFrame) {
     // Lab 5 2.D
-    unimplemented!("sys_sock_create")
+    if !ETHERNET.is_initialized() {
+        tf.xs[7] = OsError::NoEntry as u64;
+        return;
+    }
+
+    let handle = ETHERNET.add_socket();
+    let idx = SCHEDULER.critical(|scheduler| {
+        let sockets = &mut scheduler.current_process().sockets;
+        sockets.push((SocketKind::Tcp, handle));
+        sockets.len() - 1
+    });
+    tf.xs[0] = idx as u64;
+    tf.xs[7] = OsError::Ok as u64;
 }
 
 /// Returns the status of a socket.
@@ -94,12 +1238,46 @@ pub fn sys_sock_create(tf: &mut TrapFrame) {
 /// - x2: can_send
 /// - x3: can_recv
 ///
+/// UDP and ICMP sockets never listen; for them, `is_active` reports whether
+/// the socket is bound.
+///
 /// # Errors
 /// This function returns `OsError::InvalidSocket` if a socket that corresponds
 /// to the provided descriptor is not found.
 pub fn sys_sock_status(sock_idx: usize, tf: &mut TrapFrame) {
     // Lab 5 2.D
-    unimplemented!("sys_sock_status")
+    match socket_handle(sock_idx, tf) {
+        Ok((SocketKind::Tcp, handle)) => {
+            ETHERNET.with_socket(handle, |socket| {
+                tf.xs[0] = socket.is_active() as u64;
+                tf.xs[1] = socket.is_listening() as u64;
+                tf.xs[2] = socket.can_send() as u64;
+                tf.xs[3] = socket.can_recv() as u64;
+            });
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Ok((SocketKind::Udp, handle)) => {
+            ETHERNET.with_udp_socket(handle, |socket| {
+                tf.xs[0] = socket.is_open() as u64;
+                tf.xs[1] = false as u64;
+                tf.xs[2] = socket.can_send() as u64;
+                tf.xs[3] = socket.can_recv() as u64;
+            });
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Ok((SocketKind::Icmp, handle)) => {
+            ETHERNET.with_icmp_socket(handle, |socket| {
+                tf.xs[0] = socket.is_open() as u64;
+                tf.xs[1] = false as u64;
+                tf.xs[2] = socket.can_send() as u64;
+                tf.xs[3] = socket.can_recv() as u64;
+            });
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => {
+            tf.xs[7] = e as u64;
+        }
+    }
 }
 
 /// Connects a local ephemeral port to a remote IP endpoint with a socket.
@@ -127,7 +1305,27 @@ pub fn sys_sock_connect(
     tf: &mut TrapFrame,
 ) {
     // Lab 5 2.D
-    unimplemented!("sys_sock_connect")
+    let remote_endpoint = remote_endpoint.into();
+    let result = socket_handle_of(SocketKind::Tcp, sock_idx, tf).and_then(|handle| {
+        ETHERNET.critical(|ethernet| {
+            let port = ethernet.get_ephemeral_port().ok_or(OsError::NoEntry)?;
+            ethernet.mark_port(port);
+            let result = ethernet.get_socket(handle).connect(remote_endpoint, port);
+            if result.is_err() {
+                ethernet.erase_port(port);
+            }
+            result.map_err(socket_error)
+        })
+    });
+
+    match result {
+        Ok(()) => {
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => {
+            tf.xs[7] = e as u64;
+        }
+    }
 }
 
 /// Listens on a local port for an inbound connection.
@@ -141,12 +1339,72 @@ pub fn sys_sock_connect(
 /// This function can return following errors:
 ///
 /// - `OsError::InvalidSocket`: Cannot find a socket that corresponds to the provided descriptor.
-/// - `OsError::IllegalSocketOperation`: `listen()` returned `smoltcp::Error::Illegal`.
+/// - `OsError::IllegalSocketOperation`: The port is in use or `listen()` returned `smoltcp::Error::Illegal`.
 /// - `OsError::BadAddress`: `listen()` returned `smoltcp::Error::Unaddressable`.
 /// - `OsError::Unknown`: All the other errors from calling `listen()`.
 pub fn sys_sock_listen(sock_idx: usize, local_port: u16, tf: &mut TrapFrame) {
     // Lab 5 2.D
-    unimplemented!("sys_sock_listen")
+    let result = socket_handle_of(SocketKind::Tcp, sock_idx, tf).and_then(|handle| {
+        ETHERNET.critical(|ethernet| {
+            ethernet.mark_port(local_port).ok_or(OsError::IllegalSocketOperation)?;
+            let result = ethernet.get_socket(handle).listen(local_port);
+            if result.is_err() {
+                ethernet.erase_port(local_port);
+            }
+            result.map_err(socket_error)
+        })
+    });
+
+    match result {
+        Ok(()) => {
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => {
+            tf.xs[7] = e as u64;
+        }
+    }
+}
+
+/// The maximum number of bytes `sys_sock_send` and `sys_sock_recv` copy in a
+/// single call. This is the size of a socket buffer.
+const SOCKET_IO_MAX: usize = 16384;
+
+/// Returns the kind and the handle of the socket with descriptor `sock_idx` in
+/// the process that owns `tf`.
+///
+/// # Errors
+/// This function returns `Err(OsError::InvalidSocket)` if there is no such
+/// socket.
+fn socket_handle(sock_idx: usize, tf: &TrapFrame) -> OsResult<(SocketKind, SocketHandle)> {
+    SCHEDULER.critical(|scheduler| {
+        scheduler
+            .current_process()
+            .sockets
+            .get(sock_idx)
+            .copied()
+            .ok_or(OsError::InvalidSocket)
+    })
+}
+
+/// Like `socket_handle()`, but requires the socket to be of kind `kind`.
+///
+/// # Errors
+/// This function returns `Err(OsError::IllegalSocketOperation)` if the socket
+/// is of another kind.
+fn socket_handle_of(kind: SocketKind, sock_idx: usize, tf: &TrapFrame) -> OsResult<SocketHandle> {
+    match socket_handle(sock_idx, tf)? {
+        (k, handle) if k == kind => Ok(handle),
+        _ => Err(OsError::IllegalSocketOperation),
+    }
+}
+
+/// Converts an error from smoltcp into an `OsError`.
+fn socket_error(e: smoltcp::Error) -> OsError {
+    match e {
+        smoltcp::Error::Illegal => OsError::IllegalSocketOperation,
+        smoltcp::Error::Unaddressable => OsError::BadAddress,
+        _ => OsError::Unknown,
+    }
 }
 
 /// Returns a slice from a virtual address and a legnth.
@@ -183,7 +1441,8 @@ unsafe fn to_user_slice_mut<'a>(va: usize, len: usize) -> OsResult<&'a mut [u8]>
 /// as the third parameter.
 ///
 /// In addition to the usual status value, this system call returns one
-/// parameter: the number of bytes sent.
+/// parameter: the number of bytes sent. The process waits until the socket
+/// can accept at least one byte.
 ///
 /// # Errors
 /// This function can return following errors:
@@ -194,7 +1453,39 @@ unsafe fn to_user_slice_mut<'a>(va: usize, len: usize) -> OsResult<&'a mut [u8]>
 /// - `OsError::Unknown`: All the other errors from smoltcp.
 pub fn sys_sock_send(sock_idx: usize, va: usize, len: usize, tf: &mut TrapFrame) {
     // Lab 5 2.D
-    unimplemented!("sys_sock_send")
+    let result = socket_handle_of(SocketKind::Tcp, sock_idx, tf).and_then(|handle| {
+        let slice = unsafe { to_user_slice(va, len) }?;
+        Ok((handle, slice[..min(len, SOCKET_IO_MAX)].to_vec()))
+    });
+    let (handle, data) = match result {
+        Ok(pair) => pair,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let f = Box::new(move |t: &mut Thread, _: &mut Process| {
+        let result = ETHERNET.with_socket(handle, |socket| {
+            if !socket.can_send() && socket.may_send() {
+                return None;
+            }
+            Some(socket.send_slice(&data).map_err(socket_error))
+        });
+        match result {
+            None => false,
+            Some(Ok(n)) => {
+                t.context.xs[0] = n as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+                true
+            }
+            Some(Err(e)) => {
+                t.context.xs[7] = e as u64;
+                true
+            }
+        }
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
 }
 
 /// Receives data from a connected socket.
@@ -204,7 +1495,8 @@ pub fn sys_sock_send(sock_idx: usize, va: usize, len: usize, tf: &mut TrapFrame)
 /// as the third parameter.
 ///
 /// In addition to the usual status value, this system call returns one
-/// parameter: the number of bytes read.
+/// parameter: the number of bytes read. The process waits until at least one
+/// byte has arrived; `0` means the remote end closed the connection.
 ///
 /// # Errors
 /// This function can return following errors:
@@ -215,16 +1507,579 @@ pub fn sys_sock_send(sock_idx: usize, va: usize, len: usize, tf: &mut TrapFrame)
 /// - `OsError::Unknown`: All the other errors from smoltcp.
 pub fn sys_sock_recv(sock_idx: usize, va: usize, len: usize, tf: &mut TrapFrame) {
     // Lab 5 2.D
-    unimplemented!("sys_sock_recv")
+    let result = socket_handle_of(SocketKind::Tcp, sock_idx, tf)
+        .and_then(|handle| unsafe { to_user_slice_mut(va, len) }.map(|_| handle));
+    let handle = match result {
+        Ok(handle) => handle,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let len = min(len, SOCKET_IO_MAX);
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let mut buf = vec![0u8; len];
+        let result = ETHERNET.with_socket(handle, |socket| {
+            if socket.can_recv() {
+                Some(socket.recv_slice(&mut buf).map_err(socket_error))
+            } else if !socket.is_active() {
+                Some(Err(OsError::IllegalSocketOperation))
+            } else if !socket.may_recv() {
+                // The remote end closed the connection.
+                Some(Ok(0))
+            } else {
+                None
+            }
+        });
+        let result = match result {
+            None => return false,
+            Some(result) => result,
+        };
+
+        match result.and_then(|n| p.vmap.write_bytes(VirtualAddr::from(va), &buf[..n]).map(|_| n)) {
+            Ok(n) => {
+                t.context.xs[0] = n as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => {
+                t.context.xs[7] = e as u64;
+            }
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
 }
 
-/// Writes a UTF-8 string to the console.
+/// Creates a UDP socket and saves the socket handle in the current process's
+/// socket list.
 ///
-/// This system call takes the address of the buffer as the first parameter and
-/// the length of the buffer as the second parameter.
+/// This system call does not take parameter.
 ///
-/// In addition to the usual status value, this system call returns the length
-/// of the UTF-8 message.
+/// In addition to the usual status value, this system call returns the
+/// descriptor of the new socket.
+///
+/// # Errors
+/// This function returns `OsError::NoEntry` if networking is not available.
+pub fn sys_sock_create_udp(tf: &mut TrapFrame) {
+    if !ETHERNET.is_initialized() {
+        tf.xs[7] = OsError::NoEntry as u64;
+        return;
+    }
+
+    let handle = ETHERNET.add_udp_socket();
+    let idx = SCHEDULER.critical(|scheduler| {
+        let sockets = &mut scheduler.current_process().sockets;
+        sockets.push((SocketKind::Udp, handle));
+        sockets.len() - 1
+    });
+    tf.xs[0] = idx as u64;
+    tf.xs[7] = OsError::Ok as u64;
+}
+
+/// Creates an ICMP socket and saves the socket handle in the current
+/// process's socket list. The socket sends and receives whole ICMP messages
+/// with `sys_sock_sendto()` and `sys_sock_recvfrom()`, and receives the echo
+/// requests and replies that carry the identifier it is bound to. Echo
+/// requests to the kernel are answered by the interface itself.
+///
+/// This system call does not take parameter.
+///
+/// In addition to the usual status value, this system call returns the
+/// descriptor of the new socket.
+///
+/// # Errors
+/// This function returns `OsError::NoEntry` if networking is not available.
+pub fn sys_sock_create_icmp(tf: &mut TrapFrame) {
+    if !ETHERNET.is_initialized() {
+        tf.xs[7] = OsError::NoEntry as u64;
+        return;
+    }
+
+    let handle = ETHERNET.add_icmp_socket();
+    let idx = SCHEDULER.critical(|scheduler| {
+        let sockets = &mut scheduler.current_process().sockets;
+        sockets.push((SocketKind::Icmp, handle));
+        sockets.len() - 1
+    });
+    tf.xs[0] = idx as u64;
+    tf.xs[7] = OsError::Ok as u64;
+}
+
+/// Binds a UDP socket to a local port, or an ICMP socket to an echo
+/// identifier.
+///
+/// This system call takes a socket descriptor as the first parameter and the
+/// local port or the identifier as the second parameter. `0` binds an
+/// ephemeral port or an unused identifier.
+///
+/// In addition to the usual status value, this system call returns the bound
+/// port or identifier.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::NoEntry`: Fails to allocate an ephemeral port.
+/// - `OsError::InvalidSocket`: Cannot find a socket that corresponds to the provided descriptor.
+/// - `OsError::IllegalSocketOperation`: The socket is a TCP socket, the port or identifier is in
+///   use, or the socket is already bound.
+pub fn sys_sock_bind(sock_idx: usize, local_port: u16, tf: &mut TrapFrame) {
+    let result = socket_handle(sock_idx, tf).and_then(|(kind, handle)| {
+        ETHERNET.critical(|ethernet| match kind {
+            SocketKind::Udp => bind_udp(ethernet, handle, local_port),
+            SocketKind::Icmp => ethernet
+                .bind_icmp(handle, local_port)
+                .ok_or(OsError::IllegalSocketOperation),
+            SocketKind::Tcp => Err(OsError::IllegalSocketOperation),
+        })
+    });
+
+    match result {
+        Ok(port) => {
+            tf.xs[0] = port as u64;
+            tf.xs[7] = OsError::Ok as u64;
+        }
+        Err(e) => {
+            tf.xs[7] = e as u64;
+        }
+    }
+}
+
+/// Binds the UDP socket `handle` to `local_port`, or to an ephemeral port if
+/// `local_port` is `0`, and returns the bound port.
+fn bind_udp(ethernet: &mut EthernetDriver, handle: SocketHandle, local_port: u16) -> OsResult<u16> {
+    let port = match local_port {
+        0 => ethernet.get_ephemeral_port().ok_or(OsError::NoEntry)?,
+        port => port,
+    };
+    ethernet.mark_port(port).ok_or(OsError::IllegalSocketOperation)?;
+    if let Err(e) = ethernet.get_udp_socket(handle).bind(port) {
+        ethernet.erase_port(port);
+        return Err(socket_error(e));
+    }
+    Ok(port)
+}
+
+/// Sends a datagram with a UDP socket, or a message with an ICMP socket.
+///
+/// This system call takes a socket descriptor as the first parameter, the
+/// address and the length of the buffer as the second and third parameters,
+/// and the IP of the remote endpoint in big endian and its port as the fourth
+/// and fifth parameters. An unbound socket is bound to an ephemeral port or
+/// an unused identifier first. The port is ignored for ICMP sockets, whose
+/// buffer holds a whole ICMP message; smoltcp fills in its checksum.
+///
+/// In addition to the usual status value, this system call returns the number
+/// of bytes sent, which is always the whole buffer. The process waits until
+/// the socket has room for the datagram.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::InvalidSocket`: Cannot find a socket that corresponds to the provided descriptor.
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice,
+///   or the remote endpoint is unspecified.
+/// - `OsError::InvalidArgument`: The datagram is larger than the socket buffer.
+/// - `OsError::IllegalSocketOperation`: The socket is a TCP socket, or an ICMP message is malformed.
+/// - `OsError::Unknown`: All the other errors from smoltcp.
+pub fn sys_sock_sendto(
+    sock_idx: usize,
+    va: usize,
+    len: usize,
+    remote_endpoint: impl Into<IpEndpoint>,
+    tf: &mut TrapFrame,
+) {
+    let remote_endpoint = remote_endpoint.into();
+    let result = socket_handle(sock_idx, tf).and_then(|(kind, handle)| {
+        let slice = unsafe { to_user_slice(va, len) }?;
+        if slice.len() > SOCKET_IO_MAX {
+            return Err(OsError::InvalidArgument);
+        }
+        ETHERNET.critical(|ethernet| match kind {
+            SocketKind::Udp if !ethernet.get_udp_socket(handle).is_open() => {
+                bind_udp(ethernet, handle, 0).map(|_| ())
+            }
+            SocketKind::Icmp if ethernet.icmp_ident(handle).is_none() => ethernet
+                .bind_icmp(handle, 0)
+                .map(|_| ())
+                .ok_or(OsError::IllegalSocketOperation),
+            SocketKind::Tcp => Err(OsError::IllegalSocketOperation),
+            _ => Ok(()),
+        })?;
+        Ok((kind, handle, slice.to_vec()))
+    });
+    let (kind, handle, data) = match result {
+        Ok(triple) => triple,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let f = Box::new(move |t: &mut Thread, _: &mut Process| {
+        let result = match kind {
+            SocketKind::Icmp => ETHERNET.with_icmp_socket(handle, |socket| {
+                socket.send_slice(&data, remote_endpoint.addr)
+            }),
+            _ => ETHERNET.with_udp_socket(handle, |socket| socket.send_slice(&data, remote_endpoint)),
+        };
+        let result = match result {
+            Err(smoltcp::Error::Exhausted) => return false,
+            Err(smoltcp::Error::Truncated) => Err(OsError::InvalidArgument),
+            Err(e) => Err(socket_error(e)),
+            Ok(()) => Ok(data.len()),
+        };
+        match result {
+            Ok(n) => {
+                t.context.xs[0] = n as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => {
+                t.context.xs[7] = e as u64;
+            }
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Receives a datagram from a UDP socket, or a message from an ICMP socket.
+///
+/// This system call takes a socket descriptor as the first parameter, the
+/// address of the buffer as the second parameter, and the length of the buffer
+/// as the third parameter. The process waits until a datagram arrives. Each
+/// call returns exactly one datagram; bytes that do not fit in the buffer are
+/// discarded.
+///
+/// In addition to the usual status value, this system call returns three
+/// parameters: the number of bytes read, and the IP in big endian and the
+/// port of the sender. The port is `0` for ICMP sockets.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::InvalidSocket`: Cannot find a socket that corresponds to the provided descriptor.
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice.
+/// - `OsError::IllegalSocketOperation`: The socket is not a bound UDP or ICMP socket.
+/// - `OsError::Unknown`: All the other errors from smoltcp.
+pub fn sys_sock_recvfrom(sock_idx: usize, va: usize, len: usize, tf: &mut TrapFrame) {
+    let result = socket_handle(sock_idx, tf).and_then(|(kind, handle)| match kind {
+        SocketKind::Tcp => Err(OsError::IllegalSocketOperation),
+        _ => unsafe { to_user_slice_mut(va, len) }.map(|_| (kind, handle)),
+    });
+    let (kind, handle) = match result {
+        Ok(pair) => pair,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let len = min(len, SOCKET_IO_MAX);
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let mut buf = vec![0u8; len];
+        let result = match kind {
+            SocketKind::Icmp => ETHERNET.with_icmp_socket(handle, |socket| {
+                if socket.can_recv() {
+                    let result = socket.recv_slice(&mut buf).map_err(socket_error);
+                    Some(result.map(|(n, addr)| (n, IpEndpoint::new(addr, 0))))
+                } else if !socket.is_open() {
+                    Some(Err(OsError::IllegalSocketOperation))
+                } else {
+                    None
+                }
+            }),
+            _ => ETHERNET.with_udp_socket(handle, |socket| {
+                if socket.can_recv() {
+                    Some(socket.recv_slice(&mut buf).map_err(socket_error))
+                } else if !socket.is_open() {
+                    Some(Err(OsError::IllegalSocketOperation))
+                } else {
+                    None
+                }
+            }),
+        };
+        let result = match result {
+            None => return false,
+            Some(result) => result,
+        };
+
+        let result = result.and_then(|(n, endpoint)| {
+            p.vmap.write_bytes(VirtualAddr::from(va), &buf[..n]).map(|_| (n, endpoint))
+        });
+        match result {
+            Ok((n, endpoint)) => {
+                let ip = match endpoint.addr {
+                    IpAddress::Ipv4(ip) => u32::from_be_bytes(ip.0),
+                    _ => 0,
+                };
+                t.context.xs[0] = n as u64;
+                t.context.xs[1] = ip as u64;
+                t.context.xs[2] = endpoint.port as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => {
+                t.context.xs[7] = e as u64;
+            }
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// How `sys_resolve()` finds an address.
+enum Lookup {
+    /// The address is known without asking a DNS server.
+    Done(Ipv4Address),
+    /// The query with this ID has been sent.
+    Query(u16),
+}
+
+/// Resolves a host name to an IPv4 address.
+///
+/// This system call takes the address and the length of the name. A dotted
+/// IPv4 address is returned as is. Other names are looked up in the hosts
+/// table at `dns::HOSTS_PATH`, then in the DNS cache, and finally sent as a
+/// query to the DNS servers of the network configuration, in which case the
+/// process waits for the response.
+///
+/// In addition to the usual status value, this system call returns the
+/// address in big endian.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The address and the length pair does not form a valid userspace slice.
+/// - `OsError::IoErrorInvalidData`: The name is not valid UTF-8.
+/// - `OsError::InvalidArgument`: The name is not a valid domain name.
+/// - `OsError::NoEntry`: The name does not exist, or networking or a DNS server is not available.
+/// - `OsError::IoErrorTimedOut`: No DNS server answered in time.
+/// - `OsError::IoError`: Every DNS server that answered failed to resolve the name.
+pub fn sys_resolve(va: usize, len: usize, tf: &mut TrapFrame) {
+    let name = unsafe { to_user_slice(va, len) }.and_then(|slice| {
+        core::str::from_utf8(slice)
+            .map(dns::normalize)
+            .map_err(|_| OsError::IoErrorInvalidData)
+    });
+    let now = Instant::from_millis(current_time().as_millis() as i64);
+    let result = name.and_then(|name| {
+        if let Ok(addr) = name.parse::<Ipv4Address>() {
+            return Ok(Lookup::Done(addr));
+        }
+        if let Some(addr) = dns::lookup_hosts(&name) {
+            return Ok(Lookup::Done(addr));
+        }
+        if !ETHERNET.is_initialized() {
+            return Err(OsError::NoEntry);
+        }
+        ETHERNET.critical(|ethernet| match ethernet.lookup_cached(&name, now) {
+            Some(addr) => Ok(Lookup::Done(addr)),
+            None => ethernet.start_query(&name, now).map(Lookup::Query),
+        })
+    });
+
+    let id = match result {
+        Ok(Lookup::Done(addr)) => {
+            tf.xs[0] = u32::from_be_bytes(addr.0) as u64;
+            tf.xs[7] = OsError::Ok as u64;
+            return;
+        }
+        Ok(Lookup::Query(id)) => id,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+
+    let query = dns::PendingQuery::new(id);
+    let f = Box::new(move |t: &mut Thread, _: &mut Process| {
+        let now = Instant::from_millis(current_time().as_millis() as i64);
+        match query.outcome(now) {
+            None => return false,
+            Some(Ok(addr)) => {
+                t.context.xs[0] = u32::from_be_bytes(addr.0) as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Some(Err(e)) => {
+                t.context.xs[7] = e as u64;
+            }
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// The maximum number of entries `sys_poll` takes.
+const POLL_MAX: usize = 64;
+
+/// Returns the events that have occurred on the socket `handle` of kind
+/// `kind`. A TCP socket hangs up once the remote end has closed the
+/// connection or if it is not connected; reads then return promptly, so it is
+/// readable too.
+fn socket_events(kind: SocketKind, handle: SocketHandle) -> u16 {
+    match kind {
+        SocketKind::Tcp => ETHERNET.with_socket(handle, |socket| {
+            let connecting = match socket.state() {
+                TcpState::Listen | TcpState::SynSent | TcpState::SynReceived => true,
+                _ => false,
+            };
+            let hangup = !socket.may_recv() && !connecting;
+            let mut events = 0;
+            if socket.can_recv() || hangup {
+                events |= POLL_IN;
+            }
+            if socket.can_send() {
+                events |= POLL_OUT;
+            }
+            if hangup {
+                events |= POLL_HUP;
+            }
+            events
+        }),
+        SocketKind::Udp => ETHERNET.with_udp_socket(handle, |socket| {
+            let mut events = 0;
+            if socket.can_recv() {
+                events |= POLL_IN;
+            }
+            if socket.can_send() {
+                events |= POLL_OUT;
+            }
+            events
+        }),
+        SocketKind::Icmp => ETHERNET.with_icmp_socket(handle, |socket| {
+            let mut events = 0;
+            if socket.can_recv() {
+                events |= POLL_IN;
+            }
+            if socket.can_send() {
+                events |= POLL_OUT;
+            }
+            events
+        }),
+    }
+}
+
+/// Returns the events that have occurred on the pipe end `end`. An end hangs
+/// up once every end of the other kind has been closed; a read end is then
+/// readable too, since reads return end-of-file.
+fn pipe_events(end: &PipeEnd) -> u16 {
+    let pipe = end.lock();
+    let mut events = match end.end() {
+        End::Read if pipe.can_read() => POLL_IN,
+        End::Write if pipe.can_write() => POLL_OUT,
+        _ => 0,
+    };
+    if pipe.is_hung_up(end.end()) {
+        events |= POLL_HUP;
+    }
+    events
+}
+
+/// Returns the events that have occurred on the console of the process `p`.
+/// Input comes from the pipe its console reads are redirected to, if any, and
+/// output goes to the pipe its console writes are redirected to, if any.
+fn console_events(p: &Process) -> u16 {
+    let input = match p.stdin {
+        Some(ref end) => pipe_events(end),
+        None if CONSOLE.lock().has_byte() => POLL_IN,
+        None => 0,
+    };
+    let output = match p.stdout {
+        Some(ref end) => pipe_events(end),
+        None => POLL_OUT,
+    };
+    input | output
+}
+
+/// Sets `revents` of every entry of `fds` from the state of its descriptor,
+/// looking sockets and pipes up in the process `p`. Returns the number of
+/// entries with events.
+fn poll_ready(p: &Process, fds: &mut [PollFd]) -> usize {
+    let mut ready = 0;
+    for fd in fds.iter_mut() {
+        let events = match fd.source {
+            POLL_SOURCE_SOCKET => match p.sockets.get(fd.descriptor as usize) {
+                Some(&(kind, handle)) => socket_events(kind, handle),
+                None => POLL_INVALID,
+            },
+            POLL_SOURCE_PIPE => match p.pipe(fd.descriptor as usize) {
+                Ok(end) => pipe_events(end),
+                Err(_) => POLL_INVALID,
+            },
+            POLL_SOURCE_CONSOLE => console_events(p),
+            _ => POLL_INVALID,
+        };
+        fd.revents = events & (fd.events | POLL_INVALID);
+        if fd.revents != 0 {
+            ready += 1;
+        }
+    }
+    ready
+}
+
+/// Waits for events on sockets, pipes and the console.
+///
+/// This system call takes three parameters: the address of an array of
+/// `PollFd`, the number of entries, and a timeout in milliseconds, where
+/// `u64::MAX` waits indefinitely. The process waits until at least one entry
+/// has an event it asks for, or until the timeout expires. The kernel then
+/// writes `revents` of every entry back to the array.
+///
+/// In addition to the usual status value, this system call returns the
+/// number of entries with events, which is `0` if the timeout expired.
+///
+/// # Errors
+/// This function can return following errors:
+///
+/// - `OsError::BadAddress`: The array is not entirely in userspace.
+/// - `OsError::InvalidArgument`: There are more than `POLL_MAX` entries.
+pub fn sys_poll(va: usize, count: usize, timeout_ms: u64, tf: &mut TrapFrame) {
+    if count > POLL_MAX {
+        tf.xs[7] = OsError::InvalidArgument as u64;
+        return;
+    }
+    let size = count * core::mem::size_of::<PollFd>();
+    let user = match unsafe { to_user_slice(va, size) } {
+        Ok(user) => user,
+        Err(e) => {
+            tf.xs[7] = e as u64;
+            return;
+        }
+    };
+    let mut fds: Vec<PollFd> = (0..count)
+        .map(|i| unsafe { (user.as_ptr() as *const PollFd).add(i).read_unaligned() })
+        .collect();
+
+    let wakeup = match timeout_ms {
+        core::u64::MAX => None,
+        ms => Some(Wakeup::after(Duration::from_millis(ms))),
+    };
+    let f = Box::new(move |t: &mut Thread, p: &mut Process| {
+        let ready = poll_ready(p, &mut fds);
+        if ready == 0 && !wakeup.as_ref().map_or(false, Wakeup::has_expired) {
+            return false;
+        }
+
+        let bytes = unsafe { core::slice::from_raw_parts(fds.as_ptr() as *const u8, size) };
+        match p.vmap.write_bytes(VirtualAddr::from(va), bytes) {
+            Ok(()) => {
+                t.context.xs[0] = ready as u64;
+                t.context.xs[7] = OsError::Ok as u64;
+            }
+            Err(e) => t.context.xs[7] = e as u64,
+        }
+        true
+    });
+    SCHEDULER.switch(State::Waiting(f), tf);
+}
+
+/// Writes a UTF-8 string to the console, or to the pipe the process's output
+/// is redirected to.
+///
+/// This system call takes the address and the length of the string.
+///
+/// In addition to the usual status value, this system call returns the number
+/// of bytes written.
 ///
 /// # Errors
 /// This function can return following errors:
@@ -236,6 +2091,7 @@ pub fn sys_write_str(va: usize, len: usize, tf: &mut TrapFrame) {
         .and_then(|slice| core::str::from_utf8(slice).map_err(|_| OsError::InvalidArgument));
 
     match result {
+        Ok(msg) if is_stdout_redirected(tf) => pipe_write(PipeRef::Stdout, msg.as_bytes().to_vec(), tf),
         Ok(msg) => {
             kprint!("{}", msg);
 
@@ -266,6 +2122,141 @@ pub fn handle_syscall(num: u16, tf: &mut TrapFrame) {
         NR_TIME => {
             sys_time(tf);
         },
+        NR_EXIT => {
+            sys_exit(tf.xs[0], tf);
+        },
+        NR_READ_CONSOLE => {
+            sys_read_console(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2], tf);
+        },
+        NR_SPAWN => {
+            let (path_va, path_len) = (tf.xs[0] as usize, tf.xs[1] as usize);
+            let (args_va, args_len) = (tf.xs[2] as usize, tf.xs[3] as usize);
+            sys_spawn(path_va, path_len, args_va, args_len, tf.xs[4], tf.xs[5], tf);
+        },
+        NR_WAIT => {
+            sys_wait(tf.xs[0], tf);
+        },
+        NR_GETCWD => {
+            sys_getcwd(tf.xs[0] as usize, tf.xs[1] as usize, tf);
+        },
+        NR_CHDIR => {
+            sys_chdir(tf.xs[0] as usize, tf.xs[1] as usize, tf);
+        },
+        NR_SOCK_CREATE => {
+            sys_sock_create(tf);
+        },
+        NR_SOCK_STATUS => {
+            sys_sock_status(tf.xs[0] as usize, tf);
+        },
+        NR_SOCK_CONNECT => {
+            let ip = Ipv4Address::from_bytes(&(tf.xs[1] as u32).to_be_bytes());
+            let remote = IpEndpoint::new(IpAddress::Ipv4(ip), tf.xs[2] as u16);
+            sys_sock_connect(tf.xs[0] as usize, remote, tf);
+        },
+        NR_SOCK_LISTEN => {
+            sys_sock_listen(tf.xs[0] as usize, tf.xs[1] as u16, tf);
+        },
+        NR_SOCK_SEND => {
+            sys_sock_send(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2] as usize, tf);
+        },
+        NR_SOCK_RECV => {
+            sys_sock_recv(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2] as usize, tf);
+        },
+        NR_SOCK_CREATE_UDP => {
+            sys_sock_create_udp(tf);
+        },
+        NR_SOCK_BIND => {
+            sys_sock_bind(tf.xs[0] as usize, tf.xs[1] as u16, tf);
+        },
+        NR_SOCK_SENDTO => {
+            let ip = Ipv4Address::from_bytes(&(tf.xs[3] as u32).to_be_bytes());
+            let remote = IpEndpoint::new(IpAddress::Ipv4(ip), tf.xs[4] as u16);
+            sys_sock_sendto(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2] as usize, remote, tf);
+        },
+        NR_SOCK_RECVFROM => {
+            sys_sock_recvfrom(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2] as usize, tf);
+        },
+        NR_SOCK_CREATE_ICMP => {
+            sys_sock_create_icmp(tf);
+        },
+        NR_RESOLVE => {
+            sys_resolve(tf.xs[0] as usize, tf.xs[1] as usize, tf);
+        },
+        NR_POLL => {
+            sys_poll(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2], tf);
+        },
+        NR_PIPE => {
+            sys_pipe(tf);
+        },
+        NR_PIPE_READ => {
+            sys_pipe_read(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2] as usize, tf);
+        },
+        NR_PIPE_WRITE => {
+            sys_pipe_write(tf.xs[0] as usize, tf.xs[1] as usize, tf.xs[2] as usize, tf);
+        },
+        NR_PIPE_CLOSE => {
+            sys_pipe_close(tf.xs[0] as usize, tf);
+        },
+        NR_KILL => {
+            sys_kill(tf.xs[0], tf.xs[1], tf);
+        },
+        NR_SIGACTION => {
+            sys_sigaction(tf.xs[0], tf.xs[1], tf.xs[2], tf.xs[3], tf);
+        },
+        NR_SIGRETURN => {
+            sys_sigreturn(tf);
+        },
+        NR_SIGPROCMASK => {
+            sys_sigprocmask(tf.xs[0], tf.xs[1], tf);
+        },
+        NR_FUTEX_WAIT => {
+            sys_futex_wait(tf.xs[0] as usize, tf.xs[1] as u32, tf.xs[2], tf);
+        },
+        NR_FUTEX_WAKE => {
+            sys_futex_wake(tf.xs[0] as usize, tf.xs[1] as usize, tf);
+        },
+        NR_THREAD_CREATE => {
+            sys_thread_create(tf.xs[0], tf.xs[1], tf.xs[2], tf.xs[3], tf);
+        },
+        NR_THREAD_JOIN => {
+            sys_thread_join(tf.xs[0], tf);
+        },
+        NR_THREAD_EXIT => {
+            sys_thread_exit(tf.xs[0], tf);
+        },
+        NR_SHM_CREATE => {
+            sys_shm_create(tf.xs[0] as usize, tf.xs[1], tf);
+        },
+        NR_SHM_MAP => {
+            sys_shm_map(tf.xs[0], tf.xs[1], tf);
+        },
+        NR_SHM_UNMAP => {
+            sys_shm_unmap(tf.xs[0], tf);
+        },
+        NR_PORT_CREATE => {
+            sys_port_create(tf);
+        },
+        NR_PORT_DESTROY => {
+            sys_port_destroy(tf.xs[0], tf);
+        },
+        NR_PORT_SEND => {
+            sys_port_send(tf.xs[0], tf.xs[1], tf);
+        },
+        NR_PORT_RECEIVE => {
+            sys_port_receive(tf.xs[0], tf.xs[1], tf);
+        },
+        NR_PORT_CALL => {
+            sys_port_call(tf.xs[0], tf.xs[1], tf);
+        },
+        NR_PORT_REPLY => {
+            sys_port_reply(tf.xs[0], tf.xs[1], tf);
+        },
+        NR_IDLE_TIME => {
+            sys_idle_time(tf.xs[0] as usize, tf);
+        },
+        NR_ALARM => {
+            sys_alarm(tf.xs[0], tf.xs[1], tf);
+        },
         _ => (),
     }
 }
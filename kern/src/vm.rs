@@ -7,12 +7,27 @@ pub use self::pagetable::*;
 use aarch64::*;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::mutex::Mutex;
+use crate::mutex::{Once, RwLock};
 use crate::param::{KERNEL_MASK_BITS, USER_MASK_BITS};
 use crate::percore::{is_mmu_ready, set_mmu_ready};
 
+/// Invalidates the translations cached by every core after page table entries
+/// have been removed.
+pub fn flush_tlb() {
+    unsafe {
+        asm!("dsb ishst
+              tlbi vmalle1is
+              dsb ish"
+             ::: "memory"
+             : "volatile");
+    }
+    isb();
+}
+
 pub struct VMManager {
-    kern_pt: Mutex<Option<KernPageTable>>,
+    kern_pt: Once<RwLock<KernPageTable>>,
+    /// The base address of `kern_pt`, for `setup()`: it runs on cores whose
+    /// MMU is still off, which cannot take locks.
     kern_pt_addr: AtomicUsize,
     ready_core_cnt: AtomicUsize,
 }
@@ -24,7 +39,7 @@ impl VMManager {
     /// before the first memory allocation. Failure to do will result in panics.
     pub const fn uninitialized() -> Self {
         VMManager {
-            kern_pt: Mutex::new(None),
+            kern_pt: Once::new(),
             kern_pt_addr: AtomicUsize::new(0),
             ready_core_cnt: AtomicUsize::new(0),
         }
@@ -34,8 +49,8 @@ impl VMManager {
     /// The caller should assure that the method is invoked only once during the kernel
     /// initialization.
     pub unsafe fn initialize(&self) {
-        *self.kern_pt.lock() = Some(KernPageTable::new());
-        let kern_pt_addr = self.kern_pt.lock().as_mut().unwrap().get_baddr();
+        let kern_pt = self.kern_pt.call_once(|| RwLock::new(KernPageTable::new()));
+        let kern_pt_addr = kern_pt.read().get_baddr();
         self.kern_pt_addr.store(kern_pt_addr.as_usize(), Ordering::Relaxed);
     }
 
@@ -118,7 +133,15 @@ impl VMManager {
     }
 
     /// Returns the base address of the kernel page table as `PhysicalAddr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initialize()` has not been called.
     pub fn get_baddr(&self) -> PhysicalAddr {
-        return PhysicalAddr::from(self.kern_pt_addr.load(Ordering::Relaxed));
+        self.kern_pt
+            .get()
+            .expect("VMM uninitialized")
+            .read()
+            .get_baddr()
     }
 }
@@ -14,6 +14,7 @@ use crate::ALLOCATOR;
 use pi::*;
 
 use aarch64::vmsa::*;
+use kernel_api::{OsError, OsResult};
 use shim::const_assert_size;
 
 #[repr(C)]
@@ -24,7 +25,7 @@ impl Page {
     pub const SIZE: usize = PAGE_SIZE;
     pub const ALIGN: usize = PAGE_SIZE;
 
-    fn layout() -> Layout {
+    pub fn layout() -> Layout {
         unsafe { Layout::from_size_align_unchecked(Self::SIZE, Self::ALIGN) }
     }
 }
@@ -70,7 +71,7 @@ impl L3Entry {
         if !self.is_valid() {
             None
         } else {
-            Some(PhysicalAddr::from(self.0.get_value(0xFFFFFFFF0000)))
+            Some(PhysicalAddr::from(self.0.get_masked(RawL3Entry::ADDR)))
         }
     }
 }
@@ -273,6 +274,13 @@ impl UserPageTable {
         }
         let addr = va - VirtualAddr::from(USER_IMG_BASE);
         let ptr = unsafe { ALLOCATOR.alloc(Page::layout()) };
+        self.0.set_entry(addr, UserPageTable::entry(PhysicalAddr::from(ptr as u64)));
+        // kprintln!("{:x} {:x}", ptr as u64, entry.get());
+        unsafe { core::slice::from_raw_parts_mut(ptr, PAGE_SIZE) }
+    }
+
+    /// Returns a valid user read/write L3 entry for the page at `pa`.
+    fn entry(pa: PhysicalAddr) -> RawL3Entry {
         let mut entry = RawL3Entry::new(0);
         entry
             .set_value(EntryValid::Valid, RawL3Entry::VALID)
@@ -281,10 +289,92 @@ impl UserPageTable {
             .set_value(EntryPerm::USER_RW, RawL3Entry::AP)
             .set_value(EntrySh::ISh, RawL3Entry::SH)
             .set_bit(RawL3Entry::AF)
-            .set_masked(ptr as u64, RawL3Entry::ADDR);
-        self.0.set_entry(addr, entry);
-        // kprintln!("{:x} {:x}", ptr as u64, entry.get());
-        unsafe { core::slice::from_raw_parts_mut(ptr, PAGE_SIZE) }
+            .set_masked(pa.as_u64(), RawL3Entry::ADDR);
+        entry
+    }
+
+    /// Sets an L3 entry that translates the page at `va` to the physical page
+    /// at `pa`, which belongs to the caller rather than to this table.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(OsError::BadAddress)` if `va` is lower than
+    /// `USER_IMG_BASE`, is not page aligned or is already mapped.
+    pub fn map(&mut self, va: VirtualAddr, pa: PhysicalAddr) -> OsResult<()> {
+        if va.as_usize() < USER_IMG_BASE || va.as_usize() % PAGE_SIZE != 0 || self.translate(va).is_some() {
+            return Err(OsError::BadAddress);
+        }
+        let addr = va - VirtualAddr::from(USER_IMG_BASE);
+        self.0.set_entry(addr, UserPageTable::entry(pa));
+        Ok(())
+    }
+
+    /// Clears the L3 entry of the page at `va` and returns the physical page
+    /// it translated to, or `None` if the page was not mapped. The caller
+    /// flushes the TLB once it has unmapped its pages.
+    pub fn unmap(&mut self, va: VirtualAddr) -> Option<PhysicalAddr> {
+        let page = VirtualAddr::from(va.as_usize() & PAGE_MASK);
+        let pa = self.translate(page)?;
+        self.0.set_entry(page - VirtualAddr::from(USER_IMG_BASE), RawL3Entry::new(0));
+        Some(pa)
+    }
+
+    /// Translates the given user virtual address into the physical address it
+    /// is mapped to.
+    ///
+    /// Returns `None` if the address is lower than `USER_IMG_BASE` or if the
+    /// page containing it has not been allocated.
+    pub fn translate(&self, va: VirtualAddr) -> Option<PhysicalAddr> {
+        if va.as_usize() < USER_IMG_BASE {
+            return None;
+        }
+        let addr = va - VirtualAddr::from(USER_IMG_BASE);
+        let (l2idx, l3idx) = PageTable::locate(addr);
+        let page = self.0.l3[l2idx].entries[l3idx].get_page_addr()?;
+        Some(page + PhysicalAddr::from(addr.as_usize() & (PAGE_SIZE - 1)))
+    }
+
+    /// Copies `buf` into user memory starting at `va`. The copy goes through
+    /// this page table, so it works even when the table is not the one
+    /// currently installed in `TTBR1`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(OsError::BadAddress)` if any byte of the destination is
+    /// not mapped. Bytes before the first unmapped page may have been written.
+    pub fn write_bytes(&self, va: VirtualAddr, buf: &[u8]) -> OsResult<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            let addr = va.as_usize().checked_add(done).ok_or(OsError::BadAddress)?;
+            let pa = self.translate(addr.into()).ok_or(OsError::BadAddress)?;
+            let n = core::cmp::min(buf.len() - done, PAGE_SIZE - (addr & (PAGE_SIZE - 1)));
+            unsafe {
+                core::ptr::copy_nonoverlapping(buf[done..].as_ptr(), pa.as_usize() as *mut u8, n);
+            }
+            done += n;
+        }
+        Ok(())
+    }
+
+    /// Copies user memory starting at `va` into `buf`, translating through
+    /// this page table.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(OsError::BadAddress)` if any byte of the source is not
+    /// mapped.
+    pub fn read_bytes(&self, va: VirtualAddr, buf: &mut [u8]) -> OsResult<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            let addr = va.as_usize().checked_add(done).ok_or(OsError::BadAddress)?;
+            let pa = self.translate(addr.into()).ok_or(OsError::BadAddress)?;
+            let n = core::cmp::min(buf.len() - done, PAGE_SIZE - (addr & (PAGE_SIZE - 1)));
+            unsafe {
+                core::ptr::copy_nonoverlapping(pa.as_usize() as *const u8, buf[done..].as_mut_ptr(), n);
+            }
+            done += n;
+        }
+        Ok(())
     }
 }
 
@@ -267,3 +267,16 @@ defreg!(CNTP_CVAL_EL0);
 
 // (ref: D7.5.12 Counter-timer Physical Timer TimerValue Register)
 defreg!(CNTP_TVAL_EL0, [TVAL[31 - 00],]);
+
+// (ref: D7.3.20 Monitor Debug System Control Register)
+defreg!(
+    MDSCR_EL1,
+    [
+        MDE[15 - 15], // Monitor debug events
+        KDE[13 - 13], // Local (kernel) debug enable
+        SS[00 - 00],  // Software step control
+    ]
+);
+
+// (ref: D7.3.23 OS Lock Access Register)
+defreg!(OSLAR_EL1, [OSLK[00 - 00],]);
@@ -474,3 +474,53 @@ fn shuffle_test() {
     let hash = hash_files_recursive_from(vfat, "/");
     assert_hash_eq!("mock 1 file hashes", hash, hash_for!("files-1"));
 }
+
+/// A disk whose contents stay readable after it is handed to a cache.
+struct SharedDisk(Arc<Mutex<Vec<u8>>>);
+
+impl BlockDevice for SharedDisk {
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let disk = self.0.lock().unwrap();
+        let start = n as usize * 512;
+        let len = ::std::cmp::min(512, buf.len());
+        buf[..len].copy_from_slice(&disk[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        let mut disk = self.0.lock().unwrap();
+        let start = n as usize * 512;
+        let len = ::std::cmp::min(512, buf.len());
+        disk[start..start + len].copy_from_slice(&buf[..len]);
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_cache_flush() {
+    use crate::vfat::cache::{CachedPartition, Partition};
+
+    let disk = Arc::new(Mutex::new(vec![0u8; 512 * 8]));
+    let partition = Partition {
+        start: 2,
+        num_sectors: 3,
+        sector_size: 1024,
+    };
+    let mut cache = CachedPartition::new(SharedDisk(disk.clone()), partition);
+
+    cache.get(0).unwrap();
+    cache.get_mut(1).unwrap()[510..514].copy_from_slice(b"abcd");
+    assert!(disk.lock().unwrap().iter().all(|&b| b == 0));
+
+    cache.flush().unwrap();
+    {
+        let disk = disk.lock().unwrap();
+        assert_eq!(&disk[512 * 4 + 510..512 * 4 + 514], b"abcd");
+        assert_eq!(disk.iter().filter(|&&b| b != 0).count(), 4);
+    }
+
+    // Clean sectors are not written again.
+    disk.lock().unwrap()[512 * 4 + 510] = 0;
+    cache.flush().unwrap();
+    assert_eq!(disk.lock().unwrap()[512 * 4 + 510], 0);
+}
@@ -136,6 +136,25 @@ impl CachedPartition {
         self.cache.insert(sector, ce);
         return Ok(&(self.cache.get(&sector).unwrap().data[..]));
     }
+
+    /// Writes every dirty sector back to the disk and marks it clean.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is an error writing a sector to the disk.
+    /// Sectors that have not been written yet stay dirty.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let factor = self.factor();
+        let device_sector_size = self.device.sector_size() as usize;
+        for (&sector, cached) in self.cache.iter_mut().filter(|(_, cached)| cached.dirty) {
+            let start = self.partition.start + sector * factor;
+            for (i, data) in cached.data.chunks(device_sector_size).enumerate() {
+                self.device.write_sector(start + i as u64, data)?;
+            }
+            cached.dirty = false;
+        }
+        Ok(())
+    }
 }
 
 // FIXME: Implement `BlockDevice` for `CacheDevice`. The `read_sector` and
@@ -131,6 +131,11 @@ impl<HANDLE: VFatHandle> VFat<HANDLE> {
         Ok(size)
     }
 
+    /// Writes the sectors modified in the cache back to the disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.device.flush()
+    }
+
     //  * A method to return a reference to a `FatEntry` for a cluster where the
     //    reference points directly into a cached sector.
     //
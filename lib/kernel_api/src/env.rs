@@ -0,0 +1,36 @@
+/// The argument block passed by the kernel: NUL-separated UTF-8 strings.
+static mut ARGS: &'static [u8] = &[];
+
+/// Records the argument block the kernel placed on top of the stack. `ptr`
+/// and `len` are the values of `x0` and `x1` at the program's entry point.
+///
+/// This must be called once, before `main`, and never again.
+pub unsafe fn init(ptr: *const u8, len: usize) {
+    if !ptr.is_null() {
+        ARGS = core::slice::from_raw_parts(ptr, len);
+    }
+}
+
+/// Returns an iterator over the arguments this program was spawned with. The
+/// first argument is conventionally the program's own name.
+pub fn args() -> impl Iterator<Item = &'static str> {
+    unsafe { ARGS }
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .filter_map(|arg| core::str::from_utf8(arg).ok())
+}
+
+/// Formats `n` in decimal into `buf`, for passing numbers to spawned programs
+/// as arguments.
+pub fn format_u64(mut n: u64, buf: &mut [u8; 20]) -> &str {
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    core::str::from_utf8(&buf[i..]).unwrap()
+}
@@ -5,6 +5,10 @@ use core::fmt;
 
 use shim::io;
 
+#[cfg(feature = "user-space")]
+pub mod env;
+#[cfg(feature = "user-space")]
+pub mod sync;
 #[cfg(feature = "user-space")]
 pub mod syscall;
 
@@ -22,12 +26,14 @@ pub enum OsError {
     BadAddress = 50,
     FileExists = 60,
     InvalidArgument = 70,
+    Interrupted = 80,
 
     IoError = 101,
     IoErrorEof = 102,
     IoErrorInvalidData = 103,
     IoErrorInvalidInput = 104,
     IoErrorTimedOut = 105,
+    IoErrorBrokenPipe = 106,
 
     InvalidSocket = 200,
     IllegalSocketOperation = 201,
@@ -45,11 +51,14 @@ impl core::convert::From<u64> for OsError {
             50 => OsError::BadAddress,
             60 => OsError::FileExists,
             70 => OsError::InvalidArgument,
+            80 => OsError::Interrupted,
 
             101 => OsError::IoError,
             102 => OsError::IoErrorEof,
             103 => OsError::IoErrorInvalidData,
             104 => OsError::IoErrorInvalidInput,
+            105 => OsError::IoErrorTimedOut,
+            106 => OsError::IoErrorBrokenPipe,
 
             200 => OsError::InvalidSocket,
             201 => OsError::IllegalSocketOperation,
@@ -66,7 +75,9 @@ impl core::convert::From<io::Error> for OsError {
             io::ErrorKind::InvalidData => OsError::IoErrorInvalidData,
             io::ErrorKind::InvalidInput => OsError::IoErrorInvalidInput,
             io::ErrorKind::TimedOut => OsError::IoErrorTimedOut,
+            io::ErrorKind::BrokenPipe => OsError::IoErrorBrokenPipe,
             io::ErrorKind::NotFound => OsError::NoEntry,
+            io::ErrorKind::Interrupted => OsError::Interrupted,
             _ => OsError::IoError,
         }
     }
@@ -78,6 +89,23 @@ pub const NR_EXIT: usize = 3;
 pub const NR_WRITE: usize = 4;
 pub const NR_GETPID: usize = 5;
 pub const NR_WRITE_STR: usize = 6;
+pub const NR_READ_CONSOLE: usize = 7;
+pub const NR_SPAWN: usize = 8;
+pub const NR_WAIT: usize = 9;
+pub const NR_GETCWD: usize = 10;
+pub const NR_CHDIR: usize = 11;
+
+/// Flag for `NR_READ_CONSOLE`: return `IoErrorTimedOut` instead of blocking
+/// when no byte has been received.
+pub const READ_CONSOLE_NONBLOCK: u64 = 1;
+
+/// Stdio descriptor for `NR_SPAWN`: the child's console input or output is
+/// redirected like the parent's.
+pub const STDIO_INHERIT: u64 = core::u64::MAX;
+
+/// The largest block of NUL-terminated arguments `NR_SPAWN` accepts, in
+/// bytes: one page of the kernel, at the top of the child's stack.
+pub const ARGS_MAX: usize = 64 * 1024;
 
 #[derive(Clone, Copy, Debug)]
 pub struct SocketDescriptor(u64);
@@ -88,6 +116,16 @@ impl SocketDescriptor {
     }
 }
 
+/// A descriptor of one end of a pipe.
+#[derive(Clone, Copy, Debug)]
+pub struct PipeDescriptor(u64);
+
+impl PipeDescriptor {
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug)]
 pub struct SocketStatus {
     pub is_active: bool,
@@ -127,3 +165,210 @@ pub const NR_SOCK_CONNECT: usize = 22;
 pub const NR_SOCK_LISTEN: usize = 23;
 pub const NR_SOCK_SEND: usize = 24;
 pub const NR_SOCK_RECV: usize = 25;
+pub const NR_SOCK_CREATE_UDP: usize = 26;
+pub const NR_SOCK_BIND: usize = 27;
+pub const NR_SOCK_SENDTO: usize = 28;
+pub const NR_SOCK_RECVFROM: usize = 29;
+pub const NR_RESOLVE: usize = 30;
+pub const NR_POLL: usize = 31;
+pub const NR_SOCK_CREATE_ICMP: usize = 32;
+pub const NR_PIPE: usize = 33;
+pub const NR_PIPE_READ: usize = 34;
+pub const NR_PIPE_WRITE: usize = 35;
+pub const NR_PIPE_CLOSE: usize = 36;
+pub const NR_KILL: usize = 37;
+pub const NR_SIGACTION: usize = 38;
+pub const NR_SIGRETURN: usize = 39;
+pub const NR_SIGPROCMASK: usize = 40;
+pub const NR_FUTEX_WAIT: usize = 41;
+pub const NR_FUTEX_WAKE: usize = 42;
+pub const NR_THREAD_CREATE: usize = 43;
+pub const NR_THREAD_JOIN: usize = 44;
+pub const NR_THREAD_EXIT: usize = 45;
+pub const NR_SHM_CREATE: usize = 46;
+pub const NR_SHM_MAP: usize = 47;
+pub const NR_SHM_UNMAP: usize = 48;
+pub const NR_PORT_CREATE: usize = 49;
+pub const NR_PORT_DESTROY: usize = 50;
+pub const NR_PORT_SEND: usize = 51;
+pub const NR_PORT_RECEIVE: usize = 52;
+pub const NR_PORT_CALL: usize = 53;
+pub const NR_PORT_REPLY: usize = 54;
+pub const NR_IDLE_TIME: usize = 55;
+pub const NR_ALARM: usize = 56;
+
+/// The number of signals. Signal `0` does not exist; sets of signals are
+/// masks with bit `n` standing for signal `n`.
+pub const NSIG: u64 = 32;
+
+pub const SIGHUP: u64 = 1;
+pub const SIGINT: u64 = 2;
+pub const SIGQUIT: u64 = 3;
+pub const SIGILL: u64 = 4;
+pub const SIGTRAP: u64 = 5;
+pub const SIGABRT: u64 = 6;
+pub const SIGBUS: u64 = 7;
+pub const SIGFPE: u64 = 8;
+/// Terminates the process; cannot be caught, ignored or blocked.
+pub const SIGKILL: u64 = 9;
+pub const SIGUSR1: u64 = 10;
+pub const SIGSEGV: u64 = 11;
+pub const SIGUSR2: u64 = 12;
+pub const SIGPIPE: u64 = 13;
+pub const SIGALRM: u64 = 14;
+pub const SIGTERM: u64 = 15;
+/// Sent to the parent when a child exits; ignored by default.
+pub const SIGCHLD: u64 = 17;
+
+/// Handler for `NR_SIGACTION`: the signal's default action, which terminates
+/// the process for every signal but `SIGCHLD`.
+pub const SIG_DFL: u64 = 0;
+/// Handler for `NR_SIGACTION`: the signal is discarded.
+pub const SIG_IGN: u64 = 1;
+
+/// `how` for `NR_SIGPROCMASK`: adds the signals to the blocked set.
+pub const SIG_BLOCK: u64 = 0;
+/// `how` for `NR_SIGPROCMASK`: removes the signals from the blocked set.
+pub const SIG_UNBLOCK: u64 = 1;
+/// `how` for `NR_SIGPROCMASK`: replaces the blocked set.
+pub const SIG_SETMASK: u64 = 2;
+
+/// The exit status reported by `NR_WAIT` for a process terminated by the
+/// signal `sig`.
+pub const fn signal_status(sig: u64) -> u64 {
+    128 + sig
+}
+
+/// `PollFd::source` of a socket; `PollFd::descriptor` is the socket
+/// descriptor.
+pub const POLL_SOURCE_SOCKET: u32 = 0;
+/// `PollFd::source` of the console, or of the pipes the process's console
+/// reads and writes are redirected to; `PollFd::descriptor` is ignored.
+pub const POLL_SOURCE_CONSOLE: u32 = 1;
+/// `PollFd::source` of a pipe end; `PollFd::descriptor` is the pipe
+/// descriptor.
+pub const POLL_SOURCE_PIPE: u32 = 2;
+
+/// Data can be read without blocking.
+pub const POLL_IN: u16 = 1;
+/// Data can be written without blocking.
+pub const POLL_OUT: u16 = 2;
+/// The other end has closed the connection, or there is no connection.
+pub const POLL_HUP: u16 = 4;
+/// Set in `PollFd::revents`, whatever `PollFd::events` asks for, if the
+/// descriptor does not exist.
+pub const POLL_INVALID: u16 = 8;
+
+/// An entry of the array `NR_POLL` takes: a descriptor, the events to wait
+/// for, and the events that occurred, which the kernel fills in.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PollFd {
+    pub descriptor: u64,
+    pub source: u32,
+    pub events: u16,
+    pub revents: u16,
+}
+
+impl PollFd {
+    /// Waits for `events` on the socket `descriptor`.
+    pub fn socket(descriptor: SocketDescriptor, events: u16) -> PollFd {
+        PollFd {
+            descriptor: descriptor.raw(),
+            source: POLL_SOURCE_SOCKET,
+            events,
+            revents: 0,
+        }
+    }
+
+    /// Waits for `events` on the pipe end `descriptor`.
+    pub fn pipe(descriptor: PipeDescriptor, events: u16) -> PollFd {
+        PollFd {
+            descriptor: descriptor.raw(),
+            source: POLL_SOURCE_PIPE,
+            events,
+            revents: 0,
+        }
+    }
+
+    /// Waits for `events` on the console.
+    pub fn console(events: u16) -> PollFd {
+        PollFd {
+            descriptor: 0,
+            source: POLL_SOURCE_CONSOLE,
+            events,
+            revents: 0,
+        }
+    }
+}
+
+/// The largest payload a `Message` carries.
+pub const MSG_PAYLOAD_SIZE: usize = 104;
+
+/// A message sent through a port: a label whose meaning the protocol on the
+/// port defines, and a small payload.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Message {
+    pub label: u64,
+    /// The ID of the process that sent the message, filled in by the kernel
+    pub sender: u64,
+    /// The number of bytes of `payload` in use
+    pub len: u64,
+    pub payload: [u8; MSG_PAYLOAD_SIZE],
+}
+
+impl Message {
+    /// Returns a message with `label` and no payload.
+    pub fn new(label: u64) -> Message {
+        Message {
+            label,
+            sender: 0,
+            len: 0,
+            payload: [0; MSG_PAYLOAD_SIZE],
+        }
+    }
+
+    /// Returns a message with `label` carrying `payload`, or `None` if the
+    /// payload is longer than `MSG_PAYLOAD_SIZE`.
+    pub fn with_payload(label: u64, payload: &[u8]) -> Option<Message> {
+        if payload.len() > MSG_PAYLOAD_SIZE {
+            return None;
+        }
+        let mut message = Message::new(label);
+        message.payload[..payload.len()].copy_from_slice(payload);
+        message.len = payload.len() as u64;
+        Some(message)
+    }
+
+    /// Returns the payload in use.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..core::cmp::min(self.len as usize, MSG_PAYLOAD_SIZE)]
+    }
+
+    /// Returns the message as the bytes the kernel copies.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, core::mem::size_of::<Self>()) }
+    }
+
+    /// Returns the message as the bytes the kernel copies into.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as *mut u8, core::mem::size_of::<Self>()) }
+    }
+}
+
+impl Default for Message {
+    fn default() -> Message {
+        Message::new(0)
+    }
+}
+
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Message")
+            .field("label", &self.label)
+            .field("sender", &self.sender)
+            .field("payload", &self.payload())
+            .finish()
+    }
+}
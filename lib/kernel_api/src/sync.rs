@@ -0,0 +1,145 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+
+use crate::syscall::{futex_wait, futex_wake};
+use crate::*;
+
+/// The mutex is free.
+const UNLOCKED: u32 = 0;
+/// The mutex is held and nobody waits for it.
+const LOCKED: u32 = 1;
+/// The mutex is held and other processes may be waiting for it.
+const CONTENDED: u32 = 2;
+
+/// A mutual exclusion lock for memory shared between threads or processes.
+/// Taking a free lock and releasing one nobody waits for do not enter the
+/// kernel; waiters sleep in `NR_FUTEX_WAIT` instead of spinning.
+pub struct Mutex<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+pub struct MutexGuard<'a, T: 'a> {
+    lock: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(val: T) -> Mutex<T> {
+        Mutex {
+            state: AtomicU32::new(UNLOCKED),
+            data: UnsafeCell::new(val),
+        }
+    }
+
+    /// Takes the lock if it is free.
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        match self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed) {
+            Ok(_) => Some(MutexGuard { lock: self }),
+            Err(_) => None,
+        }
+    }
+
+    /// Takes the lock, waiting in the kernel while another process holds it.
+    pub fn lock(&self) -> MutexGuard<T> {
+        if let Some(guard) = self.try_lock() {
+            return guard;
+        }
+        // Whoever releases the lock next must wake a waiter, since this
+        // process may be one.
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            let _ = futex_wait(&self.state, CONTENDED, None);
+        }
+        MutexGuard { lock: self }
+    }
+
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            let _ = futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => f.debug_struct("Mutex").field("data", &&*guard).finish(),
+            None => f.debug_struct("Mutex").field("data", &"<locked>").finish(),
+        }
+    }
+}
+
+impl<'a, T: 'a> Deref for MutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: 'a> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: 'a> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.unlock()
+    }
+}
+
+/// A condition variable for use with `Mutex`.
+///
+/// The variable is a sequence number that every notification bumps. A waiter
+/// reads it before releasing the mutex and sleeps only while it is unchanged,
+/// so a notification between the two is not lost. Wakeups may be spurious;
+/// callers check their condition in a loop.
+#[derive(Debug)]
+pub struct Condvar {
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Condvar {
+        Condvar { seq: AtomicU32::new(0) }
+    }
+
+    /// Releases the mutex held by `guard`, waits for a notification, and
+    /// takes the mutex again.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let (guard, _) = self.wait_inner(guard, None);
+        guard
+    }
+
+    /// Like `wait()`, but gives up after `timeout`. The second value returned
+    /// is `true` if the timeout expired.
+    pub fn wait_timeout<'a, T>(&self, guard: MutexGuard<'a, T>, timeout: Duration) -> (MutexGuard<'a, T>, bool) {
+        self.wait_inner(guard, Some(timeout))
+    }
+
+    fn wait_inner<'a, T>(&self, guard: MutexGuard<'a, T>, timeout: Option<Duration>) -> (MutexGuard<'a, T>, bool) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        let lock = guard.lock;
+        drop(guard);
+        let timed_out = futex_wait(&self.seq, seq, timeout) == Err(OsError::IoErrorTimedOut);
+        (lock.lock(), timed_out)
+    }
+
+    /// Wakes one process waiting on this variable.
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        let _ = futex_wake(&self.seq, 1);
+    }
+
+    /// Wakes every process waiting on this variable.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        let _ = futex_wake(&self.seq, core::usize::MAX);
+    }
+}
@@ -1,5 +1,6 @@
 use core::fmt;
 use core::fmt::Write;
+use core::sync::atomic::AtomicU32;
 use core::time::Duration;
 
 use crate::*;
@@ -51,9 +52,87 @@ pub fn time() -> Duration {
     return Duration::from_micros(time);
 }
 
-pub fn exit() -> ! {
+/// Returns how long core `core` has spent idle since boot and how many times
+/// it has gone idle.
+///
+/// Returns `Err(OsError::InvalidArgument)` if there is no such core.
+pub fn idle_time(core: usize) -> OsResult<(Duration, u64)> {
+    let mut ecode: u64;
+    let mut time: u64;
+    let mut count: u64;
+
+    unsafe {
+        asm!("mov x0, $3
+              svc $4
+              mov $0, x0
+              mov $1, x1
+              mov $2, x7"
+             : "=r"(time), "=r"(count), "=r"(ecode)
+             : "r"(core), "i"(NR_IDLE_TIME)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, (Duration::from_micros(time), count))
+}
+
+/// Returns `t` in microseconds, rounded up so that a non-zero duration does
+/// not become zero.
+fn ceil_micros(t: Duration) -> u64 {
+    let us = (t.as_nanos() + 999) / 1000;
+    if us > core::u64::MAX as u128 {
+        core::u64::MAX
+    } else {
+        us as u64
+    }
+}
+
+/// Arms the interval timer of this process to raise `SIGALRM` after `value`
+/// and, unless `interval` is zero, every `interval` after that. A zero
+/// `value` disarms the timer. Periods are kept by the kernel, so a periodic
+/// timer does not drift.
+///
+/// Returns the time left on the timer this one replaces and its interval,
+/// both zero if it was disarmed, or `Err(OsError::InvalidArgument)` if
+/// `interval` is non-zero but too short.
+pub fn setitimer(value: Duration, interval: Duration) -> OsResult<(Duration, Duration)> {
+    let mut ecode: u64;
+    let mut left: u64;
+    let mut period: u64;
+
+    unsafe {
+        asm!("mov x0, $3
+              mov x1, $4
+              svc $5
+              mov $0, x0
+              mov $1, x1
+              mov $2, x7"
+             : "=r"(left), "=r"(period), "=r"(ecode)
+             : "r"(ceil_micros(value)), "r"(ceil_micros(interval)), "i"(NR_ALARM)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, (Duration::from_micros(left), Duration::from_micros(period)))
+}
+
+/// Raises `SIGALRM` once after `after`, replacing the interval timer of this
+/// process, or disarms the timer if `after` is zero. Returns the time that
+/// was left on the replaced timer.
+pub fn alarm(after: Duration) -> OsResult<Duration> {
+    setitimer(after, Duration::from_secs(0)).map(|(left, _)| left)
+}
+
+pub fn exit(status: u64) -> ! {
+    unsafe {
+        asm!("mov x0, $0
+              svc $1"
+             :
+             : "r"(status), "i"(NR_EXIT)
+             : "x0"
+             : "volatile");
+    }
     loop {}
-    // unimplemented!("exit()")
 }
 
 pub fn write(b: u8) {
@@ -68,22 +147,164 @@ pub fn write(b: u8) {
 }
 
 pub fn write_str(msg: &str) {
-    let mut len = 0;
-    let mut buffer: [u8; 100] = [' ' as u8; 100];
-    for b in msg.bytes() {
-        buffer[len] = b;
-        len += 1;
-    }
-
     unsafe {
         asm!("mov x0, $0
               mov x1, $1
               svc $2"
              :
-             : "r"(buffer.as_ptr()), "r"(len), "i"(NR_WRITE_STR)
+             : "r"(msg.as_ptr()), "r"(msg.len()), "i"(NR_WRITE_STR)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+}
+
+/// Reads bytes from the console into `buf`, blocking until at least one byte
+/// is available. Returns the number of bytes read. If this process's input is
+/// redirected to a pipe, `0` is returned once the pipe reaches end-of-file.
+pub fn read_console(buf: &mut [u8]) -> OsResult<usize> {
+    do_read_console(buf, 0)
+}
+
+/// Reads bytes from the console into `buf` without blocking. Returns
+/// `Err(OsError::IoErrorTimedOut)` if no byte has been received.
+pub fn try_read_console(buf: &mut [u8]) -> OsResult<usize> {
+    do_read_console(buf, READ_CONSOLE_NONBLOCK)
+}
+
+fn do_read_console(buf: &mut [u8], flags: u64) -> OsResult<usize> {
+    let mut ecode: u64;
+    let mut len: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              svc $5
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(len), "=r"(ecode)
+             : "r"(buf.as_mut_ptr()), "r"(buf.len()), "r"(flags), "i"(NR_READ_CONSOLE)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, len)
+}
+
+/// Spawns the program at `path` as a child of the current process and returns
+/// the child's ID. `args` are passed to the child, which can read them with
+/// `env::args()`. Relative paths are resolved against the current working
+/// directory.
+pub fn spawn(path: &str, args: &[&str]) -> OsResult<u64> {
+    spawn_with_stdio(path, args, None, None)
+}
+
+/// Spawns a child like `spawn()`, with its console input read from the pipe
+/// end `stdin` and its console output written to the pipe end `stdout`. The
+/// child inherits this process's input or output where they are `None`. The
+/// child inherits every pipe descriptor of this process in any case.
+///
+/// Returns `Err(OsError::InvalidArgument)` if the arguments, each followed by
+/// a NUL byte, take more than `ARGS_MAX` bytes.
+pub fn spawn_with_stdio(
+    path: &str,
+    args: &[&str],
+    stdin: Option<PipeDescriptor>,
+    stdout: Option<PipeDescriptor>,
+) -> OsResult<u64> {
+    let mut block = [0u8; ARGS_MAX];
+    let mut block_len = 0;
+    for arg in args {
+        let end = block_len + arg.len() + 1;
+        if end > block.len() {
+            return Err(OsError::InvalidArgument);
+        }
+        block[block_len..end - 1].copy_from_slice(arg.as_bytes());
+        block_len = end;
+    }
+
+    let stdin = stdin.map_or(STDIO_INHERIT, |fd| fd.raw());
+    let stdout = stdout.map_or(STDIO_INHERIT, |fd| fd.raw());
+    let mut ecode: u64;
+    let mut pid: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              mov x3, $5
+              mov x4, $6
+              mov x5, $7
+              svc $8
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(pid), "=r"(ecode)
+             : "r"(path.as_ptr()), "r"(path.len()), "r"(block.as_ptr()), "r"(block_len),
+               "r"(stdin), "r"(stdout), "i"(NR_SPAWN)
+             : "x0", "x1", "x2", "x3", "x4", "x5", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, pid)
+}
+
+/// Blocks until the child process `pid` exits and returns its exit status.
+pub fn wait(pid: u64) -> OsResult<u64> {
+    let mut ecode: u64;
+    let mut status: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              svc $3
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(status), "=r"(ecode)
+             : "r"(pid), "i"(NR_WAIT)
              : "x0", "x7"
              : "volatile");
     }
+
+    err_or!(ecode, status)
+}
+
+/// Writes the current working directory into `buf` and returns it as a
+/// string slice of `buf`.
+pub fn getcwd(buf: &mut [u8]) -> OsResult<&str> {
+    let mut ecode: u64;
+    let mut len: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              svc $4
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(len), "=r"(ecode)
+             : "r"(buf.as_mut_ptr()), "r"(buf.len()), "i"(NR_GETCWD)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    let len = err_or!(ecode, len)?;
+    core::str::from_utf8(&buf[..len]).map_err(|_| OsError::IoErrorInvalidData)
+}
+
+/// Changes the current working directory to `path`.
+pub fn chdir(path: &str) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              svc $3
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(path.as_ptr()), "r"(path.len()), "i"(NR_CHDIR)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
 }
 
 pub fn getpid() -> u64 {
@@ -99,34 +320,823 @@ pub fn getpid() -> u64 {
     return pid;
 }
 
-pub fn sock_create() -> SocketDescriptor {
+pub fn sock_create() -> OsResult<SocketDescriptor> {
     // Lab 5 2.D
-    unimplemented!("sock_create")
+    let mut ecode: u64;
+    let mut sock_idx: u64;
+
+    unsafe {
+        asm!("svc $2
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(sock_idx), "=r"(ecode)
+             : "i"(NR_SOCK_CREATE)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, SocketDescriptor(sock_idx))
 }
 
 pub fn sock_status(descriptor: SocketDescriptor) -> OsResult<SocketStatus> {
     // Lab 5 2.D
-    unimplemented!("sock_status")
+    let mut ecode: u64;
+    let mut is_active: u64;
+    let mut is_listening: u64;
+    let mut can_send: u64;
+    let mut can_recv: u64;
+
+    unsafe {
+        asm!("mov x0, $5
+              svc $6
+              mov $0, x0
+              mov $1, x1
+              mov $2, x2
+              mov $3, x3
+              mov $4, x7"
+             : "=r"(is_active), "=r"(is_listening), "=r"(can_send), "=r"(can_recv), "=r"(ecode)
+             : "r"(descriptor.raw()), "i"(NR_SOCK_STATUS)
+             : "x0", "x1", "x2", "x3", "x7"
+             : "volatile");
+    }
+
+    err_or!(
+        ecode,
+        SocketStatus {
+            is_active: is_active != 0,
+            is_listening: is_listening != 0,
+            can_send: can_send != 0,
+            can_recv: can_recv != 0,
+        }
+    )
 }
 
 pub fn sock_connect(descriptor: SocketDescriptor, addr: IpAddr) -> OsResult<()> {
     // Lab 5 2.D
-    unimplemented!("sock_connect")
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              mov x2, $3
+              svc $4
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(addr.ip as u64), "r"(addr.port as u64),
+               "i"(NR_SOCK_CONNECT)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
 }
 
 pub fn sock_listen(descriptor: SocketDescriptor, local_port: u16) -> OsResult<()> {
     // Lab 5 2.D
-    unimplemented!("sock_listen")
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              svc $3
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(local_port as u64), "i"(NR_SOCK_LISTEN)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
 }
 
 pub fn sock_send(descriptor: SocketDescriptor, buf: &[u8]) -> OsResult<usize> {
     // Lab 5 2.D
-    unimplemented!("sock_send")
+    let mut ecode: u64;
+    let mut len: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              svc $5
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(len), "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(buf.as_ptr()), "r"(buf.len()), "i"(NR_SOCK_SEND)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, len)
 }
 
 pub fn sock_recv(descriptor: SocketDescriptor, buf: &mut [u8]) -> OsResult<usize> {
     // Lab 5 2.D
-    unimplemented!("sock_recv")
+    let mut ecode: u64;
+    let mut len: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              svc $5
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(len), "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(buf.as_mut_ptr()), "r"(buf.len()), "i"(NR_SOCK_RECV)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, len)
+}
+
+pub fn sock_create_udp() -> OsResult<SocketDescriptor> {
+    let mut ecode: u64;
+    let mut sock_idx: u64;
+
+    unsafe {
+        asm!("svc $2
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(sock_idx), "=r"(ecode)
+             : "i"(NR_SOCK_CREATE_UDP)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, SocketDescriptor(sock_idx))
+}
+
+/// Binds a UDP socket to `local_port`, or to an ephemeral port if it is `0`,
+/// and returns the bound port. For an ICMP socket, `local_port` is the echo
+/// identifier, and `0` picks an unused one.
+pub fn sock_bind(descriptor: SocketDescriptor, local_port: u16) -> OsResult<u16> {
+    let mut ecode: u64;
+    let mut port: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              svc $4
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(port), "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(local_port as u64), "i"(NR_SOCK_BIND)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, port as u16)
+}
+
+/// Sends `buf` as a single datagram to `addr`. An unbound socket is bound to
+/// an ephemeral port first.
+pub fn sock_sendto(descriptor: SocketDescriptor, buf: &[u8], addr: IpAddr) -> OsResult<usize> {
+    let mut ecode: u64;
+    let mut len: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              mov x3, $5
+              mov x4, $6
+              svc $7
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(len), "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(buf.as_ptr()), "r"(buf.len()),
+               "r"(addr.ip as u64), "r"(addr.port as u64), "i"(NR_SOCK_SENDTO)
+             : "x0", "x1", "x2", "x3", "x4", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, len)
+}
+
+/// Receives a single datagram into `buf` and returns its length and sender.
+/// Bytes of the datagram that do not fit in `buf` are discarded.
+pub fn sock_recvfrom(descriptor: SocketDescriptor, buf: &mut [u8]) -> OsResult<(usize, IpAddr)> {
+    let mut ecode: u64;
+    let mut len: usize;
+    let mut ip: u64;
+    let mut port: u64;
+
+    unsafe {
+        asm!("mov x0, $4
+              mov x1, $5
+              mov x2, $6
+              svc $7
+              mov $0, x0
+              mov $1, x1
+              mov $2, x2
+              mov $3, x7"
+             : "=r"(len), "=r"(ip), "=r"(port), "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(buf.as_mut_ptr()), "r"(buf.len()),
+               "i"(NR_SOCK_RECVFROM)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    let addr = IpAddr {
+        ip: ip as u32,
+        port: port as u16,
+    };
+    err_or!(ecode, (len, addr))
+}
+
+/// Creates an ICMP socket. Bind it to an echo identifier with `sock_bind()`
+/// to receive the echo replies that carry the identifier; `sock_sendto()` and
+/// `sock_recvfrom()` then send and receive whole ICMP messages, and ignore the
+/// port.
+pub fn sock_create_icmp() -> OsResult<SocketDescriptor> {
+    let mut ecode: u64;
+    let mut sock_idx: u64;
+
+    unsafe {
+        asm!("svc $2
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(sock_idx), "=r"(ecode)
+             : "i"(NR_SOCK_CREATE_ICMP)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, SocketDescriptor(sock_idx))
+}
+
+/// Creates a pipe and returns its read end and its write end. Bytes written to
+/// the write end are read from the read end in the same order.
+pub fn pipe() -> OsResult<(PipeDescriptor, PipeDescriptor)> {
+    let mut ecode: u64;
+    let mut read: u64;
+    let mut write: u64;
+
+    unsafe {
+        asm!("svc $3
+              mov $0, x0
+              mov $1, x1
+              mov $2, x7"
+             : "=r"(read), "=r"(write), "=r"(ecode)
+             : "i"(NR_PIPE)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, (PipeDescriptor(read), PipeDescriptor(write)))
+}
+
+/// Reads from the read end of a pipe into `buf`, blocking until at least one
+/// byte is available. Returns the number of bytes read, which is `0` once
+/// every write end has been closed and the pipe is empty.
+pub fn pipe_read(descriptor: PipeDescriptor, buf: &mut [u8]) -> OsResult<usize> {
+    let mut ecode: u64;
+    let mut len: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              svc $5
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(len), "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(buf.as_mut_ptr()), "r"(buf.len()), "i"(NR_PIPE_READ)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, len)
+}
+
+/// Writes `buf` to the write end of a pipe, blocking until there is room for
+/// it. Returns the number of bytes written, which is less than `buf.len()` if
+/// `buf` is very large or the last reader closes the pipe. Fails with
+/// `OsError::IoErrorBrokenPipe` if there is no reader.
+pub fn pipe_write(descriptor: PipeDescriptor, buf: &[u8]) -> OsResult<usize> {
+    let mut ecode: u64;
+    let mut len: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              svc $5
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(len), "=r"(ecode)
+             : "r"(descriptor.raw()), "r"(buf.as_ptr()), "r"(buf.len()), "i"(NR_PIPE_WRITE)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, len)
+}
+
+/// Closes a pipe descriptor. Children that inherited the descriptor keep
+/// their copies open.
+pub fn pipe_close(descriptor: PipeDescriptor) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              svc $2
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(descriptor.raw()), "i"(NR_PIPE_CLOSE)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Sends the signal `sig` to the process `pid`. Signal `0` only checks that
+/// the process exists.
+pub fn kill(pid: u64, sig: u64) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              svc $3
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(pid), "r"(sig), "i"(NR_KILL)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// What a process does when it receives a signal.
+#[derive(Clone, Copy)]
+pub enum SigAction {
+    /// The default action, which terminates the process for every signal but
+    /// `SIGCHLD`
+    Default,
+    /// The signal is discarded.
+    Ignore,
+    /// The function is called with the signal number. The interrupted code
+    /// resumes when it returns.
+    Handler(extern "C" fn(u64)),
+}
+
+/// Where signal handlers return to: restores the state the process was in
+/// when the signal arrived.
+extern "C" fn sigreturn() -> ! {
+    unsafe {
+        asm!("svc $0"
+             :
+             : "i"(NR_SIGRETURN)
+             :
+             : "volatile");
+    }
+    loop {}
+}
+
+/// Sets the action of the signal `sig`. The signals in `mask` are blocked,
+/// in addition to `sig` itself, while a handler runs.
+pub fn sigaction(sig: u64, action: SigAction, mask: u64) -> OsResult<()> {
+    let handler = match action {
+        SigAction::Default => SIG_DFL,
+        SigAction::Ignore => SIG_IGN,
+        SigAction::Handler(f) => f as usize as u64,
+    };
+    let restorer = sigreturn as usize as u64;
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              mov x2, $3
+              mov x3, $4
+              svc $5
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(sig), "r"(handler), "r"(restorer), "r"(mask), "i"(NR_SIGACTION)
+             : "x0", "x1", "x2", "x3", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Changes the blocked signals: `how` is `SIG_BLOCK`, `SIG_UNBLOCK` or
+/// `SIG_SETMASK`, and `mask` has bit `n` set for signal `n`. Returns the
+/// previous mask. Signals sent while blocked are delivered once unblocked.
+pub fn sigprocmask(how: u64, mask: u64) -> OsResult<u64> {
+    let mut ecode: u64;
+    let mut old: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              svc $4
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(old), "=r"(ecode)
+             : "r"(how), "r"(mask), "i"(NR_SIGPROCMASK)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, old)
+}
+
+/// Waits on the futex word `futex` if it still holds `expected`, until
+/// `futex_wake()` is called on it or until `timeout` expires. Returns at once
+/// if the word holds another value. A successful return does not mean that
+/// the word has changed, so callers check it again.
+///
+/// Returns `Err(OsError::IoErrorTimedOut)` if the timeout expires, or
+/// `Err(OsError::Interrupted)` if a signal arrives first.
+pub fn futex_wait(futex: &AtomicU32, expected: u32, timeout: Option<Duration>) -> OsResult<()> {
+    let timeout_ms = match timeout {
+        Some(t) => core::cmp::min(t.as_millis(), core::u64::MAX as u128 - 1) as u64,
+        None => core::u64::MAX,
+    };
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              mov x2, $3
+              svc $4
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(futex as *const AtomicU32), "r"(expected as u64), "r"(timeout_ms), "i"(NR_FUTEX_WAIT)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Wakes up to `n` processes waiting on the futex word `futex`, oldest first,
+/// and returns how many were woken.
+pub fn futex_wake(futex: &AtomicU32, n: usize) -> OsResult<usize> {
+    let mut ecode: u64;
+    let mut woken: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              svc $4
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(woken), "=r"(ecode)
+             : "r"(futex as *const AtomicU32), "r"(n), "i"(NR_FUTEX_WAKE)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, woken)
+}
+
+/// Where thread entry functions return to: ends the thread with the value
+/// the entry function returned, which is still in `x0`.
+extern "C" fn thread_return() -> ! {
+    unsafe {
+        asm!("svc $0"
+             :
+             : "i"(NR_THREAD_EXIT)
+             :
+             : "volatile");
+    }
+    loop {}
+}
+
+/// Starts a thread of this process that runs `entry(arg)` on `stack`, and
+/// returns its ID. The thread ends when `entry` returns or calls
+/// `thread_exit()`; `thread_join()` collects the value it ends with.
+///
+/// Returns `Err(OsError::NoVmSpace)` if no further threads can be scheduled.
+pub fn thread_create(entry: extern "C" fn(u64) -> u64, arg: u64, stack: &'static mut [u8]) -> OsResult<u64> {
+    let sp = stack.as_mut_ptr() as u64 + stack.len() as u64;
+    let restorer = thread_return as usize as u64;
+    let mut ecode: u64;
+    let mut tid: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              mov x3, $5
+              svc $6
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(tid), "=r"(ecode)
+             : "r"(entry as usize as u64), "r"(arg), "r"(sp), "r"(restorer), "i"(NR_THREAD_CREATE)
+             : "x0", "x1", "x2", "x3", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, tid)
+}
+
+/// Waits for the thread `tid` of this process to end and returns the value
+/// it ended with. A thread can be joined once.
+///
+/// Returns `Err(OsError::NoEntry)` if `tid` is the calling thread or no
+/// unjoined thread of this process.
+pub fn thread_join(tid: u64) -> OsResult<u64> {
+    let mut ecode: u64;
+    let mut value: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              svc $3
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(value), "=r"(ecode)
+             : "r"(tid), "i"(NR_THREAD_JOIN)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, value)
+}
+
+/// Ends the calling thread with `value`. Ending the last thread of a process
+/// exits the process with `value` as its status.
+pub fn thread_exit(value: u64) -> ! {
+    unsafe {
+        asm!("mov x0, $0
+              svc $1"
+             :
+             : "r"(value), "i"(NR_THREAD_EXIT)
+             : "x0"
+             : "volatile");
+    }
+    loop {}
+}
+
+/// Returns the thread pointer of the calling thread. Every thread has its own,
+/// which starts out as `0`, for thread-local storage.
+pub fn thread_pointer() -> u64 {
+    let ptr: u64;
+    unsafe {
+        asm!("mrs $0, TPIDR_EL0"
+             : "=r"(ptr)
+             :
+             :
+             : "volatile");
+    }
+    ptr
+}
+
+/// Sets the thread pointer of the calling thread to `ptr`.
+pub fn set_thread_pointer(ptr: u64) {
+    unsafe {
+        asm!("msr TPIDR_EL0, $0"
+             :
+             : "r"(ptr)
+             :
+             : "volatile");
+    }
+}
+
+/// Creates a shared-memory region of `pages` zeroed pages and maps it at
+/// `addr`, or where the kernel chooses if `addr` is `None`. Returns the ID
+/// other processes pass to `shm_map()` and the mapped memory.
+///
+/// The region is freed once every process that maps it has unmapped it or
+/// exited.
+pub fn shm_create(pages: usize, addr: Option<usize>) -> OsResult<(u64, &'static mut [u8])> {
+    let mut ecode: u64;
+    let mut id: u64;
+    let mut base: usize;
+    let mut size: usize;
+
+    unsafe {
+        asm!("mov x0, $4
+              mov x1, $5
+              svc $6
+              mov $0, x0
+              mov $1, x1
+              mov $2, x2
+              mov $3, x7"
+             : "=r"(id), "=r"(base), "=r"(size), "=r"(ecode)
+             : "r"(pages), "r"(addr.unwrap_or(0)), "i"(NR_SHM_CREATE)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, (id, unsafe { core::slice::from_raw_parts_mut(base as *mut u8, size) }))
+}
+
+/// Maps the shared-memory region `id` at `addr`, or where the kernel chooses
+/// if `addr` is `None`, and returns the mapped memory.
+///
+/// Returns `Err(OsError::NoEntry)` if there is no region with ID `id`.
+pub fn shm_map(id: u64, addr: Option<usize>) -> OsResult<&'static mut [u8]> {
+    let mut ecode: u64;
+    let mut base: usize;
+    let mut size: usize;
+
+    unsafe {
+        asm!("mov x0, $3
+              mov x1, $4
+              svc $5
+              mov $0, x0
+              mov $1, x1
+              mov $2, x7"
+             : "=r"(base), "=r"(size), "=r"(ecode)
+             : "r"(id), "r"(addr.unwrap_or(0)), "i"(NR_SHM_MAP)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, unsafe { core::slice::from_raw_parts_mut(base as *mut u8, size) })
+}
+
+/// Unmaps a shared-memory region returned by `shm_create()` or `shm_map()`.
+pub fn shm_unmap(region: &'static mut [u8]) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              svc $2
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(region.as_mut_ptr()), "i"(NR_SHM_UNMAP)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Creates a port whose messages this process receives, and returns its ID.
+pub fn port_create() -> OsResult<u64> {
+    let mut ecode: u64;
+    let mut port: u64;
+
+    unsafe {
+        asm!("svc $2
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(port), "=r"(ecode)
+             : "i"(NR_PORT_CREATE)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, port)
+}
+
+/// Destroys the port `port` of this process.
+pub fn port_destroy(port: u64) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              svc $2
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(port), "i"(NR_PORT_DESTROY)
+             : "x0", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Sends `message` to the port `port` and waits until it has been received.
+pub fn port_send(port: u64, message: &Message) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              svc $3
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(port), "r"(message as *const Message), "i"(NR_PORT_SEND)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Waits for a message on the port `port` of this process and receives it
+/// into `message`. Returns the token to reply to if the message is a call,
+/// or `0` otherwise.
+pub fn port_receive(port: u64, message: &mut Message) -> OsResult<u64> {
+    let mut ecode: u64;
+    let mut token: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              svc $4
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(token), "=r"(ecode)
+             : "r"(port), "r"(message as *mut Message), "i"(NR_PORT_RECEIVE)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, token)
+}
+
+/// Sends `message` to the port `port` and waits for the reply, which
+/// replaces `message`.
+///
+/// Returns `Err(OsError::IoErrorBrokenPipe)` if the receiver exited or
+/// destroyed the port without replying.
+pub fn port_call(port: u64, message: &mut Message) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              svc $3
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(port), "r"(message as *mut Message), "i"(NR_PORT_CALL)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Answers the call `port_receive()` returned `token` for with `reply`.
+pub fn port_reply(token: u64, reply: &Message) -> OsResult<()> {
+    let mut ecode: u64;
+
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              svc $3
+              mov $0, x7"
+             : "=r"(ecode)
+             : "r"(token), "r"(reply as *const Message), "i"(NR_PORT_REPLY)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Resolves the host name `name` to an IPv4 address, using the kernel's hosts
+/// table, DNS cache and DNS servers. The port of the returned address is `0`.
+pub fn resolve(name: &str) -> OsResult<IpAddr> {
+    let mut ecode: u64;
+    let mut ip: u64;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              svc $4
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(ip), "=r"(ecode)
+             : "r"(name.as_ptr()), "r"(name.len()), "i"(NR_RESOLVE)
+             : "x0", "x1", "x7"
+             : "volatile");
+    }
+
+    let addr = IpAddr {
+        ip: ip as u32,
+        port: 0,
+    };
+    err_or!(ecode, addr)
+}
+
+/// Waits until at least one entry of `fds` is ready for the events it asks
+/// for, or until `timeout` passes; `None` waits indefinitely. Sets `revents`
+/// of every entry and returns the number of entries with events, which is
+/// `0` if the timeout expired.
+pub fn poll(fds: &mut [PollFd], timeout: Option<Duration>) -> OsResult<usize> {
+    let timeout_ms = match timeout {
+        Some(t) => core::cmp::min(t.as_millis(), core::u64::MAX as u128 - 1) as u64,
+        None => core::u64::MAX,
+    };
+    let mut ecode: u64;
+    let mut ready: usize;
+
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              mov x2, $4
+              svc $5
+              mov $0, x0
+              mov $1, x7"
+             : "=r"(ready), "=r"(ecode)
+             : "r"(fds.as_mut_ptr()), "r"(fds.len()), "r"(timeout_ms), "i"(NR_POLL)
+             : "x0", "x1", "x2", "x7"
+             : "volatile");
+    }
+
+    err_or!(ecode, ready)
 }
 
 struct Console;
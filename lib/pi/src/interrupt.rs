@@ -10,19 +10,22 @@ pub enum Interrupt {
     Timer1 = 1,
     Timer3 = 3,
     Usb = 9,
+    /// The auxiliary peripherals, which include the mini UART.
+    Aux = 29,
     Gpio0 = 49,
     Gpio1 = 50,
     Gpio2 = 51,
     Gpio3 = 52,
+    /// The PL011 UART. The mini UART raises `Aux` instead.
     Uart = 57,
 }
 
 impl Interrupt {
-    pub const MAX: usize = 8;
+    pub const MAX: usize = 9;
 
     pub fn iter() -> impl Iterator<Item = Interrupt> {
         use Interrupt::*;
-        [Timer1, Timer3, Usb, Gpio0, Gpio1, Gpio2, Gpio3, Uart]
+        [Timer1, Timer3, Usb, Aux, Gpio0, Gpio1, Gpio2, Gpio3, Uart]
             .iter()
             .map(|int| *int)
     }
@@ -35,6 +38,7 @@ impl From<usize> for Interrupt {
             1 => Timer1,
             3 => Timer3,
             9 => Usb,
+            29 => Aux,
             49 => Gpio0,
             50 => Gpio1,
             51 => Gpio2,
@@ -90,6 +94,7 @@ impl Controller {
     /// Enables the interrupt as FIQ interrupt
     pub fn enable_fiq(&mut self, int: Interrupt) {
         // Lab 5 2.B
-        unimplemented!("enable_fiq")
+        // Bit 7 enables FIQ; bits 0-6 select the source.
+        self.registers.fiq_control = 0x80 | int as u32;
     }
 }
@@ -11,5 +11,6 @@ pub mod common;
 pub mod gpio;
 pub mod interrupt;
 pub mod local_interrupt;
+pub mod pl011;
 pub mod timer;
 pub mod uart;
@@ -0,0 +1,411 @@
+use core::fmt;
+use core::time::Duration;
+
+use shim::const_assert_size;
+use shim::io;
+
+use volatile::prelude::*;
+use volatile::{ReadVolatile, Reserved, Volatile, WriteVolatile};
+
+use crate::common::IO_BASE;
+use crate::gpio::{Function, Gpio};
+use crate::timer;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the `UART0` (PL011) registers.
+const UART0_REG_BASE: usize = IO_BASE + 0x201000;
+
+/// The frequency of the UART reference clock, `UARTCLK`. The firmware sets it
+/// with `init_uart_clock` in `config.txt`; this is its default on the Pi 3.
+pub const UART_CLOCK: u32 = 48_000_000;
+
+/// Enum representing bit fields of the `DR` register.
+#[repr(u32)]
+enum DrStatus {
+    FramingError = 1 << 8,
+    ParityError = 1 << 9,
+    BreakError = 1 << 10,
+    OverrunError = 1 << 11,
+}
+
+/// Enum representing bit fields of the `FR` register.
+#[repr(u32)]
+enum FrStatus {
+    Busy = 1 << 3,
+    RxFifoEmpty = 1 << 4,
+    TxFifoFull = 1 << 5,
+}
+
+/// Enum representing bit fields of the `LCRH` register.
+#[repr(u32)]
+enum LcrhStatus {
+    ParityEnable = 1 << 1,
+    EvenParity = 1 << 2,
+    TwoStopBits = 1 << 3,
+    FifoEnable = 1 << 4,
+}
+
+/// Enum representing bit fields of the `CR` register.
+#[repr(u32)]
+enum CrStatus {
+    UartEnable = 1 << 0,
+    TxEnable = 1 << 8,
+    RxEnable = 1 << 9,
+    RtsEnable = 1 << 14,
+    CtsEnable = 1 << 15,
+}
+
+/// Enum representing bit fields of the `IMSC`, `RIS`, `MIS` and `ICR`
+/// registers.
+#[repr(u32)]
+enum IntStatus {
+    Rx = 1 << 4,
+    Tx = 1 << 5,
+    RxTimeout = 1 << 6,
+    All = 0x7ff,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    DR: Volatile<u32>,
+    RSRECR: Volatile<u32>,
+    _r0: [Reserved<u32>; 4],
+    FR: ReadVolatile<u32>,
+    _r1: Reserved<u32>,
+    ILPR: Volatile<u32>,
+    IBRD: Volatile<u32>,
+    FBRD: Volatile<u32>,
+    LCRH: Volatile<u32>,
+    CR: Volatile<u32>,
+    IFLS: Volatile<u32>,
+    IMSC: Volatile<u32>,
+    RIS: ReadVolatile<u32>,
+    MIS: ReadVolatile<u32>,
+    ICR: WriteVolatile<u32>,
+    DMACR: Volatile<u32>,
+}
+
+const_assert_size!(Registers, 0x7E20104C - 0x7E201000);
+
+/// The number of data bits in a frame.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DataBits {
+    Five = 0b00,
+    Six = 0b01,
+    Seven = 0b10,
+    Eight = 0b11,
+}
+
+/// The parity bit sent with every frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+/// The number of stop bits sent after every frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// How full a FIFO must be before the receive interrupt fires, or how empty
+/// before the transmit interrupt fires.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FifoLevel {
+    OneEighth = 0b000,
+    OneQuarter = 0b001,
+    OneHalf = 0b010,
+    ThreeQuarters = 0b011,
+    SevenEighths = 0b100,
+}
+
+/// Line settings for the PL011 UART.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    /// Enables RTS/CTS hardware flow control on GPIO 16 and 17.
+    pub flow_control: bool,
+    pub rx_level: FifoLevel,
+    pub tx_level: FifoLevel,
+}
+
+impl Default for Config {
+    /// 115200 baud, 8N1, no flow control, interrupts at half-full FIFOs.
+    fn default() -> Config {
+        Config {
+            baud_rate: 115200,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: false,
+            rx_level: FifoLevel::OneHalf,
+            tx_level: FifoLevel::OneHalf,
+        }
+    }
+}
+
+/// Returns the integer and fractional baud rate divisors for `baud_rate`.
+///
+/// The divisor is `UART_CLOCK / (16 * baud_rate)`. The fractional part is kept
+/// in 1/64ths, rounded to the nearest.
+fn baud_divisors(baud_rate: u32) -> (u32, u32) {
+    // Scaled by 64 and doubled once more so that the last bit rounds.
+    let div = (UART_CLOCK as u64 * 8) / baud_rate as u64;
+    let div = (div + 1) / 2;
+    ((div >> 6) as u32, (div & 0x3f) as u32)
+}
+
+/// The Raspberry Pi's PL011 UART, `UART0`.
+///
+/// On the Pi 3, `UART0` is wired to the Bluetooth module unless `config.txt`
+/// contains `dtoverlay=disable-bt` or `dtoverlay=miniuart-bt`. QEMU's `raspi3`
+/// machine connects it to the first serial port.
+pub struct Pl011 {
+    registers: &'static mut Registers,
+    timeout: Option<Duration>,
+}
+
+impl Pl011 {
+    /// Initializes the PL011 UART with the default `Config`: 115200 baud, 8N1
+    /// and no flow control.
+    ///
+    /// By default, reads will never time out. To set a read timeout, use
+    /// `set_read_timeout()`.
+    pub fn new() -> Pl011 {
+        Pl011::with_config(Config::default())
+    }
+
+    /// Initializes the PL011 UART with `config`, setting GPIO pins 14 and 15
+    /// (and 16 and 17 if flow control is enabled) to their UART0 functions.
+    pub fn with_config(config: Config) -> Pl011 {
+        let registers = unsafe { &mut *(UART0_REG_BASE as *mut Registers) };
+
+        // The UART must be disabled and idle before it is reconfigured.
+        registers.CR.write(0);
+        while registers.FR.read() & FrStatus::Busy as u32 != 0 {}
+        registers.LCRH.write(0);
+
+        Gpio::new(14).into_alt(Function::Alt0);
+        Gpio::new(15).into_alt(Function::Alt0);
+        if config.flow_control {
+            Gpio::new(16).into_alt(Function::Alt3);
+            Gpio::new(17).into_alt(Function::Alt3);
+        }
+
+        registers.ICR.write(IntStatus::All as u32);
+        registers.IMSC.write(0);
+
+        let mut uart = Pl011 {
+            registers,
+            timeout: None,
+        };
+        uart.configure(config);
+        uart
+    }
+
+    /// Applies `config`. The UART is briefly disabled while the line settings
+    /// are changed; bytes in the transmit FIFO are sent first.
+    pub fn configure(&mut self, config: Config) {
+        self.registers.CR.write(0);
+        while self.registers.FR.read() & FrStatus::Busy as u32 != 0 {}
+
+        let (ibrd, fbrd) = baud_divisors(config.baud_rate);
+        self.registers.IBRD.write(ibrd);
+        self.registers.FBRD.write(fbrd);
+
+        let mut lcrh = LcrhStatus::FifoEnable as u32 | (config.data_bits as u32) << 5;
+        match config.parity {
+            Parity::None => (),
+            Parity::Odd => lcrh |= LcrhStatus::ParityEnable as u32,
+            Parity::Even => lcrh |= LcrhStatus::ParityEnable as u32 | LcrhStatus::EvenParity as u32,
+        }
+        if config.stop_bits == StopBits::Two {
+            lcrh |= LcrhStatus::TwoStopBits as u32;
+        }
+        // Writing LCRH latches IBRD and FBRD, so it must come last.
+        self.registers.LCRH.write(lcrh);
+
+        self.registers.IFLS.write((config.rx_level as u32) << 3 | config.tx_level as u32);
+
+        let mut cr = CrStatus::UartEnable as u32 | CrStatus::TxEnable as u32 | CrStatus::RxEnable as u32;
+        if config.flow_control {
+            cr |= CrStatus::RtsEnable as u32 | CrStatus::CtsEnable as u32;
+        }
+        self.registers.CR.write(cr);
+    }
+
+    /// Returns a handle to the PL011 UART without reinitializing it, so that
+    /// an interrupt handler can service the device while another handle
+    /// exists.
+    ///
+    /// # Safety
+    ///
+    /// The UART must already have been initialized with `new()` or
+    /// `with_config()`, and the caller must ensure that the handles never read
+    /// or write the FIFOs at the same time.
+    pub unsafe fn shared() -> Pl011 {
+        Pl011 {
+            registers: &mut *(UART0_REG_BASE as *mut Registers),
+            timeout: None,
+        }
+    }
+
+    /// Set the read timeout to `t` duration.
+    pub fn set_read_timeout(&mut self, t: Duration) {
+        self.timeout = Some(t);
+    }
+
+    /// Enables or disables the receive and transmit interrupts. The receive
+    /// interrupt fires when the receive FIFO reaches its level or when bytes
+    /// sit in it for a while; the transmit interrupt fires when the transmit
+    /// FIFO drains below its level.
+    pub fn set_interrupts(&mut self, rx: bool, tx: bool) {
+        let mut imsc = 0;
+        if rx {
+            imsc |= IntStatus::Rx as u32 | IntStatus::RxTimeout as u32;
+        }
+        if tx {
+            imsc |= IntStatus::Tx as u32;
+        }
+        self.registers.IMSC.write(imsc);
+    }
+
+    /// Returns `true` if the transmit FIFO can accept at least one byte. This
+    /// method does not block.
+    pub fn can_write(&self) -> bool {
+        self.registers.FR.read() & FrStatus::TxFifoFull as u32 == 0
+    }
+
+    /// Write the byte `byte`. This method blocks until there is space available
+    /// in the output FIFO.
+    pub fn write_byte(&mut self, byte: u8) {
+        while !self.can_write() {}
+        self.registers.DR.write(byte as u32);
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read. If this
+    /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately. This method does not block.
+    pub fn has_byte(&self) -> bool {
+        self.registers.FR.read() & FrStatus::RxFifoEmpty as u32 == 0
+    }
+
+    /// Blocks until there is a byte ready to read. If a read timeout is set,
+    /// this method blocks for at most that amount of time. Otherwise, this
+    /// method blocks indefinitely until there is a byte to read.
+    ///
+    /// Returns `Ok(())` if a byte is ready to read. Returns `Err(())` if the
+    /// timeout expired while waiting for a byte to be ready.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        match self.timeout {
+            Some(t) => {
+                let end = timer::current_time() + t;
+                while timer::current_time() <= end {
+                    if self.has_byte() {
+                        return Ok(());
+                    }
+                }
+                Err(())
+            }
+            None => {
+                while !self.has_byte() {}
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads a byte, reporting any receive error flagged for it. Blocks
+    /// indefinitely until a byte is ready to be read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` for a framing or parity error,
+    /// `ConnectionAborted` for a break condition, and `Other` if the receive
+    /// FIFO overflowed before this byte. The byte itself is discarded.
+    pub fn try_read_byte(&mut self) -> io::Result<u8> {
+        while !self.has_byte() {}
+        let dr = self.registers.DR.read();
+        if dr & DrStatus::BreakError as u32 != 0 {
+            return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "break condition"));
+        }
+        if dr & DrStatus::FramingError as u32 != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "framing error"));
+        }
+        if dr & DrStatus::ParityError as u32 != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "parity error"));
+        }
+        if dr & DrStatus::OverrunError as u32 != 0 {
+            return Err(io::Error::new(io::ErrorKind::Other, "receive FIFO overrun"));
+        }
+        Ok(dr as u8)
+    }
+
+    /// Reads a byte, ignoring receive errors. Blocks indefinitely until a byte
+    /// is ready to be read.
+    pub fn read_byte(&mut self) -> u8 {
+        while !self.has_byte() {}
+        self.registers.DR.read() as u8
+    }
+}
+
+impl fmt::Write for Pl011 {
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        for c in s.bytes() {
+            if c == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(c);
+        }
+        Ok(())
+    }
+}
+
+impl io::Read for Pl011 {
+    /// Waits at most the read timeout for the first byte, then reads as many
+    /// bytes as are available without waiting. A receive error is returned
+    /// only if it affects the first byte; otherwise the erroneous byte is
+    /// discarded and the read stops there.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.wait_for_byte().is_err() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Timed Out"));
+        }
+        buf[0] = self.try_read_byte()?;
+        let mut idx = 1;
+        while idx < buf.len() && self.has_byte() {
+            match self.try_read_byte() {
+                Ok(b) => buf[idx] = b,
+                Err(_) => break,
+            }
+            idx += 1;
+        }
+        Ok(idx)
+    }
+}
+
+impl io::Write for Pl011 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &b in buf {
+            self.write_byte(b);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while self.registers.FR.read() & FrStatus::Busy as u32 != 0 {}
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+use super::*;
+
+#[test]
+fn divisors() {
+    // 48 MHz / (16 * 115200) = 26.0417
+    assert_eq!(baud_divisors(115200), (26, 3));
+    // 48 MHz / (16 * 9600) = 312.5
+    assert_eq!(baud_divisors(9600), (312, 32));
+    // 48 MHz / (16 * 3000000) = 1
+    assert_eq!(baud_divisors(3000000), (1, 0));
+}
@@ -17,6 +17,15 @@ const MU_REG_BASE: usize = IO_BASE + 0x215040;
 /// The `AUXENB` register from page 9 of the BCM2837 documentation.
 const AUX_ENABLES: *mut Volatile<u8> = (IO_BASE + 0x215004) as *mut Volatile<u8>;
 
+/// Enum representing bit fields of the `AUX_MU_IER_REG` register. The BCM2837
+/// documentation swaps the receive and transmit bits; these follow the
+/// errata. Bits 2 and 3 must also be set for receive interrupts to fire.
+#[repr(u8)]
+enum IerStatus {
+    RxEnable = 1 | (0b11 << 2),
+    TxEnable = 1 << 1,
+}
+
 /// Enum representing bit fields of the `AUX_MU_LSR_REG` register.
 #[repr(u8)]
 enum LsrStatus {
@@ -88,6 +97,42 @@ impl MiniUart {
         };
     }
 
+    /// Returns a handle to the mini UART without reinitializing it, so that an
+    /// interrupt handler can service the device while another handle exists.
+    ///
+    /// # Safety
+    ///
+    /// The mini UART must already have been initialized with `new()`, and the
+    /// caller must ensure that the handles never read or write the FIFOs at
+    /// the same time.
+    pub unsafe fn shared() -> MiniUart {
+        MiniUart {
+            registers: &mut *(MU_REG_BASE as *mut Registers),
+            timeout: None,
+        }
+    }
+
+    /// Enables or disables the receive and transmit interrupts. A receive
+    /// interrupt is pending while the receive FIFO holds a byte; a transmit
+    /// interrupt is pending while the transmit FIFO is empty.
+    pub fn set_interrupts(&mut self, rx: bool, tx: bool) {
+        let mut ier = 0;
+        if rx {
+            ier |= IerStatus::RxEnable as u8;
+        }
+        if tx {
+            ier |= IerStatus::TxEnable as u8;
+        }
+        self.registers.IER_REG.write(ier);
+    }
+
+    /// Returns `true` if the transmit FIFO can accept at least one byte. If
+    /// this method returns `true`, a subsequent call to `write_byte` is
+    /// guaranteed to return immediately. This method does not block.
+    pub fn can_write(&self) -> bool {
+        return (self.registers.LSR_REG.read() & LsrStatus::TxAvailable as u8) != 0;
+    }
+
     /// Set the read timeout to `t` duration.
     pub fn set_read_timeout(&mut self, t: Duration) {
         self.timeout = Some(t);
@@ -0,0 +1,113 @@
+#![cfg_attr(not(test), no_std)]
+
+//! Serial Line IP framing (RFC 1055).
+//!
+//! A packet is sent as its bytes followed by `END`. `END` and `ESC` bytes
+//! inside the packet are replaced by `ESC ESC_END` and `ESC ESC_ESC`. An `END`
+//! is also sent before each packet so that line noise received between
+//! packets is flushed as a separate, discarded packet.
+
+#[cfg(test)]
+mod tests;
+
+/// Marks the end of a packet.
+pub const END: u8 = 0xC0;
+/// Starts an escape sequence.
+pub const ESC: u8 = 0xDB;
+/// Follows `ESC` to stand for an `END` data byte.
+pub const ESC_END: u8 = 0xDC;
+/// Follows `ESC` to stand for an `ESC` data byte.
+pub const ESC_ESC: u8 = 0xDD;
+
+/// The largest packet a `Decoder` accepts.
+pub const MAX_PACKET_SIZE: usize = 1500;
+
+/// Encodes `packet`, passing every byte of the encoding to `out`.
+pub fn encode<F: FnMut(u8)>(packet: &[u8], mut out: F) {
+    out(END);
+    for &byte in packet {
+        match byte {
+            END => {
+                out(ESC);
+                out(ESC_END);
+            }
+            ESC => {
+                out(ESC);
+                out(ESC_ESC);
+            }
+            byte => out(byte),
+        }
+    }
+    out(END);
+}
+
+/// Returns the number of bytes `encode()` produces for `packet`.
+pub fn encoded_len(packet: &[u8]) -> usize {
+    let escapes = packet.iter().filter(|&&b| b == END || b == ESC).count();
+    packet.len() + escapes + 2
+}
+
+/// Reassembles packets from a stream of encoded bytes.
+pub struct Decoder {
+    buf: [u8; MAX_PACKET_SIZE],
+    len: usize,
+    escaped: bool,
+    overflowed: bool,
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new()
+    }
+}
+
+impl Decoder {
+    /// Returns a decoder waiting for the first byte of a packet.
+    pub const fn new() -> Decoder {
+        Decoder {
+            buf: [0; MAX_PACKET_SIZE],
+            len: 0,
+            escaped: false,
+            overflowed: false,
+        }
+    }
+
+    /// Feeds `byte` to the decoder. Returns the packet that `byte` completes,
+    /// if any.
+    ///
+    /// Empty packets are skipped. Packets longer than `MAX_PACKET_SIZE` are
+    /// dropped as a whole. An `ESC` followed by anything other than `ESC_END`
+    /// or `ESC_ESC` is a protocol violation; as RFC 1055 suggests, the byte
+    /// after the `ESC` is kept as is.
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if byte == END {
+            let (len, overflowed) = (self.len, self.overflowed);
+            self.len = 0;
+            self.escaped = false;
+            self.overflowed = false;
+            return match (len, overflowed) {
+                (0, _) | (_, true) => None,
+                (len, false) => Some(&self.buf[..len]),
+            };
+        }
+
+        let byte = match (self.escaped, byte) {
+            (false, ESC) => {
+                self.escaped = true;
+                return None;
+            }
+            (true, ESC_END) => END,
+            (true, ESC_ESC) => ESC,
+            (_, byte) => byte,
+        };
+        self.escaped = false;
+
+        if self.len < MAX_PACKET_SIZE {
+            self.buf[self.len] = byte;
+            self.len += 1;
+        } else {
+            self.overflowed = true;
+        }
+        None
+    }
+}
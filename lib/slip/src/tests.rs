@@ -0,0 +1,66 @@
+use super::*;
+
+fn encoded(packet: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    encode(packet, |b| out.push(b));
+    out
+}
+
+fn decode_all(bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut decoder = Decoder::new();
+    bytes
+        .iter()
+        .filter_map(|&b| decoder.push(b).map(|p| p.to_vec()))
+        .collect()
+}
+
+#[test]
+fn encode_plain() {
+    assert_eq!(encoded(b"abc"), [END, b'a', b'b', b'c', END]);
+    assert_eq!(encoded(b""), [END, END]);
+}
+
+#[test]
+fn encode_escapes() {
+    assert_eq!(
+        encoded(&[1, END, 2, ESC, 3]),
+        [END, 1, ESC, ESC_END, 2, ESC, ESC_ESC, 3, END]
+    );
+    assert_eq!(encoded_len(&[1, END, 2, ESC, 3]), 9);
+}
+
+#[test]
+fn round_trip() {
+    let packets: [&[u8]; 3] = [b"hello", &[END, ESC, END, ESC], &[0, ESC_END, ESC_ESC, 255]];
+    let mut stream = vec![];
+    for packet in packets.iter() {
+        stream.extend(encoded(packet));
+    }
+
+    let decoded = decode_all(&stream);
+    assert_eq!(decoded.len(), packets.len());
+    for (decoded, packet) in decoded.iter().zip(packets.iter()) {
+        assert_eq!(&decoded[..], *packet);
+    }
+}
+
+#[test]
+fn skips_empty_packets() {
+    assert_eq!(decode_all(&[END, END, END, b'x', END, END]), vec![vec![b'x']]);
+}
+
+#[test]
+fn drops_oversized_packets() {
+    let big = vec![7u8; MAX_PACKET_SIZE + 1];
+    let mut stream = encoded(&big);
+    stream.extend(encoded(b"next"));
+    assert_eq!(decode_all(&stream), vec![b"next".to_vec()]);
+
+    let max = vec![7u8; MAX_PACKET_SIZE];
+    assert_eq!(decode_all(&encoded(&max)), vec![max]);
+}
+
+#[test]
+fn keeps_byte_after_invalid_escape() {
+    assert_eq!(decode_all(&[ESC, b'q', b'r', END]), vec![b"qr".to_vec()]);
+}
@@ -0,0 +1,120 @@
+mod tun;
+
+use serial;
+use structopt;
+use structopt_derive::StructOpt;
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use serial::core::{BaudRate, SerialDevice, SerialPortSettings};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(about = "Bridge a SLIP serial line to a TUN interface.")]
+struct Opt {
+    #[structopt(short = "b", long = "baud", parse(try_from_str = "parse_baud_rate"),
+                help = "Set baud rate", default_value = "115200")]
+    baud_rate: BaudRate,
+
+    #[structopt(short = "n", long = "name", help = "Name of the TUN interface",
+                default_value = "slip0")]
+    name: String,
+
+    #[structopt(short = "a", long = "addr",
+                help = "Address of the host end, assigned to the TUN interface",
+                default_value = "192.168.7.1/24")]
+    addr: String,
+
+    #[structopt(help = "Path to TTY device", parse(from_os_str))]
+    tty_path: PathBuf,
+}
+
+fn parse_baud_rate(s: &str) -> Result<BaudRate, ::std::num::ParseIntError> {
+    Ok(BaudRate::from_speed(s.parse()?))
+}
+
+/// Opens the TTY at `path` with `baud_rate`, 8N1 and no flow control.
+fn open_port(path: &Path, baud_rate: BaudRate) -> io::Result<serial::SystemPort> {
+    let mut port = serial::open(path)?;
+    let mut settings = port.read_settings()?;
+    settings.set_baud_rate(baud_rate)?;
+    settings.set_char_size(serial::Bits8);
+    settings.set_parity(serial::ParityNone);
+    settings.set_stop_bits(serial::Stop1);
+    settings.set_flow_control(serial::FlowNone);
+    port.write_settings(&settings)?;
+    port.set_timeout(Duration::from_secs(60))?;
+    Ok(port)
+}
+
+/// Runs `ip` with `args`, failing if it does not exit successfully.
+fn ip(args: &[&str]) -> io::Result<()> {
+    let status = Command::new("ip").args(args).status()?;
+    if !status.success() {
+        return Err(io::Error::new(io::ErrorKind::Other, format!("ip {:?}: {}", args, status)));
+    }
+    Ok(())
+}
+
+/// Decodes packets from the serial line and writes them to the TUN device.
+fn serial_to_tun(mut port: serial::SystemPort, mut tun: tun::Tun) -> io::Result<()> {
+    let mut decoder = slip::Decoder::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match port.read(&mut buf) {
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e),
+        };
+        for &byte in &buf[..n] {
+            if let Some(packet) = decoder.push(byte) {
+                if let Err(e) = tun.write(packet) {
+                    eprintln!("dropping a packet from the serial line: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Reads packets from the TUN device and encodes them onto the serial line.
+fn tun_to_serial(mut tun: tun::Tun, mut port: serial::SystemPort) -> io::Result<()> {
+    let mut buf = [0u8; 4096];
+    let mut encoded = Vec::with_capacity(2 * buf.len());
+    loop {
+        let n = tun.read(&mut buf)?;
+        if n > slip::MAX_PACKET_SIZE {
+            eprintln!("dropping a {} byte packet from the host", n);
+            continue;
+        }
+        encoded.clear();
+        slip::encode(&buf[..n], |b| encoded.push(b));
+        port.write_all(&encoded)?;
+    }
+}
+
+fn main() {
+    let opt = Opt::from_args();
+
+    let tun = tun::Tun::open(&opt.name).expect("failed to create the TUN interface");
+    ip(&["addr", "add", &opt.addr, "dev", &opt.name]).expect("failed to set the address");
+    ip(&["link", "set", &opt.name, "up"]).expect("failed to bring the interface up");
+
+    // One handle of each per direction, so that neither blocks the other.
+    let reader = open_port(&opt.tty_path, opt.baud_rate).expect("path points to invalid TTY");
+    let writer = open_port(&opt.tty_path, opt.baud_rate).expect("path points to invalid TTY");
+    let tun_writer = tun.try_clone().expect("failed to clone the TUN device");
+
+    println!("Bridging {} to {} ({})", opt.tty_path.display(), opt.name, opt.addr);
+    let rx = thread::spawn(move || serial_to_tun(reader, tun_writer));
+    let tx = thread::spawn(move || tun_to_serial(tun, writer));
+
+    for (direction, handle) in vec![("serial -> tun", rx), ("tun -> serial", tx)] {
+        if let Err(e) = handle.join().expect("bridge thread panicked") {
+            eprintln!("{}: {}", direction, e);
+        }
+    }
+}
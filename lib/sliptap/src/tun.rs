@@ -0,0 +1,65 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+
+const TUNSETIFF: libc::c_ulong = 0x4004_54ca;
+const IFF_TUN: libc::c_short = 0x0001;
+const IFF_NO_PI: libc::c_short = 0x1000;
+
+/// The prefix of `struct ifreq` that `TUNSETIFF` uses.
+#[repr(C)]
+struct IfReq {
+    name: [u8; libc::IFNAMSIZ],
+    flags: libc::c_short,
+    _pad: [u8; 22],
+}
+
+/// A Linux TUN device without packet information headers. Every `read()`
+/// returns one IP packet and every `write()` sends one.
+///
+/// SLIP carries bare IP packets, which is what a TUN device exchanges; a TAP
+/// device would also need ethernet headers and ARP.
+pub struct Tun(File);
+
+impl Tun {
+    /// Creates the TUN interface `name`, or attaches to it if it exists and
+    /// is not in use. Usually requires `CAP_NET_ADMIN`.
+    pub fn open(name: &str) -> io::Result<Tun> {
+        if name.len() >= libc::IFNAMSIZ {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "interface name too long"));
+        }
+
+        let file = OpenOptions::new().read(true).write(true).open("/dev/net/tun")?;
+        let mut req = IfReq {
+            name: [0; libc::IFNAMSIZ],
+            flags: IFF_TUN | IFF_NO_PI,
+            _pad: [0; 22],
+        };
+        req.name[..name.len()].copy_from_slice(name.as_bytes());
+        if unsafe { libc::ioctl(file.as_raw_fd(), TUNSETIFF, &mut req) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Tun(file))
+    }
+
+    /// Returns another handle to the same device.
+    pub fn try_clone(&self) -> io::Result<Tun> {
+        self.0.try_clone().map(Tun)
+    }
+}
+
+impl Read for Tun {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for Tun {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+#![cfg_attr(not(test), no_std)]
+
+//! A client for downloading a file with TFTP (RFC 1350), with the block size
+//! option (RFC 2347 and RFC 2348).
+//!
+//! `Download` is a state machine that does no I/O. The caller sends
+//! `packet()` to `destination_port()` on the server, passes every UDP
+//! datagram that arrives to `receive()`, sends `packet()` again whenever
+//! `receive()` asks for it, and calls `timeout()` when nothing arrives in
+//! time.
+
+#[cfg(test)]
+mod tests;
+
+/// The port TFTP servers listen on for requests.
+pub const SERVER_PORT: u16 = 69;
+
+/// The block size used unless the server accepts another one.
+pub const DEFAULT_BLOCK_SIZE: usize = 512;
+
+/// The largest block size that can be requested: a block that fills an
+/// ethernet frame.
+pub const MAX_BLOCK_SIZE: usize = 1468;
+
+/// The number of times the last packet is sent again before the download
+/// times out.
+pub const MAX_RETRIES: u32 = 5;
+
+/// The largest packet `Download` sends.
+const MAX_REQUEST_SIZE: usize = 512;
+
+const OP_RRQ: u16 = 1;
+const OP_DATA: u16 = 3;
+const OP_ACK: u16 = 4;
+const OP_ERROR: u16 = 5;
+const OP_OACK: u16 = 6;
+
+/// Why a download failed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The server sent an error packet with this error code. Code 1 means
+    /// the file was not found.
+    Remote(u16),
+    /// The server sent a packet that is not valid at this point.
+    Protocol,
+    /// The file name does not fit in a request, or the block size is out of
+    /// range.
+    InvalidRequest,
+    /// The server stopped answering.
+    TimedOut,
+}
+
+/// What a received packet means to the caller.
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    /// `data` belongs at `offset` in the file. If `last` is set, the file is
+    /// complete. The caller must send `packet()`.
+    Data {
+        offset: usize,
+        data: &'a [u8],
+        last: bool,
+    },
+    /// The caller must send `packet()`.
+    Reply,
+    /// The packet is not part of this download.
+    Ignored,
+}
+
+/// The state of a download.
+pub struct Download {
+    /// The packet to send: the request, then the latest acknowledgement
+    packet: [u8; MAX_REQUEST_SIZE],
+    packet_len: usize,
+    /// The port of the server's end of the transfer, once it has answered
+    server_port: Option<u16>,
+    /// The block size asked for in the request, if any
+    requested_block_size: Option<usize>,
+    /// The block size in effect
+    block_size: usize,
+    /// The number of the last block received, or 0
+    block: u16,
+    /// The number of bytes received
+    received: usize,
+    retries: u32,
+    done: bool,
+}
+
+impl Download {
+    /// Starts a download of `filename`. If `block_size` is `Some`, the
+    /// server is asked to use that block size, which must be between 8 and
+    /// `MAX_BLOCK_SIZE`.
+    pub fn new(filename: &str, block_size: Option<usize>) -> Result<Download, Error> {
+        if let Some(size) = block_size {
+            if !(8..=MAX_BLOCK_SIZE).contains(&size) {
+                return Err(Error::InvalidRequest);
+            }
+        }
+
+        let mut download = Download {
+            packet: [0; MAX_REQUEST_SIZE],
+            packet_len: 0,
+            server_port: None,
+            requested_block_size: block_size,
+            block_size: DEFAULT_BLOCK_SIZE,
+            block: 0,
+            received: 0,
+            retries: 0,
+            done: false,
+        };
+
+        let mut digits = [0u8; 4];
+        let mut fields: [&[u8]; 4] = [filename.as_bytes(), b"octet", b"", b""];
+        let mut count = 2;
+        if let Some(size) = block_size {
+            fields[2] = b"blksize";
+            fields[3] = format_decimal(size, &mut digits);
+            count = 4;
+        }
+
+        download.push(&OP_RRQ.to_be_bytes())?;
+        for field in &fields[..count] {
+            if field.contains(&0) {
+                return Err(Error::InvalidRequest);
+            }
+            download.push(field)?;
+            download.push(&[0])?;
+        }
+        Ok(download)
+    }
+
+    /// Appends `bytes` to the packet.
+    fn push(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.packet_len + bytes.len();
+        if end > MAX_REQUEST_SIZE {
+            return Err(Error::InvalidRequest);
+        }
+        self.packet[self.packet_len..end].copy_from_slice(bytes);
+        self.packet_len = end;
+        Ok(())
+    }
+
+    /// Makes an acknowledgement of `block` the packet to send.
+    fn acknowledge(&mut self, block: u16) {
+        self.packet[..2].copy_from_slice(&OP_ACK.to_be_bytes());
+        self.packet[2..4].copy_from_slice(&block.to_be_bytes());
+        self.packet_len = 4;
+        self.retries = 0;
+    }
+
+    /// Returns the packet to send to the server.
+    pub fn packet(&self) -> &[u8] {
+        &self.packet[..self.packet_len]
+    }
+
+    /// Returns the server port to send `packet()` to: `SERVER_PORT` until
+    /// the server answers, and the port it answered from afterwards.
+    pub fn destination_port(&self) -> u16 {
+        self.server_port.unwrap_or(SERVER_PORT)
+    }
+
+    /// Returns the block size in effect.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the number of bytes received so far.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Returns `true` once the whole file has been received.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Handles the UDP payload `packet` received from the server's port
+    /// `from_port`.
+    ///
+    /// Block numbers wrap around after 65535, so files of any size can be
+    /// received.
+    pub fn receive<'a>(&mut self, from_port: u16, packet: &'a [u8]) -> Result<Event<'a>, Error> {
+        if self.server_port.is_some() && self.server_port != Some(from_port) {
+            return Ok(Event::Ignored);
+        }
+        if packet.len() < 4 {
+            return Err(Error::Protocol);
+        }
+        let opcode = u16::from_be_bytes([packet[0], packet[1]]);
+        let number = u16::from_be_bytes([packet[2], packet[3]]);
+
+        match opcode {
+            OP_DATA => {
+                let data = &packet[4..];
+                if data.len() > self.block_size {
+                    return Err(Error::Protocol);
+                }
+                if number == self.block && self.server_port.is_some() {
+                    // Our acknowledgement was lost; the server sent the block again.
+                    return Ok(Event::Reply);
+                }
+                if self.done || number != self.block.wrapping_add(1) {
+                    return Ok(Event::Ignored);
+                }
+
+                self.server_port = Some(from_port);
+                self.block = number;
+                let offset = self.received;
+                self.received += data.len();
+                self.done = data.len() < self.block_size;
+                self.acknowledge(number);
+                Ok(Event::Data {
+                    offset,
+                    data,
+                    last: self.done,
+                })
+            }
+            OP_OACK if self.server_port.is_none() => {
+                self.block_size = self.parse_options(&packet[2..])?;
+                self.server_port = Some(from_port);
+                self.acknowledge(0);
+                Ok(Event::Reply)
+            }
+            // Our acknowledgement of the options was lost.
+            OP_OACK if self.block == 0 => Ok(Event::Reply),
+            OP_OACK => Ok(Event::Ignored),
+            OP_ERROR => Err(Error::Remote(number)),
+            _ => Err(Error::Protocol),
+        }
+    }
+
+    /// Parses the options of an option acknowledgement and returns the block
+    /// size the server accepted. The server may only acknowledge options
+    /// that were requested, and may only lower the block size.
+    fn parse_options(&self, options: &[u8]) -> Result<usize, Error> {
+        let mut block_size = DEFAULT_BLOCK_SIZE;
+        let mut fields = options.split(|&b| b == 0);
+        while let Some(name) = fields.next() {
+            if name.is_empty() {
+                break;
+            }
+            let value = fields.next().ok_or(Error::Protocol)?;
+            let requested = match self.requested_block_size {
+                Some(size) if name.eq_ignore_ascii_case(b"blksize") => size,
+                _ => return Err(Error::Protocol),
+            };
+            block_size = parse_decimal(value).ok_or(Error::Protocol)?;
+            if block_size < 8 || block_size > requested {
+                return Err(Error::Protocol);
+            }
+        }
+        Ok(block_size)
+    }
+
+    /// Records that nothing arrived in time. The caller must send `packet()`
+    /// again.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::TimedOut` once the packet has been sent again
+    /// `MAX_RETRIES` times without an answer.
+    pub fn timeout(&mut self) -> Result<(), Error> {
+        if self.retries >= MAX_RETRIES {
+            return Err(Error::TimedOut);
+        }
+        self.retries += 1;
+        Ok(())
+    }
+}
+
+/// Writes `n` in decimal to the end of `buf` and returns the digits.
+fn format_decimal(mut n: usize, buf: &mut [u8; 4]) -> &[u8] {
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 || start == 0 {
+            break;
+        }
+    }
+    &buf[start..]
+}
+
+/// Parses a decimal number of at most 5 digits.
+fn parse_decimal(digits: &[u8]) -> Option<usize> {
+    if digits.is_empty() || digits.len() > 5 {
+        return None;
+    }
+    digits.iter().try_fold(0, |n, &d| match d {
+        b'0'..=b'9' => Some(n * 10 + (d - b'0') as usize),
+        _ => None,
+    })
+}
@@ -0,0 +1,213 @@
+use super::*;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+/// How a test server misbehaves.
+#[derive(Clone, Copy, Default)]
+struct Quirks {
+    /// Ignore the block size option instead of acknowledging it.
+    ignore_options: bool,
+    /// Do not send this block the first time, as if it was lost.
+    drop_block: Option<u16>,
+}
+
+/// A minimal TFTP server on localhost that serves `file` as `kernel8.img`
+/// to a single client and then exits. Returns its address.
+fn serve(file: Vec<u8>, quirks: Quirks) -> SocketAddr {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buf = [0u8; 2048];
+        let (len, client) = listener.recv_from(&mut buf).unwrap();
+        let request = &buf[..len];
+        assert_eq!(&request[..2], &OP_RRQ.to_be_bytes());
+        let fields: Vec<&[u8]> = request[2..len - 1].split(|&b| b == 0).collect();
+
+        // Every transfer uses a fresh port, as RFC 1350 requires.
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(300))).unwrap();
+        if fields[0] != b"kernel8.img" {
+            let mut error = vec![0, OP_ERROR as u8, 0, 1];
+            error.extend_from_slice(b"File not found\0");
+            socket.send_to(&error, client).unwrap();
+            return;
+        }
+
+        let mut block_size = DEFAULT_BLOCK_SIZE;
+        if fields.len() == 4 && !quirks.ignore_options {
+            block_size = std::str::from_utf8(fields[3]).unwrap().parse().unwrap();
+            let mut oack = vec![0, OP_OACK as u8];
+            oack.extend_from_slice(b"blksize\0");
+            oack.extend_from_slice(fields[3]);
+            oack.push(0);
+            socket.send_to(&oack, client).unwrap();
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..len], &[0, OP_ACK as u8, 0, 0]);
+        }
+
+        let mut dropped = false;
+        let mut block: u16 = 1;
+        // A file that is a multiple of the block size ends with an empty block.
+        for i in 0..file.len() / block_size + 1 {
+            let chunk = &file[i * block_size..file.len().min((i + 1) * block_size)];
+            let mut data = vec![0, OP_DATA as u8];
+            data.extend_from_slice(&block.to_be_bytes());
+            data.extend_from_slice(chunk);
+            loop {
+                if quirks.drop_block == Some(block) && !dropped {
+                    dropped = true;
+                } else {
+                    socket.send_to(&data, client).unwrap();
+                }
+                match socket.recv_from(&mut buf) {
+                    Ok((4, _)) if buf[..4] == [0, OP_ACK as u8, data[2], data[3]] => break,
+                    Ok(_) | Err(_) => continue,
+                }
+            }
+            block = block.wrapping_add(1);
+        }
+    });
+    addr
+}
+
+/// Downloads `filename` from the server at `server` with `Download`.
+fn fetch(server: SocketAddr, filename: &str, block_size: Option<usize>) -> Result<Vec<u8>, Error> {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+
+    let mut download = Download::new(filename, block_size)?;
+    let mut file = vec![];
+    let mut buf = [0u8; 2048];
+    while !download.is_done() {
+        // The test server cannot listen on port 69, so requests go to its
+        // actual port.
+        let port = match download.destination_port() {
+            SERVER_PORT => server.port(),
+            port => port,
+        };
+        socket.send_to(download.packet(), (server.ip(), port)).unwrap();
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(_) => {
+                    download.timeout()?;
+                    break;
+                }
+            };
+            match download.receive(from.port(), &buf[..len])? {
+                Event::Data { offset, data, .. } => {
+                    assert_eq!(offset, file.len());
+                    file.extend_from_slice(data);
+                    break;
+                }
+                Event::Reply => break,
+                Event::Ignored => continue,
+            }
+        }
+    }
+    let dest = SocketAddr::new(server.ip(), download.destination_port());
+    socket.send_to(download.packet(), dest).unwrap();
+    Ok(file)
+}
+
+fn test_file(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 256) as u8).collect()
+}
+
+#[test]
+fn request_packet() {
+    let download = Download::new("kernel8.img", None).unwrap();
+    assert_eq!(download.packet(), b"\0\x01kernel8.img\0octet\0");
+    assert_eq!(download.destination_port(), SERVER_PORT);
+
+    let download = Download::new("k", Some(1024)).unwrap();
+    assert_eq!(download.packet(), b"\0\x01k\0octet\0blksize\x001024\0");
+}
+
+#[test]
+fn invalid_requests() {
+    assert!(Download::new("k", Some(4)).is_err());
+    assert!(Download::new("k", Some(MAX_BLOCK_SIZE + 1)).is_err());
+    assert!(Download::new("k\0", None).is_err());
+    assert!(Download::new(&"k".repeat(600), None).is_err());
+}
+
+#[test]
+fn ignores_other_ports_and_times_out() {
+    let mut download = Download::new("k", None).unwrap();
+    assert_eq!(download.receive(1000, &[0, 3, 0, 1, 42]), Ok(Event::Data {
+        offset: 0,
+        data: &[42],
+        last: true,
+    }));
+    assert_eq!(download.receive(1001, &[0, 3, 0, 2, 42]), Ok(Event::Ignored));
+    assert_eq!(download.receive(1000, &[0, 3, 0, 1, 42]), Ok(Event::Reply));
+    assert_eq!(download.packet(), &[0, 4, 0, 1]);
+    assert!(download.is_done());
+
+    let mut download = Download::new("k", None).unwrap();
+    for _ in 0..MAX_RETRIES {
+        assert_eq!(download.timeout(), Ok(()));
+    }
+    assert_eq!(download.timeout(), Err(Error::TimedOut));
+}
+
+#[test]
+fn rejects_unrequested_options() {
+    let mut download = Download::new("k", None).unwrap();
+    assert_eq!(download.receive(1000, b"\0\x06blksize\x001024\0"), Err(Error::Protocol));
+
+    let mut download = Download::new("k", Some(512)).unwrap();
+    assert_eq!(download.receive(1000, b"\0\x06blksize\x001024\0"), Err(Error::Protocol));
+}
+
+#[test]
+fn download_default_block_size() {
+    let file = test_file(3 * 512 + 100);
+    let server = serve(file.clone(), Quirks::default());
+    assert_eq!(fetch(server, "kernel8.img", None), Ok(file));
+}
+
+#[test]
+fn download_multiple_of_block_size() {
+    let file = test_file(4 * 512);
+    let server = serve(file.clone(), Quirks::default());
+    assert_eq!(fetch(server, "kernel8.img", None), Ok(file));
+}
+
+#[test]
+fn download_negotiated_block_size() {
+    let file = test_file(10 * 1024 + 1);
+    let server = serve(file.clone(), Quirks::default());
+    assert_eq!(fetch(server, "kernel8.img", Some(1024)), Ok(file));
+}
+
+#[test]
+fn download_options_ignored() {
+    let file = test_file(2000);
+    let quirks = Quirks {
+        ignore_options: true,
+        ..Quirks::default()
+    };
+    let server = serve(file.clone(), quirks);
+    assert_eq!(fetch(server, "kernel8.img", Some(1024)), Ok(file));
+}
+
+#[test]
+fn download_lost_block() {
+    let file = test_file(5 * 512 + 3);
+    let quirks = Quirks {
+        drop_block: Some(3),
+        ..Quirks::default()
+    };
+    let server = serve(file.clone(), quirks);
+    assert_eq!(fetch(server, "kernel8.img", None), Ok(file));
+}
+
+#[test]
+fn download_missing_file() {
+    let server = serve(vec![], Quirks::default());
+    assert_eq!(fetch(server, "missing.img", None), Err(Error::Remote(1)));
+}
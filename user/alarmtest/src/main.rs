@@ -0,0 +1,69 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use core::ptr;
+use core::time::Duration;
+
+use kernel_api::syscall::*;
+use kernel_api::{println, OsError, SIGALRM};
+
+/// The number of `SIGALRM`s `on_alarm` has received.
+static mut ALARMS: u64 = 0;
+
+extern "C" fn on_alarm(_sig: u64) {
+    unsafe {
+        ptr::write_volatile(&mut ALARMS, ptr::read_volatile(&ALARMS) + 1);
+    }
+}
+
+fn alarms() -> u64 {
+    unsafe { ptr::read_volatile(&ALARMS) }
+}
+
+/// Sleeps until `on_alarm` has received `n` alarms in total. The sleeps are
+/// interrupted by the alarms.
+fn wait_alarms(n: u64) {
+    while alarms() < n {
+        let _ = sleep(Duration::from_secs(1));
+    }
+}
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+/// Checks one-shot and periodic alarms.
+fn main() {
+    sigaction(SIGALRM, SigAction::Handler(on_alarm), 0).unwrap();
+
+    // A one-shot alarm expires once.
+    assert_eq!(alarm(ms(20)).unwrap(), Duration::from_secs(0));
+    wait_alarms(1);
+    let _ = sleep(ms(50));
+    assert_eq!(alarms(), 1);
+
+    // Replacing an alarm reports the time left on it, and a disarmed alarm
+    // does not expire.
+    alarm(ms(1000)).unwrap();
+    let left = alarm(Duration::from_secs(0)).unwrap();
+    assert!(left > ms(500) && left <= ms(1000));
+    let _ = sleep(ms(50));
+    assert_eq!(alarms(), 1);
+
+    // A periodic alarm keeps its period while we sleep through it.
+    let start = time();
+    setitimer(ms(10), ms(10)).unwrap();
+    wait_alarms(6);
+    let elapsed = time() - start;
+    let (left, interval) = setitimer(Duration::from_secs(0), Duration::from_secs(0)).unwrap();
+    assert!(elapsed >= ms(45) && elapsed < ms(1000));
+    assert!(left <= ms(10) && interval == ms(10));
+
+    // Periods too short to be useful are refused.
+    assert_eq!(setitimer(ms(10), Duration::from_micros(1)), Err(OsError::InvalidArgument));
+
+    println!("alarmtest: ok");
+}
@@ -3,7 +3,7 @@
 IMG=fs.img
 MNT=mnt
 
-PROGS=(sleep fib echo)
+PROGS=(sleep fib echo shell socktest ping wc sigtest threads shmtest ipctest alarmtest)
 
 if [ -z "$CS3210_COPY" ]; then
     echo "[!] please set CS3210_COPY environment variable"
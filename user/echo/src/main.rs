@@ -1,5 +1,4 @@
 #![feature(asm)]
-#![feature(never_type)]
 #![no_std]
 #![no_main]
 
@@ -17,7 +16,33 @@ fn main() {
     }
 }
 
-fn main_inner() -> OsResult<!> {
+/// The port the echo server listens on.
+const PORT: u16 = 80;
+
+/// Accepts a connection on `PORT` and sends every received byte back until
+/// the client disconnects. The socket is released when the process exits.
+fn main_inner() -> OsResult<()> {
     // Lab 5 3
-    unimplemented!("main_inner()")
+    let mut buf = [0u8; 512];
+    let sock = sock_create()?;
+    sock_listen(sock, PORT)?;
+    println!("[{:02}] Listening on port {}", getpid(), PORT);
+
+    while !sock_status(sock)?.is_active {
+        sleep(Duration::from_millis(100))?;
+    }
+    println!("[{:02}] Accepted a connection", getpid());
+
+    loop {
+        let n = sock_recv(sock, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let mut sent = 0;
+        while sent < n {
+            sent += sock_send(sock, &buf[sent..n])?;
+        }
+    }
+    println!("[{:02}] Connection closed", getpid());
+    Ok(())
 }
@@ -0,0 +1 @@
+../../shared/cr0.rs
\ No newline at end of file
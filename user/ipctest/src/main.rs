@@ -0,0 +1,58 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use kernel_api::syscall::*;
+use kernel_api::{env, println, Message, OsError};
+
+/// A call whose reply carries the payload in upper case.
+const UPPERCASE: u64 = 1;
+/// A message that needs no reply.
+const NOTE: u64 = 2;
+
+/// Checks that a child can call a port of its parent and get the reply, and
+/// send to it without one.
+fn main() {
+    if let Some(port) = env::args().nth(1) {
+        client(port.parse().unwrap());
+        return;
+    }
+
+    run();
+    println!("ipctest: ok");
+}
+
+fn client(port: u64) {
+    let mut message = Message::with_payload(UPPERCASE, b"hello").unwrap();
+    port_call(port, &mut message).unwrap();
+    assert_eq!(message.payload(), b"HELLO");
+
+    // Only the owner of a port receives from it.
+    assert_eq!(port_receive(port, &mut message), Err(OsError::NoAccess));
+    port_send(port, &Message::new(NOTE)).unwrap();
+}
+
+fn run() {
+    let port = port_create().unwrap();
+    let mut buf = [0; 20];
+    let child = spawn("/ipctest.bin", &["ipctest", env::format_u64(port, &mut buf)]).unwrap();
+
+    let mut message = Message::default();
+    let token = port_receive(port, &mut message).unwrap();
+    assert!(token != 0 && message.label == UPPERCASE && message.sender == child);
+    assert_eq!(message.payload().len(), 5);
+    let mut upper = [0; 5];
+    upper.copy_from_slice(message.payload());
+    upper.make_ascii_uppercase();
+    let reply = Message::with_payload(UPPERCASE, &upper).unwrap();
+    port_reply(token, &reply).unwrap();
+    assert_eq!(port_reply(token, &reply), Err(OsError::NoEntry));
+
+    assert!(port_receive(port, &mut message).unwrap() == 0 && message.label == NOTE);
+    assert_eq!(wait(child).unwrap(), 0);
+
+    port_destroy(port).unwrap();
+    assert_eq!(port_send(port, &Message::new(NOTE)), Err(OsError::NoEntry));
+}
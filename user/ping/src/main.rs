@@ -0,0 +1,148 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use core::time::Duration;
+
+use kernel_api::syscall::*;
+use kernel_api::{env, println, IpAddr, OsResult, PollFd, SocketDescriptor, POLL_IN};
+
+/// The number of echo requests sent unless given on the command line.
+const DEFAULT_COUNT: u16 = 4;
+
+/// How long to wait for each reply.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The time between two requests.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_HEADER_LEN: usize = 8;
+
+/// The data carried by each request, which the reply must echo.
+const PAYLOAD: &[u8] = b"cs3210 ping payload";
+
+/// Sends ICMP echo requests to a host and reports the round-trip time of each
+/// reply. Exits with a non-zero status if no reply arrives.
+fn main() {
+    let mut args = env::args().skip(1);
+    let host = match args.next() {
+        Some(host) => host,
+        None => {
+            println!("usage: ping <host> [count]");
+            exit(2);
+        }
+    };
+    let count = match args.next().map(str::parse) {
+        None => DEFAULT_COUNT,
+        Some(Ok(count)) if count > 0 => count,
+        Some(_) => {
+            println!("ping: invalid count");
+            exit(2);
+        }
+    };
+
+    match ping(host, count) {
+        Ok(0) => exit(1),
+        Ok(_) => (),
+        Err(error) => {
+            println!("ping: {}: {:?}", host, error);
+            exit(1);
+        }
+    }
+}
+
+/// Pings `host` `count` times and returns the number of replies.
+fn ping(host: &str, count: u16) -> OsResult<u16> {
+    let addr = resolve(host)?;
+    let sock = sock_create_icmp()?;
+    let ident = sock_bind(sock, 0)?;
+
+    let [a, b, c, d] = addr.ip.to_be_bytes();
+    println!("PING {} ({}.{}.{}.{})", host, a, b, c, d);
+
+    let mut received = 0;
+    let mut total = Duration::from_secs(0);
+    for seq in 0..count {
+        if seq > 0 {
+            sleep(INTERVAL)?;
+        }
+        match echo(sock, addr, ident, seq)? {
+            Some(rtt) => {
+                println!("reply from {}.{}.{}.{}: seq={} time={}.{:03} ms",
+                         a, b, c, d, seq, rtt.as_millis(), rtt.as_micros() % 1000);
+                received += 1;
+                total += rtt;
+            }
+            None => println!("request timed out: seq={}", seq),
+        }
+    }
+
+    let lost = (count - received) as u32 * 100 / count as u32;
+    println!("{} sent, {} received, {}% lost", count, received, lost);
+    if received > 0 {
+        let average = total / received as u32;
+        println!("average time {}.{:03} ms", average.as_millis(), average.as_micros() % 1000);
+    }
+    Ok(received)
+}
+
+/// Sends the echo request `seq` to `addr` and waits up to `TIMEOUT` for the
+/// reply. Returns the round-trip time, or `None` if no reply arrived.
+fn echo(sock: SocketDescriptor, addr: IpAddr, ident: u16, seq: u16) -> OsResult<Option<Duration>> {
+    let mut request = [0u8; ICMP_HEADER_LEN + PAYLOAD.len()];
+    request[0] = ICMP_ECHO_REQUEST;
+    request[4..6].copy_from_slice(&ident.to_be_bytes());
+    request[6..8].copy_from_slice(&seq.to_be_bytes());
+    request[ICMP_HEADER_LEN..].copy_from_slice(PAYLOAD);
+    let sum = checksum(&request);
+    request[2..4].copy_from_slice(&sum.to_be_bytes());
+
+    let start = time();
+    sock_sendto(sock, &request, addr)?;
+
+    let deadline = start + TIMEOUT;
+    let mut buf = [0u8; 256];
+    loop {
+        let now = time();
+        if now >= deadline {
+            return Ok(None);
+        }
+        let mut fds = [PollFd::socket(sock, POLL_IN)];
+        if poll(&mut fds, Some(deadline - now))? == 0 {
+            return Ok(None);
+        }
+
+        // Replies to earlier requests that timed out are skipped.
+        let (n, from) = sock_recvfrom(sock, &mut buf)?;
+        let reply = &buf[..n];
+        if from.ip == addr.ip
+            && n == request.len()
+            && reply[0] == ICMP_ECHO_REPLY
+            && reply[4..8] == request[4..8]
+            && &reply[ICMP_HEADER_LEN..] == PAYLOAD
+        {
+            return Ok(Some(time() - start));
+        }
+    }
+}
+
+/// Returns the internet checksum of `data`.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = match *chunk {
+            [hi, lo] => u16::from_be_bytes([hi, lo]),
+            [hi] => u16::from_be_bytes([hi, 0]),
+            _ => unreachable!(),
+        };
+        sum += word as u32;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
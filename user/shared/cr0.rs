@@ -16,7 +16,7 @@ fn panic(_info: &PanicInfo) -> ! {
     } else {
         println!("No idea where the panic occured");
     }
-    loop {}
+    kernel_api::syscall::exit(101);
 }
 
 unsafe fn zeros_bss() {
@@ -34,9 +34,12 @@ unsafe fn zeros_bss() {
     }
 }
 
+/// The entry point of every user program. The kernel passes the address and
+/// the length of the argument block in `x0` and `x1`.
 #[no_mangle]
-pub unsafe extern "C" fn _start() -> ! {
+pub unsafe extern "C" fn _start(args: *const u8, len: usize) -> ! {
     zeros_bss();
+    kernel_api::env::init(args, len);
     crate::main();
-    kernel_api::syscall::exit();
+    kernel_api::syscall::exit(0);
 }
@@ -0,0 +1,234 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use stack_vec::StackVec;
+
+use kernel_api::syscall::{
+    chdir, getcwd, idle_time, kill, pipe, pipe_close, read_console, spawn_with_stdio, time, wait, write,
+};
+use kernel_api::{print, println, signal_status, OsError, PipeDescriptor, SIGTERM};
+
+/// The maximum length of a command line.
+const MAX_LINE: usize = 512;
+
+/// The maximum number of arguments of a command, including its name.
+const MAX_ARGS: usize = 16;
+
+/// The maximum number of commands in a pipeline.
+const MAX_STAGES: usize = 4;
+
+/// Reads a line from the console into `buf`, echoing every byte back, and
+/// returns the line without the trailing newline.
+fn read_line(buf: &mut [u8]) -> &str {
+    let mut idx = 0;
+    let mut chunk = [0u8; 64];
+    loop {
+        let n = match read_console(&mut chunk) {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        for &b in &chunk[..n] {
+            match b {
+                b'\r' | b'\n' => {
+                    println!();
+                    return core::str::from_utf8(&buf[..idx]).unwrap_or("");
+                }
+                8 | 127 => {
+                    if idx > 0 {
+                        print!("{} {}", 8 as char, 8 as char);
+                        idx -= 1;
+                    }
+                }
+                b if idx < buf.len() && (b.is_ascii_graphic() || b == b' ') => {
+                    write(b);
+                    buf[idx] = b;
+                    idx += 1;
+                }
+                _ => write(7),
+            }
+        }
+    }
+}
+
+/// Splits `command` into its arguments, which are stored in `storage`.
+fn split<'a, 'b>(command: &'a str, storage: &'b mut [&'a str]) -> Result<StackVec<'b, &'a str>, ()> {
+    let mut args = StackVec::new(storage);
+    for arg in command.split(' ').filter(|a| !a.is_empty()) {
+        args.push(arg)?;
+    }
+    Ok(args)
+}
+
+/// Spawns the program named by `args[0]` with its input and output redirected
+/// to `stdin` and `stdout`, and returns its ID. A bare name such as `fib` runs
+/// `/fib.bin`; anything containing a `/` is used as a path.
+fn start(args: &[&str], stdin: Option<PipeDescriptor>, stdout: Option<PipeDescriptor>) -> Option<u64> {
+    let mut path_buf = [0u8; MAX_LINE];
+    let path = if args[0].contains('/') {
+        args[0]
+    } else {
+        let len = args[0].len();
+        if len + 5 > path_buf.len() {
+            println!("{}: name too long", args[0]);
+            return None;
+        }
+        path_buf[0] = b'/';
+        path_buf[1..len + 1].copy_from_slice(args[0].as_bytes());
+        path_buf[len + 1..len + 5].copy_from_slice(b".bin");
+        core::str::from_utf8(&path_buf[..len + 5]).unwrap_or("")
+    };
+
+    match spawn_with_stdio(path, args, stdin, stdout) {
+        Ok(pid) => Some(pid),
+        Err(OsError::NoEntry) => {
+            println!("{}: command not found", args[0]);
+            None
+        }
+        Err(e) => {
+            println!("{}: failed to spawn: {:?}", args[0], e);
+            None
+        }
+    }
+}
+
+/// Runs the commands of `stages`, each of whose output is the input of the
+/// next one, and waits for all of them to exit. A single command is a
+/// pipeline of one.
+fn run(stages: &[&[&str]]) {
+    let mut pids = [None; MAX_STAGES];
+    let mut input: Option<PipeDescriptor> = None;
+    for (i, args) in stages.iter().enumerate() {
+        let (output, next) = if i + 1 < stages.len() {
+            match pipe() {
+                Ok((read, write)) => (Some(write), Some(read)),
+                Err(e) => {
+                    println!("error: failed to create a pipe: {:?}", e);
+                    (None, None)
+                }
+            }
+        } else {
+            (None, None)
+        };
+
+        pids[i] = start(args, input, output);
+
+        // The children hold their own copies of the ends. Closing ours lets
+        // each reader see end-of-file once its writers have exited.
+        for &end in input.iter().chain(output.iter()) {
+            let _ = pipe_close(end);
+        }
+        input = next;
+    }
+
+    for (args, pid) in stages.iter().zip(pids.iter()) {
+        let pid = match *pid {
+            Some(pid) => pid,
+            None => continue,
+        };
+        match wait(pid) {
+            Ok(0) => (),
+            Ok(status) if status > signal_status(0) => {
+                println!("{}: terminated by signal {}", args[0], status - signal_status(0))
+            }
+            Ok(status) => println!("{}: exited with status {}", args[0], status),
+            Err(e) => println!("{}: failed to wait: {:?}", args[0], e),
+        }
+    }
+}
+
+/// Runs `line`, a pipeline of commands separated by `|`.
+fn run_pipeline(line: &str) {
+    let mut storage = [[""; MAX_ARGS]; MAX_STAGES];
+    let mut stages: [&[&str]; MAX_STAGES] = [&[]; MAX_STAGES];
+    let count = line.split('|').count();
+    if count > MAX_STAGES {
+        println!("error: too many commands in pipeline");
+        return;
+    }
+
+    for ((command, storage), stage) in line.split('|').zip(storage.iter_mut()).zip(stages.iter_mut()) {
+        let args = match split(command, storage) {
+            Ok(args) => args,
+            Err(()) => {
+                println!("error: too many arguments");
+                return;
+            }
+        };
+        if args.is_empty() {
+            println!("error: empty command in pipeline");
+            return;
+        }
+        *stage = args.into_slice();
+    }
+    run(&stages[..count]);
+}
+
+fn main() {
+    loop {
+        let mut cwd_buf = [0u8; MAX_LINE];
+        let cwd = getcwd(&mut cwd_buf).unwrap_or("?");
+        print!("{}> ", cwd);
+
+        let mut line_buf = [0u8; MAX_LINE];
+        let line = read_line(&mut line_buf);
+
+        if line.contains('|') {
+            run_pipeline(line);
+            continue;
+        }
+
+        let mut storage = [""; MAX_ARGS];
+        let args = match split(line, &mut storage) {
+            Ok(args) => args,
+            Err(()) => {
+                println!("error: too many arguments");
+                continue;
+            }
+        };
+        if args.is_empty() {
+            continue;
+        }
+
+        match args[0] {
+            "exit" => return,
+            "pwd" => println!("{}", cwd),
+            "echo" => {
+                for arg in &args[1..] {
+                    print!("{} ", arg);
+                }
+                println!();
+            }
+            "kill" => {
+                let pid = args.get(1).and_then(|pid| pid.parse().ok());
+                let sig = args.get(2).map_or(Some(SIGTERM), |sig| sig.parse().ok());
+                match (pid, sig) {
+                    (Some(pid), Some(sig)) => {
+                        if let Err(e) = kill(pid, sig) {
+                            println!("kill: {}: {:?}", pid, e);
+                        }
+                    }
+                    _ => println!("usage: kill <pid> [signal]"),
+                }
+            }
+            "idle" => {
+                let uptime = time();
+                let mut core = 0;
+                while let Ok((idle, count)) = idle_time(core) {
+                    let percent = idle.as_micros() * 100 / uptime.as_micros().max(1);
+                    println!("core {}: idle {} ms ({}%), {} times", core, idle.as_millis(), percent, count);
+                    core += 1;
+                }
+            }
+            "cd" => {
+                let dir = if args.len() > 1 { args[1] } else { "/" };
+                if let Err(e) = chdir(dir) {
+                    println!("cd: {}: {:?}", dir, e);
+                }
+            }
+            _ => run(&[args.as_slice()]),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use kernel_api::syscall::*;
+use kernel_api::{env, println, OsError};
+
+/// The word the parent and the child hand the region back and forth with.
+const TURN: usize = 0;
+/// Where the data starts, after the turn word.
+const DATA: usize = 4;
+const PARENT: u32 = 0;
+const CHILD: u32 = 1;
+
+fn turn(region: &[u8]) -> &AtomicU32 {
+    unsafe { &*(region[TURN..].as_ptr() as *const AtomicU32) }
+}
+
+/// Waits until it is `whose` turn to use the region.
+fn wait_turn(region: &[u8], whose: u32) {
+    let turn = turn(region);
+    loop {
+        let current = turn.load(Ordering::Acquire);
+        if current == whose {
+            return;
+        }
+        let _ = futex_wait(turn, current, None);
+    }
+}
+
+/// Hands the region to `whose`.
+fn give_turn(region: &[u8], whose: u32) {
+    turn(region).store(whose, Ordering::Release);
+    let _ = futex_wake(turn(region), 1);
+}
+
+/// Checks that a region created by one process is seen, not copied, by
+/// another, and that it can be unmapped.
+fn main() {
+    if let Some(id) = env::args().nth(1) {
+        child(id.parse().unwrap());
+        return;
+    }
+
+    run();
+    println!("shmtest: ok");
+}
+
+/// Doubles every data byte the parent wrote and hands the region back.
+fn child(id: u64) {
+    let region = shm_map(id, None).unwrap();
+    wait_turn(region, CHILD);
+    for byte in region[DATA..].iter_mut() {
+        *byte = byte.wrapping_mul(2);
+    }
+    give_turn(region, PARENT);
+    shm_unmap(region).unwrap();
+}
+
+fn run() {
+    let (id, region) = shm_create(2, None).unwrap();
+    assert!(region.len() >= 2 && region.iter().all(|&b| b == 0));
+    for (i, byte) in region[DATA..].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    let mut buf = [0; 20];
+    let child = spawn("/shmtest.bin", &["shmtest", env::format_u64(id, &mut buf)]).unwrap();
+    give_turn(region, CHILD);
+    wait_turn(region, PARENT);
+    assert_eq!(wait(child).unwrap(), 0);
+    for (i, &byte) in region[DATA..].iter().enumerate() {
+        assert_eq!(byte, (i as u8).wrapping_mul(2));
+    }
+
+    // An unknown ID cannot be mapped, and the region goes away with its last
+    // mapping.
+    assert_eq!(shm_map(id + 1, None).err(), Some(OsError::NoEntry));
+    shm_unmap(region).unwrap();
+    assert_eq!(shm_map(id, None).err(), Some(OsError::NoEntry));
+}
@@ -0,0 +1,75 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use core::ptr;
+use core::time::Duration;
+
+use kernel_api::syscall::*;
+use kernel_api::{env, println, signal_status, OsError};
+use kernel_api::{SIGKILL, SIGSEGV, SIGTERM, SIGUSR1, SIGUSR2, SIG_BLOCK, SIG_UNBLOCK};
+
+/// The signals `on_signal` has received, one bit per signal.
+static mut RECEIVED: u64 = 0;
+
+extern "C" fn on_signal(sig: u64) {
+    unsafe {
+        ptr::write_volatile(&mut RECEIVED, ptr::read_volatile(&RECEIVED) | 1 << sig);
+    }
+}
+
+fn received(sig: u64) -> bool {
+    unsafe { ptr::read_volatile(&RECEIVED) & 1 << sig != 0 }
+}
+
+/// Checks signal delivery, blocking, ignoring, faults and killing children.
+fn main() {
+    match env::args().nth(1) {
+        Some("segv") => {
+            unsafe { ptr::read_volatile(8 as *const u64) };
+            exit(0);
+        }
+        Some("sleep") => {
+            let _ = sleep(Duration::from_secs(60));
+            exit(0);
+        }
+        _ => (),
+    }
+
+    run();
+    println!("sigtest: ok");
+}
+
+fn run() {
+    let pid = getpid();
+
+    // A handler runs before `kill` returns to us.
+    sigaction(SIGUSR1, SigAction::Handler(on_signal), 0).unwrap();
+    kill(pid, SIGUSR1).unwrap();
+    assert!(received(SIGUSR1));
+
+    // A blocked signal waits until it is unblocked.
+    sigaction(SIGUSR2, SigAction::Handler(on_signal), 0).unwrap();
+    sigprocmask(SIG_BLOCK, 1 << SIGUSR2).unwrap();
+    kill(pid, SIGUSR2).unwrap();
+    assert!(!received(SIGUSR2));
+    sigprocmask(SIG_UNBLOCK, 1 << SIGUSR2).unwrap();
+    assert!(received(SIGUSR2));
+
+    // An ignored signal does nothing, and SIGKILL cannot be ignored.
+    sigaction(SIGTERM, SigAction::Ignore, 0).unwrap();
+    kill(pid, SIGTERM).unwrap();
+    assert_eq!(sigaction(SIGKILL, SigAction::Ignore, 0), Err(OsError::InvalidArgument));
+
+    // A fault terminates the process with SIGSEGV.
+    let child = spawn("/sigtest.bin", &["sigtest", "segv"]).unwrap();
+    assert_eq!(wait(child).unwrap(), signal_status(SIGSEGV));
+
+    // A signal interrupts a sleeping child and terminates it.
+    let child = spawn("/sigtest.bin", &["sigtest", "sleep"]).unwrap();
+    sleep(Duration::from_millis(200)).unwrap();
+    kill(child, SIGTERM).unwrap();
+    assert_eq!(wait(child).unwrap(), signal_status(SIGTERM));
+}
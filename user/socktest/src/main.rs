@@ -0,0 +1,94 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use core::time::Duration;
+
+use kernel_api::syscall::*;
+use kernel_api::{env, print, println, IpAddr, OsError, OsResult, PollFd, SocketDescriptor};
+use kernel_api::{POLL_IN, POLL_OUT};
+
+/// The port the server listens on.
+const PORT: u16 = 7000;
+
+/// The message the client sends to the server.
+const MESSAGE: &[u8] = b"hello over loopback";
+
+/// The byte the server sends back once it has received the whole message.
+const ACK: u8 = b'!';
+
+/// How long either side waits for the other.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs the server, which spawns a copy of this program as the client. The
+/// program exits with a non-zero status if the message does not arrive
+/// intact.
+fn main() {
+    let client = env::args().nth(1) == Some("client");
+    let result = if client { client_main() } else { server_main() };
+    match result {
+        Ok(()) if !client => println!("socktest: PASS"),
+        Ok(()) => (),
+        Err(error) => {
+            println!("socktest: FAIL: {:?}", error);
+            exit(1);
+        }
+    }
+}
+
+fn server_main() -> OsResult<()> {
+    let sock = sock_create()?;
+    sock_listen(sock, PORT)?;
+    let pid = spawn("/socktest.bin", &["socktest", "client"])?;
+
+    wait_for(sock, POLL_IN)?;
+
+    let mut buf = [0u8; 64];
+    let mut received = 0;
+    while received < MESSAGE.len() {
+        let n = sock_recv(sock, &mut buf[received..])?;
+        if n == 0 {
+            break;
+        }
+        received += n;
+    }
+    if &buf[..received] != MESSAGE {
+        return Err(OsError::IoErrorInvalidData);
+    }
+    sock_send(sock, &[ACK])?;
+
+    match wait(pid)? {
+        0 => Ok(()),
+        _ => Err(OsError::Unknown),
+    }
+}
+
+fn client_main() -> OsResult<()> {
+    let sock = sock_create()?;
+    sock_connect(sock, IpAddr::new((127, 0, 0, 1), PORT))?;
+    wait_for(sock, POLL_OUT)?;
+
+    let mut sent = 0;
+    while sent < MESSAGE.len() {
+        sent += sock_send(sock, &MESSAGE[sent..])?;
+    }
+
+    // Wait for the acknowledgement so that the message is not dropped with
+    // the socket when this process exits.
+    let mut ack = [0u8; 1];
+    match sock_recv(sock, &mut ack)? {
+        1 if ack[0] == ACK => Ok(()),
+        _ => Err(OsError::IoErrorInvalidData),
+    }
+}
+
+/// Waits up to `TIMEOUT` for `events` on `sock`.
+fn wait_for(sock: SocketDescriptor, events: u16) -> OsResult<()> {
+    let mut fds = [PollFd::socket(sock, events)];
+    match poll(&mut fds, Some(TIMEOUT))? {
+        0 => Err(OsError::IoErrorTimedOut),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,62 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use kernel_api::sync::{Condvar, Mutex};
+use kernel_api::syscall::*;
+use kernel_api::{println, OsError};
+
+const THREADS: usize = 4;
+const STACK_SIZE: usize = 16 * 1024;
+const ROUNDS: u64 = 1000;
+
+static mut STACKS: [[u8; STACK_SIZE]; THREADS] = [[0; STACK_SIZE]; THREADS];
+
+static COUNTER: Mutex<u64> = Mutex::new(0);
+static DONE: Mutex<usize> = Mutex::new(0);
+static ALL_DONE: Condvar = Condvar::new();
+
+/// Bumps the shared counter `ROUNDS` times, checks that the thread pointer is
+/// private to the thread, and returns its argument.
+extern "C" fn worker(arg: u64) -> u64 {
+    set_thread_pointer(arg);
+    for _ in 0..ROUNDS {
+        *COUNTER.lock() += 1;
+    }
+    if thread_pointer() != arg {
+        thread_exit(core::u64::MAX);
+    }
+
+    *DONE.lock() += 1;
+    ALL_DONE.notify_all();
+    arg * 2
+}
+
+/// Checks that threads share memory, run concurrently and can be joined.
+fn main() {
+    let mut tids = [0; THREADS];
+    for i in 0..THREADS {
+        let stack = unsafe { &mut STACKS[i] };
+        tids[i] = thread_create(worker, i as u64 + 1, stack).unwrap();
+    }
+
+    // Every worker signals the condition variable when it is done.
+    let mut done = DONE.lock();
+    while *done < THREADS {
+        done = ALL_DONE.wait(done);
+    }
+    drop(done);
+
+    for i in 0..THREADS {
+        assert_eq!(thread_join(tids[i]).unwrap(), (i as u64 + 1) * 2);
+    }
+    assert_eq!(*COUNTER.lock(), THREADS as u64 * ROUNDS);
+
+    // A thread is joined once, and a thread cannot join itself.
+    assert_eq!(thread_join(tids[0]), Err(OsError::NoEntry));
+    assert_eq!(thread_join(getpid()), Err(OsError::NoEntry));
+
+    println!("threads: ok");
+}
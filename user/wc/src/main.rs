@@ -0,0 +1,39 @@
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+mod cr0;
+
+use kernel_api::syscall::*;
+use kernel_api::println;
+
+/// Counts the lines, words and bytes of its input and prints them. The input
+/// is read until end-of-file, so `wc` is meant to read from a pipe, as in
+/// `fib | wc`.
+fn main() {
+    let mut buf = [0u8; 256];
+    let (mut lines, mut words, mut bytes) = (0, 0, 0);
+    let mut in_word = false;
+    loop {
+        let n = match read_console(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(error) => {
+                println!("wc: {:?}", error);
+                exit(1);
+            }
+        };
+        for &b in &buf[..n] {
+            if b == b'\n' {
+                lines += 1;
+            }
+            let space = b == b' ' || b == b'\t' || b == b'\r' || b == b'\n';
+            if !space && !in_word {
+                words += 1;
+            }
+            in_word = !space;
+        }
+        bytes += n;
+    }
+    println!("{} {} {}", lines, words, bytes);
+}